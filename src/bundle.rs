@@ -0,0 +1,219 @@
+use crate::{PigError, PigResult};
+use serde_json::{json, Map, Value as Json};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Bundles a resolved document (with full reference metadata) into a standalone one.
+#[derive(Debug)]
+pub struct Bundle {
+    /// The prefix of metadata keys.
+    prefix: String,
+    /// The components where references go, by `$ref`.
+    locations: BTreeMap<String, (String, String)>,
+    /// Components of the main file, or added.
+    taken: BTreeSet<(String, String)>,
+    /// Components to add.
+    components: BTreeMap<(String, String), Json>,
+}
+
+impl Bundle {
+    const METADATA: [&'static str; 6] = ["ref", "file", "keys", "name", "circular", "shared"];
+
+    pub fn new(prefix: Option<&str>) -> Self {
+        Self {
+            prefix: prefix.unwrap_or("$").into(),
+            locations: BTreeMap::new(),
+            taken: BTreeSet::new(),
+            components: BTreeMap::new(),
+        }
+    }
+
+    fn key(&self, name: &str) -> String {
+        format!("{}{name}", self.prefix)
+    }
+
+    /// Removes reference metadata, failing on recursive references.
+    pub fn dereference(&self, value: &mut Json) -> PigResult<()> {
+        match value {
+            Json::Array(values) => {
+                for value in values {
+                    self.dereference(value)?;
+                }
+            }
+            Json::Object(object) => {
+                if object.contains_key(&self.key("circular")) {
+                    return Err(PigError::InvalidRef(format!(
+                        "{} (recursive, use `--components`)",
+                        object[&self.key("ref")].as_str().unwrap_or_default()
+                    )));
+                }
+
+                self.strip(object);
+
+                for value in object.values_mut() {
+                    self.dereference(value)?;
+                }
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// Turns resolved references back into `$ref`s to `components` (where they were, when
+    /// possible), `root` being the main file, as in `$file`.
+    pub fn rebase(mut self, openapi: &mut Json, root: &str) {
+        for (section, components) in openapi["components"]
+            .as_object()
+            .into_iter()
+            .flatten()
+            .filter_map(|(section, components)| Some((section, components.as_object()?)))
+        {
+            for (name, component) in components {
+                let location = (section.clone(), name.clone());
+                let pointer = format!("{root}#/components/{section}/{}", Self::escape(name));
+
+                if let Some(path) = component.get(self.key("ref")).and_then(Json::as_str) {
+                    self.locations.insert(path.into(), location.clone());
+                }
+
+                self.locations.insert(pointer, location.clone());
+                self.taken.insert(location);
+            }
+        }
+
+        self.walk(openapi, &mut Vec::new());
+
+        for ((section, name), component) in std::mem::take(&mut self.components) {
+            openapi["components"][section][name] = component;
+        }
+    }
+
+    fn walk(&mut self, value: &mut Json, pointer: &mut Vec<String>) {
+        match value {
+            Json::Array(values) => {
+                for (i, value) in values.iter_mut().enumerate() {
+                    pointer.push(i.to_string());
+                    self.walk(value, pointer);
+                    pointer.pop();
+                }
+            }
+            Json::Object(object) => {
+                let Some(path) = object
+                    .get(&self.key("ref"))
+                    .and_then(Json::as_str)
+                    .map(String::from)
+                else {
+                    return self.children(object, pointer);
+                };
+
+                if object.contains_key(&self.key("circular")) {
+                    if let Some((section, name)) = self.locations.get(&path) {
+                        *value = Self::reference(section, name);
+                    }
+
+                    return;
+                }
+
+                let location = match self.locations.get(&path) {
+                    Some(location) => location.clone(),
+                    None => {
+                        let keys = object
+                            .get(&self.key("keys"))
+                            .and_then(Json::as_array)
+                            .into_iter()
+                            .flatten()
+                            .filter_map(Json::as_str)
+                            .collect::<Vec<_>>();
+                        // Path items stay where they are
+                        let Some(section) = (match keys[..] {
+                            ["components", section, _] => Some(section),
+                            _ => Self::section(pointer),
+                        }) else {
+                            self.strip(object);
+                            return self.children(object, pointer);
+                        };
+                        let name = object
+                            .get(&self.key("name"))
+                            .and_then(Json::as_str)
+                            .unwrap_or("Component");
+                        let location = (1..)
+                            .map(|i| match i {
+                                1 => (section.to_string(), name.to_string()),
+                                i => (section.to_string(), format!("{name}{i}")),
+                            })
+                            .find(|location| !self.taken.contains(location))
+                            .unwrap();
+
+                        self.taken.insert(location.clone());
+                        self.locations.insert(path, location.clone());
+
+                        let mut component = object.clone();
+                        let mut pointer = vec!["components".into(), location.0.clone()];
+                        pointer.push(location.1.clone());
+                        self.strip(&mut component);
+                        self.children(&mut component, &mut pointer);
+                        self.components.insert(location.clone(), component.into());
+
+                        location
+                    }
+                };
+                let (section, name) = &location;
+
+                // The component itself
+                if pointer[..] == ["components", section.as_str(), name.as_str()] {
+                    self.strip(object);
+                    return self.children(object, pointer);
+                }
+
+                *value = Self::reference(section, name);
+            }
+            _ => {}
+        }
+    }
+
+    fn children(&mut self, object: &mut Map<String, Json>, pointer: &mut Vec<String>) {
+        for (key, value) in object.iter_mut() {
+            pointer.push(key.clone());
+            self.walk(value, pointer);
+            pointer.pop();
+        }
+    }
+
+    /// The section of `components` for a reference at `pointer`, if any.
+    fn section(pointer: &[String]) -> Option<&'static str> {
+        if pointer
+            .iter()
+            .any(|key| key == "schema" || key == "schemas")
+        {
+            return Some("schemas");
+        }
+
+        let keys = pointer.iter().map(String::as_str).collect::<Vec<_>>();
+
+        match keys[..] {
+            ["paths" | "webhooks", _] | [.., "callbacks", _, _] => None,
+            [.., "parameters", _] => Some("parameters"),
+            [.., "responses", _] => Some("responses"),
+            [.., "requestBody"] => Some("requestBodies"),
+            [.., "headers", _] => Some("headers"),
+            [.., "examples", _] => Some("examples"),
+            [.., "links", _] => Some("links"),
+            [.., "callbacks", _] => Some("callbacks"),
+            _ => Some("schemas"),
+        }
+    }
+
+    fn strip(&self, object: &mut Map<String, Json>) {
+        for name in Self::METADATA {
+            object.remove(&self.key(name));
+        }
+    }
+
+    fn reference(section: &str, name: &str) -> Json {
+        json!({ "$ref": format!("#/components/{section}/{}", Self::escape(name)) })
+    }
+
+    fn escape(key: &str) -> String {
+        key.replace('~', "~0").replace('/', "~1")
+    }
+}
@@ -0,0 +1,398 @@
+use crate::{
+    config::{Lint, Severity},
+    derive::Derive,
+    PigWarning,
+};
+use serde_json::Value as Json;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// A lint rule violation.
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub rule: &'static str,
+    pub severity: Severity,
+    /// Where, e.g. a JSON Pointer or `GET /pets`.
+    pub at: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {}: {} ({})",
+            self.severity, self.at, self.message, self.rule
+        )
+    }
+}
+
+impl From<Diagnostic> for PigWarning {
+    fn from(diagnostic: Diagnostic) -> Self {
+        Self::Lint(diagnostic.rule.into(), diagnostic.at, diagnostic.message)
+    }
+}
+
+/// A naming style: its name, allowed characters and whether it starts uppercase.
+type Style = (&'static str, fn(char) -> bool, bool);
+
+#[derive(Debug)]
+pub enum Check {}
+
+impl Check {
+    pub fn check(openapi: &Json) -> Vec<PigWarning> {
+        let mut warnings = Vec::new();
+
+        Self::operation_ids(openapi, &mut warnings);
+        Self::paths(openapi, &mut warnings);
+        Self::components(openapi, &mut warnings);
+
+        warnings
+    }
+
+    /// Lints `summary` and `description` fields (once per distinct text) and the document's
+    /// structure, `components` being the referenced components of the main file.
+    pub fn lint(openapi: &Json, lint: &Lint, components: &BTreeSet<String>) -> Vec<Diagnostic> {
+        fn texts<'a>(value: &'a Json, pointer: String, found: &mut Vec<(String, &'a str)>) {
+            match value {
+                Json::Array(values) => {
+                    for (i, value) in values.iter().enumerate() {
+                        texts(value, format!("{pointer}/{i}"), found);
+                    }
+                }
+                Json::Object(object) => {
+                    for (key, value) in object {
+                        let pointer = format!("{pointer}/{}", Check::escape(key));
+
+                        match (key.as_str(), value) {
+                            ("summary" | "description", Json::String(text)) => {
+                                found.push((pointer, text))
+                            }
+                            _ => texts(value, pointer, found),
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let mut diagnostics = Vec::new();
+        let mut push = |rule: &'static str, at: String, message: String| {
+            let severity = lint.severity(rule);
+
+            if severity != Severity::Off {
+                diagnostics.push(Diagnostic {
+                    rule,
+                    severity,
+                    at,
+                    message,
+                });
+            }
+        };
+        let mut seen = BTreeSet::new();
+        let mut all = Vec::new();
+        texts(openapi, String::new(), &mut all);
+
+        for (pointer, text) in all {
+            if !seen.insert(text) {
+                continue;
+            }
+
+            let lowercase = text.to_lowercase();
+            let words = lowercase
+                .split(|char: char| !char.is_alphanumeric())
+                .collect::<BTreeSet<_>>();
+
+            for banned in &lint.banned_words {
+                if words.contains(banned.to_lowercase().as_str()) {
+                    push(
+                        "banned-words",
+                        pointer.clone(),
+                        format!("banned word {banned}"),
+                    );
+                }
+            }
+
+            if lint.sentence_case
+                && text
+                    .chars()
+                    .find(|char| char.is_alphabetic())
+                    .is_some_and(char::is_lowercase)
+            {
+                push("sentence-case", pointer.clone(), "not sentence case".into());
+            }
+
+            if let Some(max) = lint.max_length {
+                let length = text.chars().count();
+
+                if length > max {
+                    push(
+                        "max-length",
+                        pointer.clone(),
+                        format!("{length} characters, over {max}"),
+                    );
+                }
+            }
+        }
+
+        let mut operation_ids = Vec::new();
+
+        for (path, item) in openapi
+            .get("paths")
+            .and_then(Json::as_object)
+            .into_iter()
+            .flatten()
+        {
+            for method in Derive::METHODS {
+                let Some(operation) = item.get(method) else {
+                    continue;
+                };
+                let at = format!("{} {path}", method.to_uppercase());
+                let is_internal = operation.get("x-internal") == Some(&Json::Bool(true));
+                let is_documented = ["summary", "description"].iter().any(|key| {
+                    operation
+                        .get(key)
+                        .and_then(Json::as_str)
+                        .is_some_and(|text| !text.trim().is_empty())
+                });
+
+                if !is_internal && !is_documented {
+                    push(
+                        "operation-description",
+                        at.clone(),
+                        "no summary nor description".into(),
+                    );
+                }
+
+                match operation.get("operationId").and_then(Json::as_str) {
+                    Some(id) => operation_ids.push((at.clone(), id.to_string())),
+                    None => push("operation-id", at.clone(), "no operationId".into()),
+                }
+
+                if operation
+                    .get("tags")
+                    .and_then(Json::as_array)
+                    .is_none_or(Vec::is_empty)
+                {
+                    push("operation-tags", at, "no tags".into());
+                }
+            }
+        }
+
+        // Discriminator mappings reference components too
+        let mut mapped = BTreeSet::new();
+        Self::mappings(openapi, &mut mapped);
+
+        for (section, names) in openapi
+            .get("components")
+            .and_then(Json::as_object)
+            .into_iter()
+            .flatten()
+        {
+            let names = names
+                .as_object()
+                .into_iter()
+                .flat_map(|names| names.keys())
+                .map(|name| {
+                    let pointer = format!("/components/{section}/{}", Self::escape(name));
+                    (pointer, name.clone())
+                })
+                .collect::<Vec<_>>();
+
+            // Security schemes are referenced by name
+            if section != "securitySchemes" {
+                for (pointer, _) in &names {
+                    if !components.contains(pointer) && !mapped.contains(pointer) {
+                        push("unused-components", pointer.clone(), "unused".into());
+                    }
+                }
+            }
+
+            for (at, message) in Self::naming(&format!("components/{section}"), names) {
+                push("naming", at, message);
+            }
+        }
+
+        for (at, message) in Self::naming("operationIds", operation_ids) {
+            push("naming", at, message);
+        }
+
+        diagnostics
+    }
+
+    /// Names (with where they are) not in the naming style of most others.
+    fn naming(kind: &str, names: Vec<(String, String)>) -> Vec<(String, String)> {
+        let styles: [Style; 5] = [
+            ("PascalCase", char::is_alphanumeric, true),
+            ("camelCase", char::is_alphanumeric, false),
+            (
+                "snake_case",
+                |char| char.is_lowercase() || char.is_numeric() || char == '_',
+                false,
+            ),
+            (
+                "kebab-case",
+                |char| char.is_lowercase() || char.is_numeric() || char == '-',
+                false,
+            ),
+            (
+                "SCREAMING_CASE",
+                |char| char.is_uppercase() || char.is_numeric() || char == '_',
+                true,
+            ),
+        ];
+        let is = |(_, chars, uppercase): &Style, name: &str| {
+            name.chars()
+                .next()
+                .is_some_and(|first| first.is_uppercase() == *uppercase)
+                && name.chars().all(chars)
+        };
+
+        let Some(style) = styles
+            .iter()
+            .max_by_key(|style| {
+                // The first style, on ties
+                let count = names.iter().filter(|(_, name)| is(style, name)).count();
+                (count, std::cmp::Reverse(style.0))
+            })
+            .filter(|_| names.len() > 1)
+        else {
+            return Vec::new();
+        };
+
+        names
+            .into_iter()
+            .filter(|(_, name)| !is(style, name))
+            .map(|(at, name)| (at, format!("{name} is not {}, unlike most {kind}", style.0)))
+            .collect()
+    }
+
+    /// Pointers of local discriminator mappings, e.g. `/components/schemas/Cat`.
+    fn mappings(value: &Json, found: &mut BTreeSet<String>) {
+        match value {
+            Json::Array(values) => {
+                for value in values {
+                    Self::mappings(value, found);
+                }
+            }
+            Json::Object(object) => {
+                let mappings = object
+                    .get("discriminator")
+                    .and_then(|discriminator| discriminator.get("mapping"))
+                    .and_then(Json::as_object);
+
+                for mapping in mappings.into_iter().flat_map(|mapping| mapping.values()) {
+                    if let Some(pointer) = mapping.as_str().and_then(|path| path.strip_prefix('#'))
+                    {
+                        found.insert(pointer.into());
+                    }
+                }
+
+                for value in object.values() {
+                    Self::mappings(value, found);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn escape(key: &str) -> String {
+        key.replace('~', "~0").replace('/', "~1")
+    }
+
+    fn operation_ids(openapi: &Json, warnings: &mut Vec<PigWarning>) {
+        let mut operations = BTreeMap::<_, Vec<_>>::new();
+
+        for (path, item) in openapi
+            .get("paths")
+            .and_then(Json::as_object)
+            .into_iter()
+            .flatten()
+        {
+            for method in Derive::METHODS {
+                if let Some(id) = item
+                    .get(method)
+                    .and_then(|operation| operation.get("operationId"))
+                    .and_then(Json::as_str)
+                {
+                    operations
+                        .entry(id)
+                        .or_default()
+                        .push(format!("{} {path}", method.to_uppercase()));
+                }
+            }
+        }
+
+        for (id, operations) in operations {
+            if operations.len() > 1 {
+                warnings.push(PigWarning::DuplicateOperationId(id.into(), operations));
+            }
+        }
+    }
+
+    /// Paths only differing by parameter names, e.g. `/pets/{id}` and `/pets/{petId}`.
+    fn paths(openapi: &Json, warnings: &mut Vec<PigWarning>) {
+        let mut paths = BTreeMap::<_, Vec<_>>::new();
+
+        for path in openapi
+            .get("paths")
+            .and_then(Json::as_object)
+            .into_iter()
+            .flat_map(|paths| paths.keys())
+        {
+            let mut normalized = String::new();
+            let mut parameter = false;
+
+            for char in path.chars() {
+                match char {
+                    '{' => {
+                        parameter = true;
+                        normalized.push_str("{}");
+                    }
+                    '}' => parameter = false,
+                    char if !parameter => normalized.push(char),
+                    _ => {}
+                }
+            }
+
+            paths.entry(normalized).or_default().push(path.clone());
+        }
+
+        for (_, paths) in paths {
+            if paths.len() > 1 {
+                warnings.push(PigWarning::CollidingPaths(paths));
+            }
+        }
+    }
+
+    /// Component names only differing by case or separators, e.g. `PetId` and `pet_id`.
+    fn components(openapi: &Json, warnings: &mut Vec<PigWarning>) {
+        for (kind, components) in openapi
+            .get("components")
+            .and_then(Json::as_object)
+            .into_iter()
+            .flatten()
+        {
+            let mut names = BTreeMap::<_, Vec<_>>::new();
+
+            for name in components
+                .as_object()
+                .into_iter()
+                .flat_map(|map| map.keys())
+            {
+                let normalized = name
+                    .chars()
+                    .filter(|char| char.is_alphanumeric())
+                    .flat_map(char::to_lowercase)
+                    .collect::<String>();
+
+                names.entry(normalized).or_default().push(name.clone());
+            }
+
+            for (_, names) in names {
+                if names.len() > 1 {
+                    warnings.push(PigWarning::CollidingComponents(kind.clone(), names));
+                }
+            }
+        }
+    }
+}
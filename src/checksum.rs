@@ -0,0 +1,69 @@
+use std::path::Path;
+
+/// Content hashes embedded in generated files, as a trailing comment, to detect hand edits.
+#[derive(Debug)]
+pub enum Checksum {}
+
+impl Checksum {
+    const MARKER: &'static str = "pig-sha256:";
+
+    /// The SHA-256 of `bytes`, in hex.
+    pub fn sha256(bytes: &[u8]) -> String {
+        ring::digest::digest(&ring::digest::SHA256, bytes)
+            .as_ref()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
+
+    /// The comment delimiters of the language of `path`, if it has comments.
+    fn comment(path: &Path) -> Option<(&'static str, &'static str)> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+
+        Some(match extension.as_str() {
+            "rs" | "c" | "h" | "cc" | "cpp" | "hpp" | "cs" | "go" | "java" | "kt" | "kts"
+            | "scala" | "swift" | "dart" | "js" | "mjs" | "cjs" | "jsx" | "ts" | "tsx" | "php"
+            | "proto" | "zig" => ("//", ""),
+            "py" | "rb" | "sh" | "bash" | "zsh" | "yaml" | "yml" | "toml" | "ex" | "exs" | "r"
+            | "pl" | "tf" | "dockerfile" | "mk" | "cmake" | "nix" | "cfg" | "ini" | "conf" => {
+                ("#", "")
+            }
+            "sql" | "lua" | "hs" | "elm" => ("--", ""),
+            "md" | "html" | "htm" | "xml" | "svg" | "vue" => ("<!--", " -->"),
+            "css" | "scss" | "less" => ("/*", " */"),
+            "erl" | "hrl" | "tex" => ("%", ""),
+            "clj" | "cljs" | "lisp" | "el" => (";;", ""),
+            _ => return None,
+        })
+    }
+
+    /// `content` with a trailing comment holding its hash, if the language of `path` has
+    /// comments.
+    pub fn sign(path: &Path, content: String) -> String {
+        let Some((open, close)) = Self::comment(path) else {
+            return content;
+        };
+        let mut content = content;
+
+        if !content.is_empty() && !content.ends_with('\n') {
+            content.push('\n');
+        }
+
+        let hash = Self::sha256(content.as_bytes());
+        content.push_str(&format!("{open} {} {hash}{close}\n", Self::MARKER));
+
+        content
+    }
+
+    /// Whether `content` matches its embedded hash (with nothing after it), if it has one.
+    pub fn check(content: &str) -> Option<bool> {
+        let marker = content.rfind(Self::MARKER)?;
+        let start = content[..marker].rfind('\n').map_or(0, |i| i + 1);
+        let (content, trailer) = content.split_at(start);
+        let (trailer, rest) = trailer.split_once('\n').unwrap_or((trailer, ""));
+        let (_, hash) = trailer.split_once(Self::MARKER)?;
+        let hash = hash.split_whitespace().next()?;
+
+        Some(rest.is_empty() && Self::sha256(content.as_bytes()) == hash)
+    }
+}
@@ -0,0 +1,722 @@
+use crate::{
+    bundle::Bundle,
+    check::Check,
+    checksum::Checksum,
+    config::{Config, ConfigEntry, RefMetadata, Severity, Validate},
+    diff::diff,
+    explain::Explain,
+    extract::Extract,
+    ide::Ide,
+    journal::Journal,
+    manifest::Manifest,
+    output::Output,
+    pig::Pig,
+    resolver::Resolved,
+    resolver::Resolver,
+    spec_diff::spec_diff,
+    split::Split,
+    Args, PigError, PigResult, PigWarning,
+};
+use clap::{Subcommand, ValueEnum};
+use serde_json::{json, Value};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::read_dir,
+    path::{Path, PathBuf},
+    process::Command as Process,
+};
+use tera::Tera;
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Write an entry's resolved `OpenAPI` document to a single file, without rendering
+    Bundle {
+        /// Entry name
+        entry: String,
+        /// Output file (JSON for `.json`, else YAML)
+        output: PathBuf,
+        /// Keep references, to `components` (instead of dereferencing everything)
+        #[arg(long)]
+        components: bool,
+    },
+    /// Inspect the configuration
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// Explain the differences between two archived runs (see `--archive-contexts`)
+    ContextDiff {
+        /// Old run directory
+        old: PathBuf,
+        /// New run directory
+        new: PathBuf,
+    },
+    /// Report the changes between two `OpenAPI` documents, failing on breaking ones
+    DiffSpec {
+        /// Old document, or `<REV>:<PATH>` for a git revision (path relative to the current
+        /// directory)
+        old: String,
+        /// New document, or `<REV>:<PATH>`
+        new: String,
+        #[arg(long, value_enum, default_value_t)]
+        format: Format,
+    },
+    /// Write the smallest standalone document with some paths, operations and schemas of an
+    /// entry, e.g. to share a minimal reproduction
+    Extract {
+        /// Entry name (defaults to the first entry)
+        #[arg(long)]
+        entry: Option<String>,
+        /// Path to keep, with all its operations (repeatable)
+        #[arg(long = "path", value_name = "PATH")]
+        paths: Vec<String>,
+        /// `operationId` of an operation to keep (repeatable)
+        #[arg(long = "operation", value_name = "ID")]
+        operations: Vec<String>,
+        /// Name of a schema of `components` to keep (repeatable)
+        #[arg(long = "schema", value_name = "NAME")]
+        schemas: Vec<String>,
+        /// Output file (JSON for `.json`, else YAML)
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    /// Explain an error code (e.g. `E011`), or list them
+    Explain {
+        /// Error code
+        code: Option<String>,
+    },
+    /// Write a single-file document as a directory tree, one file per path item and component
+    Split {
+        /// Document
+        input: PathBuf,
+        /// Output directory
+        output: PathBuf,
+    },
+    /// Lint entries' `OpenAPI` documents, with the rules of their `lint`
+    Lint {
+        /// Entry names (defaults to all)
+        entries: Vec<String>,
+        #[arg(long, value_enum, default_value_t)]
+        format: Format,
+    },
+    /// Validate entries' `OpenAPI` documents, reporting every problem, without generating
+    Validate {
+        /// Entry names (defaults to all)
+        entries: Vec<String>,
+    },
+    /// Report generated files edited since generation (with `checksums: true`)
+    Verify {
+        /// Entry names (defaults to all)
+        entries: Vec<String>,
+    },
+    /// Finish (or undo) a clean interrupted by a crash, from its journal
+    Recover {
+        /// Move trashed files back instead
+        #[arg(long)]
+        undo: bool,
+    },
+    /// Print the files of an entry's `OpenAPI` document, as a tree of references
+    Deps {
+        /// Entry name
+        entry: String,
+    },
+    /// Serve context completions and definitions to editors (LSP-like JSON-RPC)
+    Ide {
+        /// Communicate over stdin and stdout (the only transport)
+        #[arg(long, required = true)]
+        stdio: bool,
+    },
+}
+
+impl Command {
+    pub fn run(self, args: Args) -> PigResult<()> {
+        match self {
+            Self::Bundle {
+                entry,
+                output,
+                components,
+            } => Self::bundle(args, entry, output, components),
+            Self::Config {
+                command: ConfigCommand::Show { entry, format },
+            } => Self::config_show(args, entry, format),
+            Self::ContextDiff { old, new } => Self::context_diff(old, new),
+            Self::Deps { entry } => Self::deps(args, entry),
+            Self::DiffSpec { old, new, format } => Self::diff_spec(old, new, format),
+            Self::Extract {
+                entry,
+                paths,
+                operations,
+                schemas,
+                output,
+            } => {
+                let config = Config::new(args)?;
+                let entry = match &entry {
+                    Some(entry) => config.entries.iter().find(|config| config.name == *entry),
+                    None => config.entries.first(),
+                }
+                .ok_or_else(|| PigError::UnknownEntry(entry.unwrap_or_default()))?;
+                let openapi = Self::bundled(entry, true)?;
+                let openapi = Extract::subset(&openapi, &paths, &operations, &schemas)?;
+                Self::write(&output, &openapi)?;
+
+                Output::info(format!(
+                    "Extracted {} into {}",
+                    entry.name,
+                    output.display()
+                ));
+
+                Ok(())
+            }
+            Self::Explain { code } => Explain::print(code.as_deref()),
+            Self::Ide { .. } => Ide::new(Config::new(args)?).run(),
+            Self::Lint { entries, format } => Self::lint(args, entries, format),
+            Self::Recover { undo } => {
+                let config = Config::new(args)?;
+                let root = config
+                    .file
+                    .parent()
+                    .ok_or_else(|| PigError::InvalidPath(config.file.clone()))?;
+                let Some(journal) = Journal::read(root)? else {
+                    Output::info("Nothing to recover");
+                    return Ok(());
+                };
+                let trash = journal.trash.clone();
+
+                if undo {
+                    let count = journal.undo()?;
+                    Output::info(format!("Restored {count} files from {}", trash.display()));
+                } else {
+                    let count = journal.complete()?;
+                    Output::info(format!("Trashed {count} more files to {}", trash.display()));
+                }
+
+                Ok(())
+            }
+            Self::Validate { entries } => Self::validate(args, entries),
+            Self::Verify { entries } => Self::verify(args, entries),
+            Self::Split { input, output } => {
+                let files = Split::new(&input, &output, args.force)?.write()?;
+                Output::info(format!("Wrote {files} files to {}", output.display()));
+
+                Ok(())
+            }
+        }
+    }
+
+    fn bundle(args: Args, entry: String, output: PathBuf, components: bool) -> PigResult<()> {
+        let config = Config::new(args)?;
+        let entry = config
+            .entries
+            .iter()
+            .find(|config| config.name == entry)
+            .ok_or(PigError::UnknownEntry(entry))?;
+        let openapi = Self::bundled(entry, components)?;
+        Self::write(&output, &openapi)?;
+
+        Output::info(format!("Bundled {} into {}", entry.name, output.display()));
+
+        Ok(())
+    }
+
+    /// The resolved document of `entry`, standalone, with references to `components` kept or
+    /// not.
+    fn bundled(entry: &ConfigEntry, components: bool) -> PigResult<Value> {
+        let resolver = Pig::resolver(entry, &mut Tera::default())?
+            .metadata(RefMetadata::Full, entry.file.parent())
+            .depth(None);
+        let Resolved {
+            mut openapi,
+            warnings,
+            root,
+            ..
+        } = Pig::document(entry, resolver)?;
+
+        for warning in warnings {
+            Output::warn(warning);
+        }
+
+        let bundle = Bundle::new(entry.ref_prefix.as_deref());
+        if components {
+            bundle.rebase(&mut openapi, &root);
+        } else {
+            bundle.dereference(&mut openapi)?;
+        }
+
+        Ok(openapi)
+    }
+
+    /// Writes a document, as JSON for `.json` files, else YAML.
+    fn write(output: &Path, openapi: &Value) -> PigResult<()> {
+        if output
+            .extension()
+            .is_some_and(|extension| extension == "json")
+        {
+            std::fs::write(output, serde_json::to_string_pretty(openapi)?)?;
+        } else {
+            std::fs::write(output, serde_yaml::to_string(openapi)?)?;
+        }
+
+        Ok(())
+    }
+
+    fn lint(args: Args, entries: Vec<String>, format: Format) -> PigResult<()> {
+        let config = Config::new(args)?;
+
+        for name in &entries {
+            if !config.entries.iter().any(|entry| entry.name == *name) {
+                return Err(PigError::UnknownEntry(name.clone()));
+            }
+        }
+
+        // Stdout is for the JSON
+        if let Format::Json = format {
+            Output::stderr();
+        }
+
+        let mut diagnostics = Vec::new();
+        let mut failed = Vec::new();
+
+        for entry in config
+            .entries
+            .iter()
+            .filter(|entry| entries.is_empty() || entries.contains(&entry.name))
+        {
+            let resolved = Pig::document(entry, Pig::resolver(entry, &mut Tera::default())?)?;
+            let lint = entry.lint.clone().unwrap_or_default();
+
+            for warning in &resolved.warnings {
+                Output::warn(warning);
+            }
+
+            for diagnostic in Check::lint(&resolved.openapi, &lint, &resolved.components()) {
+                if diagnostic.severity == Severity::Error && !failed.contains(&entry.name) {
+                    failed.push(entry.name.clone());
+                }
+
+                diagnostics.push((entry.name.clone(), diagnostic));
+            }
+        }
+
+        match format {
+            Format::Text if diagnostics.is_empty() => Output::info("No lint issues"),
+            Format::Text => {
+                for (entry, diagnostic) in &diagnostics {
+                    Output::line(format!("{entry}: {diagnostic}"));
+                }
+            }
+            Format::Json => {
+                let diagnostics = diagnostics
+                    .iter()
+                    .map(|(entry, diagnostic)| {
+                        json!({
+                            "entry": entry,
+                            "rule": diagnostic.rule,
+                            "severity": diagnostic.severity,
+                            "at": diagnostic.at,
+                            "message": diagnostic.message,
+                        })
+                    })
+                    .collect::<Vec<_>>();
+
+                Output::data(serde_json::to_string_pretty(&diagnostics)?);
+            }
+        }
+
+        let errors = diagnostics
+            .iter()
+            .filter(|(_, diagnostic)| diagnostic.severity == Severity::Error)
+            .count();
+
+        if errors > 0 {
+            return Err(PigError::LintErrors(failed.join(", "), errors));
+        }
+
+        Ok(())
+    }
+
+    fn validate(args: Args, entries: Vec<String>) -> PigResult<()> {
+        let config = Config::new(args)?;
+
+        for name in &entries {
+            if !config.entries.iter().any(|entry| entry.name == *name) {
+                return Err(PigError::UnknownEntry(name.clone()));
+            }
+        }
+
+        let mut problems = 0;
+
+        for entry in config
+            .entries
+            .iter()
+            .filter(|entry| entries.is_empty() || entries.contains(&entry.name))
+        {
+            // Invalid files are reported all at once rather than failing on the first one
+            let resolver = Pig::resolver(entry, &mut Tera::default())?;
+            let resolver = match entry.validate {
+                Validate::Strict => resolver.validate(Validate::Lenient),
+                validate => resolver.validate(validate),
+            };
+            let resolved = match Pig::document(entry, resolver) {
+                Ok(resolved) => resolved,
+                Err(err) => {
+                    Output::error(format!("{}: {err}", entry.name));
+                    problems += 1;
+                    continue;
+                }
+            };
+            let mut valid = true;
+
+            for warning in resolved.warnings {
+                if let PigWarning::InvalidOpenapi(_, _) = warning {
+                    Output::error(format!("{}: {warning}", entry.name));
+                    problems += 1;
+                    valid = false;
+                } else {
+                    Output::warn(format!("{}: {warning}", entry.name));
+                }
+            }
+
+            for warning in Check::check(&resolved.openapi) {
+                Output::warn(format!("{}: {warning}", entry.name));
+            }
+
+            if valid {
+                Output::info(format!("{}: valid", entry.name));
+            }
+        }
+
+        if problems > 0 {
+            return Err(PigError::Invalid(problems));
+        }
+
+        Ok(())
+    }
+
+    fn verify(args: Args, entries: Vec<String>) -> PigResult<()> {
+        let config = Config::new(args)?;
+
+        for name in &entries {
+            if !config.entries.iter().any(|entry| entry.name == *name) {
+                return Err(PigError::UnknownEntry(name.clone()));
+            }
+        }
+
+        let mut edited = 0;
+
+        for entry in config
+            .entries
+            .iter()
+            .filter(|entry| entries.is_empty() || entries.contains(&entry.name))
+        {
+            let manifest = Manifest::read(entry).unwrap_or_default();
+            let mut verified = 0;
+
+            for file in &manifest.files {
+                let path = entry.output.join(file);
+
+                // Files without hash (e.g. JSON) are not checked
+                match std::fs::read_to_string(&path).map(|content| Checksum::check(&content)) {
+                    Ok(Some(true)) => verified += 1,
+                    Ok(Some(false)) => {
+                        Output::error(format!("{}: edited {}", entry.name, path.display()));
+                        edited += 1;
+                    }
+                    Ok(None) => {}
+                    Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                        Output::warn(format!("{}: missing {}", entry.name, path.display()));
+                    }
+                    Err(err) => return Err(err.into()),
+                }
+            }
+
+            Output::info(format!("{}: {verified} files verified", entry.name));
+        }
+
+        if edited > 0 {
+            return Err(PigError::Edited(edited));
+        }
+
+        Ok(())
+    }
+
+    fn deps(args: Args, entry: String) -> PigResult<()> {
+        fn print(
+            refs: &BTreeMap<String, BTreeSet<String>>,
+            ins: &BTreeMap<&String, usize>,
+            file: &String,
+            path: &mut Vec<String>,
+            printed: &mut BTreeSet<String>,
+        ) {
+            let note = if path.contains(file) {
+                " (cycle)"
+            } else if !printed.insert(file.clone()) {
+                " (see above)"
+            } else {
+                ""
+            };
+            let outs = refs.get(file);
+
+            Output::line(format!(
+                "{}{file} (in: {}, out: {}){note}",
+                "  ".repeat(path.len()),
+                ins.get(file).copied().unwrap_or_default(),
+                outs.map_or(0, BTreeSet::len),
+            ));
+
+            if note.is_empty() {
+                path.push(file.clone());
+
+                for target in outs.into_iter().flatten() {
+                    print(refs, ins, target, path, printed);
+                }
+
+                path.pop();
+            }
+        }
+
+        let config = Config::new(args)?;
+        let entry = config
+            .entries
+            .iter()
+            .find(|config| config.name == entry)
+            .ok_or(PigError::UnknownEntry(entry))?;
+        let resolver = Pig::resolver(entry, &mut Tera::default())?;
+        let root = resolver.root();
+        let Resolved { refs, .. } = resolver.resolve()?;
+
+        let mut ins = BTreeMap::new();
+        for target in refs.values().flatten() {
+            *ins.entry(target).or_default() += 1;
+        }
+
+        print(&refs, &ins, &root, &mut Vec::new(), &mut BTreeSet::new());
+
+        Ok(())
+    }
+
+    fn diff_spec(old: String, new: String, format: Format) -> PigResult<()> {
+        // Stdout is for the JSON
+        if let Format::Json = format {
+            Output::stderr();
+        }
+
+        let changes = spec_diff(&Self::spec(&old)?, &Self::spec(&new)?);
+        let breaking = changes.iter().filter(|change| change.breaking).count();
+
+        match format {
+            Format::Text if changes.is_empty() => Output::info("No changes"),
+            Format::Text => {
+                for change in &changes {
+                    Output::line(change);
+                }
+
+                Output::info(format!("{} changes, {breaking} breaking", changes.len()));
+            }
+            Format::Json => {
+                let changes = changes
+                    .iter()
+                    .map(|change| {
+                        json!({
+                            "breaking": change.breaking,
+                            "at": change.at,
+                            "message": change.message,
+                        })
+                    })
+                    .collect::<Vec<_>>();
+
+                Output::data(serde_json::to_string_pretty(&changes)?);
+            }
+        }
+
+        if breaking > 0 {
+            return Err(PigError::BreakingChanges(breaking));
+        }
+
+        Ok(())
+    }
+
+    /// Resolves the document at `spec`, a path or `<REV>:<PATH>` (checked out in a temporary
+    /// git worktree).
+    fn spec(spec: &str) -> PigResult<serde_json::Value> {
+        fn git(args: &[&str]) -> PigResult<String> {
+            let output = Process::new("git").args(args).output()?;
+
+            if !output.status.success() {
+                return Err(PigError::Git(
+                    String::from_utf8_lossy(&output.stderr).trim().into(),
+                ));
+            }
+
+            Ok(String::from_utf8_lossy(&output.stdout).trim().into())
+        }
+
+        let resolve = |path: &Path| -> PigResult<_> {
+            let resolver = match path.is_dir() {
+                true => Resolver::directory(path, path)?,
+                false => Resolver::new(path)?,
+            };
+
+            Ok(resolver
+                .metadata(RefMetadata::None, None)
+                .resolve()?
+                .openapi)
+        };
+
+        let path = Path::new(spec);
+        let Some((rev, file)) = spec.split_once(':').filter(|_| !path.exists()) else {
+            return resolve(path);
+        };
+
+        let prefix = git(&["rev-parse", "--show-prefix"])?;
+        let worktree = std::env::temp_dir().join(format!(
+            "pig-diff-spec-{}-{}",
+            std::process::id(),
+            rev.replace(|char: char| !char.is_alphanumeric(), "_")
+        ));
+        let worktree = worktree.to_string_lossy();
+        git(&["worktree", "add", "--detach", "--quiet", &worktree, rev])?;
+
+        let openapi = resolve(&Path::new(&*worktree).join(prefix).join(file));
+        git(&["worktree", "remove", "--force", &worktree])?;
+
+        openapi
+    }
+
+    fn config_show(args: Args, entry: Option<String>, format: DataFormat) -> PigResult<()> {
+        let config = Config::new(args)?;
+        let config = match entry {
+            Some(name) => {
+                let entry = config
+                    .entries
+                    .iter()
+                    .find(|entry| entry.name == name)
+                    .ok_or(PigError::UnknownEntry(name))?;
+                let mut value = serde_json::to_value(entry)?;
+                // Not in `pig.yaml`, but where pig reads and writes
+                value["file"] = serde_json::to_value(&entry.file)?;
+                value["cache"] = serde_json::to_value(&entry.cache)?;
+
+                value
+            }
+            None => serde_json::to_value(&config)?,
+        };
+
+        match format {
+            DataFormat::Yaml => Output::data(serde_yaml::to_string(&config)?.trim_end()),
+            DataFormat::Json => Output::data(serde_json::to_string_pretty(&config)?),
+        }
+
+        Ok(())
+    }
+
+    fn context_diff(old: PathBuf, new: PathBuf) -> PigResult<()> {
+        let mut files = BTreeSet::new();
+
+        for dir in [&old, &new] {
+            for file in read_dir(dir)? {
+                files.insert(file?.file_name());
+            }
+        }
+
+        for file in files {
+            let read = |dir: &PathBuf| -> PigResult<_> {
+                match std::fs::read(dir.join(&file)) {
+                    Ok(context) => Ok(serde_json::from_slice(&context)?),
+                    Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                        Ok(serde_json::Value::Null)
+                    }
+                    Err(err) => Err(err.into()),
+                }
+            };
+            let changes = diff(&read(&old)?, &read(&new)?);
+
+            Output::line(format!(
+                "{} ({} changes)",
+                file.to_string_lossy(),
+                changes.len()
+            ));
+
+            for change in changes {
+                Output::line(format!("  {change}"));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Output formats.
+#[derive(ValueEnum, Copy, Clone, Default, Debug)]
+pub enum Format {
+    #[default]
+    Text,
+    Json,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+    /// Print the effective configuration (defaults, global config, CLI overrides and
+    /// canonicalized paths applied)
+    Show {
+        /// Only this entry
+        #[arg(long)]
+        entry: Option<String>,
+        #[arg(long, value_enum, default_value_t)]
+        format: DataFormat,
+    },
+}
+
+/// Formats of documents.
+#[derive(ValueEnum, Copy, Clone, Default, Debug)]
+pub enum DataFormat {
+    #[default]
+    Yaml,
+    Json,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{create_dir_all, remove_dir_all, write};
+
+    const MAIN: &str = r##"openapi: 3.0.3
+info: { title: T, version: "1" }
+paths:
+  /pets:
+    get:
+      responses:
+        "200":
+          description: ok
+          content: { application/json: { schema: { $ref: "#/components/schemas/Pet" } } }
+"##;
+
+    /// A directory of partials, `Pet` having a `name` of `kind`.
+    fn partials(root: &Path, name: &str, kind: &str) -> PathBuf {
+        let dir = root.join(name);
+        create_dir_all(&dir).unwrap();
+        write(dir.join("main.yaml"), MAIN).unwrap();
+        write(
+            dir.join("schemas.yaml"),
+            format!("components:\n  schemas:\n    Pet: {{ properties: {{ name: {{ type: {kind} }} }} }}\n"),
+        )
+        .unwrap();
+
+        dir
+    }
+
+    #[test]
+    fn directory_specs() {
+        let root = std::env::temp_dir().join(format!("pig-diff-spec-{}", std::process::id()));
+        let old = partials(&root, "old", "string");
+        let new = partials(&root, "new", "integer");
+        let spec = |dir: &Path| Command::spec(&dir.to_string_lossy()).unwrap();
+        let changes = spec_diff(&spec(&old), &spec(&new));
+
+        assert!(spec_diff(&spec(&old), &spec(&old)).is_empty());
+        assert_eq!(changes.len(), 1);
+        assert!(changes[0].breaking);
+        assert_eq!(changes[0].at, "GET /pets 200 application/json name");
+
+        remove_dir_all(root).unwrap();
+    }
+}
@@ -1,28 +1,447 @@
-use crate::{Args, PigError, PigResult};
+use crate::{output::Color, Args, PigError, PigResult};
 use serde::{Deserialize, Serialize};
-use std::{io::ErrorKind, path::PathBuf};
+use serde_json::Value as Json;
+use std::{
+    collections::{BTreeMap, HashSet},
+    io::ErrorKind,
+    num::NonZeroUsize,
+    path::{Component, Path, PathBuf},
+};
+
+/// An `OpenAPI` file, or an inline document.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum Api {
+    File(PathBuf),
+    Inline(serde_json::Value),
+}
+
+/// Metadata added to resolved references.
+#[derive(Serialize, Deserialize, Copy, Clone, Default, Eq, PartialEq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum RefMetadata {
+    /// `$ref`, `$file`, `$keys` and `$name`
+    #[default]
+    Full,
+    /// `$name`
+    NameOnly,
+    None,
+}
+
+/// How `OpenAPI` files are validated.
+#[derive(Serialize, Deserialize, Copy, Clone, Default, Eq, PartialEq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum Validate {
+    /// Fails on invalid files
+    #[default]
+    Strict,
+    /// Warns about invalid files, using them as is
+    Lenient,
+    /// Uses files as is
+    Off,
+}
+
+/// Who templates generate for, which decides how `readOnly` and `writeOnly` properties are seen.
+#[derive(Serialize, Deserialize, Copy, Clone, Default, Eq, PartialEq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum View {
+    /// Drops `readOnly` properties from requests and `writeOnly` ones from responses
+    Client,
+    /// Same as `client`, from the other side of the wire
+    Server,
+    /// Keeps every property
+    #[default]
+    Docs,
+}
+
+impl std::fmt::Display for View {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Client => "client",
+            Self::Server => "server",
+            Self::Docs => "docs",
+        })
+    }
+}
+
+/// TLS settings for downloading remote files from a host.
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+pub struct Tls {
+    /// A PEM bundle of extra trusted certificates, e.g. a private CA.
+    pub ca: Option<PathBuf>,
+    /// Skips certificate verification (insecure, warned about).
+    #[serde(default)]
+    pub insecure: bool,
+}
+
+/// The vendor extensions giving the versions of an item's lifecycle.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct Lifecycle {
+    /// The version adding the item (defaults to `x-since`).
+    pub added: String,
+    /// The version deprecating the item (defaults to `x-deprecated-in`).
+    pub deprecated: String,
+    /// The version removing the item (defaults to `x-sunset`).
+    pub removed: String,
+}
+
+impl Default for Lifecycle {
+    fn default() -> Self {
+        Self {
+            added: "x-since".into(),
+            deprecated: "x-deprecated-in".into(),
+            removed: "x-sunset".into(),
+        }
+    }
+}
+
+/// Operations dropped from the document, e.g. for public SDKs.
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+#[serde(default)]
+pub struct DropOperations {
+    /// Drops operations marked `deprecated: true`.
+    pub deprecated: bool,
+    /// Drops operations with one of these extensions set to `true`, e.g. `x-internal`.
+    pub extensions: Vec<String>,
+}
+
+/// Operations selected by path (globs, e.g. `/admin/**`) or tag.
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+#[serde(default)]
+pub struct Selection {
+    /// Globs of paths (or webhook names).
+    pub paths: Vec<String>,
+    /// Tags, of which operations must have one.
+    pub tags: Vec<String>,
+}
+
+impl Selection {
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty() && self.tags.is_empty()
+    }
+}
+
+/// Renames (old name to new name), applied to the resolved document.
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+pub struct Renames {
+    /// Schemas of `components`, references included.
+    #[serde(default)]
+    pub schemas: BTreeMap<String, String>,
+    /// `operationId`s, links included.
+    #[serde(default)]
+    pub operations: BTreeMap<String, String>,
+    #[serde(default)]
+    pub tags: BTreeMap<String, String>,
+}
+
+/// Naming conventions of generated names, e.g. `operationId`s.
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Case {
+    #[serde(rename = "camelCase")]
+    Camel,
+    #[serde(rename = "PascalCase")]
+    Pascal,
+    #[serde(rename = "snake_case")]
+    Snake,
+    #[serde(rename = "kebab-case")]
+    Kebab,
+}
+
+impl Case {
+    /// Joins lowercase `words` in this case.
+    pub fn join(self, words: &[String]) -> String {
+        let capitalize = |word: &String| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect())
+                .unwrap_or_default()
+        };
+
+        match self {
+            Self::Camel => words
+                .iter()
+                .enumerate()
+                .map(|(i, word)| {
+                    if i == 0 {
+                        word.clone()
+                    } else {
+                        capitalize(word)
+                    }
+                })
+                .collect(),
+            Self::Pascal => words.iter().map(capitalize).collect(),
+            Self::Snake => words.join("_"),
+            Self::Kebab => words.join("-"),
+        }
+    }
+}
+
+/// How much a lint rule matters.
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum Severity {
+    Off,
+    /// Only reported by `pig lint`
+    Info,
+    Warn,
+    /// Fails generation
+    Error,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Off => write!(f, "off"),
+            Self::Info => write!(f, "info"),
+            Self::Warn => write!(f, "warn"),
+            Self::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// Lint rules: over `summary` and `description` fields, and over the document's structure.
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+pub struct Lint {
+    /// Words that must not appear (case insensitive).
+    #[serde(default)]
+    pub banned_words: Vec<String>,
+    /// Texts must start with an uppercase letter.
+    #[serde(default)]
+    pub sentence_case: bool,
+    /// Maximum length, in characters.
+    pub max_length: Option<usize>,
+    /// Operations not marked `x-internal` must have a summary or description.
+    #[serde(default)]
+    pub required: bool,
+    /// Severities of rules, by name (see [`Lint::RULES`]).
+    #[serde(default)]
+    pub rules: BTreeMap<String, Severity>,
+}
+
+impl Lint {
+    pub const RULES: [&'static str; 8] = [
+        "banned-words",
+        "sentence-case",
+        "max-length",
+        "operation-description",
+        "operation-id",
+        "operation-tags",
+        "unused-components",
+        "naming",
+    ];
+
+    /// The configured severity of `rule`, defaulting to `warn` (`operation-description`
+    /// defaulting to `off`, unless `required`).
+    pub fn severity(&self, rule: &str) -> Severity {
+        match self.rules.get(rule) {
+            Some(severity) => *severity,
+            None if rule == "operation-description" && !self.required => Severity::Off,
+            None => Severity::Warn,
+        }
+    }
+}
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct ConfigEntry {
+    /// The config file of this entry.
+    #[serde(skip)]
+    pub file: PathBuf,
+    /// The cache directory.
+    #[serde(skip)]
+    pub cache: PathBuf,
+    #[serde(default)]
+    pub name: String,
     #[serde(rename = "api")]
-    pub openapi: PathBuf,
+    pub openapi: Api,
     #[serde(rename = "in")]
     pub input: PathBuf,
     #[serde(rename = "out")]
     pub output: PathBuf,
+    /// Any YAML, strings being templates.
+    #[serde(default)]
+    pub vars: BTreeMap<String, Json>,
+    #[serde(default)]
+    pub deny_warnings: bool,
+    #[serde(default)]
+    pub late: Vec<String>,
+    /// Globs of files in `in` copied as is, e.g. `**/*.png`.
+    #[serde(default)]
+    pub copy: Vec<String>,
+    /// Globs of templates in `in` only rendered through others (`include`, `dispatch`, ...), e.g.
+    /// `**/_*.jinja`.
+    #[serde(default)]
+    pub partials: Vec<String>,
+    #[serde(default)]
+    pub ref_roots: Vec<PathBuf>,
+    /// `OpenAPI` Overlay files applied to the resolved document, in order.
+    #[serde(default)]
+    pub overlays: Vec<PathBuf>,
+    /// JSON Patch or JSON Merge Patch files applied after `overlays`, in order.
+    #[serde(default)]
+    pub patches: Vec<PathBuf>,
+    /// Generates missing `operationId`s from methods and paths, in this case.
+    pub operation_ids: Option<Case>,
+    /// Renames applied after `patches`.
+    #[serde(default)]
+    pub renames: Renames,
+    #[serde(default)]
+    pub ref_metadata: RefMetadata,
+    /// The prefix of reference metadata keys, instead of `$`.
+    pub ref_prefix: Option<String>,
+    /// Only expands references nested in at most that many other references.
+    pub ref_depth: Option<usize>,
+    /// The kind of document, e.g. `openapi30` (auto-detected by default).
+    pub api_kind: Option<String>,
+    /// The SHA-256 of the `api` file (hex), verified before generating.
+    pub api_sha256: Option<String>,
+    /// SHA-256s of the document's other files (by `$file`), verified before generating. When
+    /// given, every file must be pinned.
+    #[serde(default)]
+    pub dependencies_sha256: BTreeMap<String, String>,
+    #[serde(default)]
+    pub validate: Validate,
+    /// Shares `out` with other tools and humans: cleaning only trashes files `pig` generated.
+    #[serde(default)]
+    pub shared_out: bool,
+    /// Appends a comment with their hash to generated files (see `pig verify`).
+    #[serde(default)]
+    pub checksums: bool,
+    /// Entries generated before this one, e.g. when it uses their output.
+    #[serde(default)]
+    pub after: Vec<String>,
+    /// Exposes the parsed files of the document, unresolved, as `pig.raw`.
+    #[serde(default)]
+    pub raw: bool,
+    /// Merges `allOf` compositions, as `$flattened`.
+    #[serde(default)]
+    pub flatten_all_of: bool,
+    /// Operations dropped after `renames`.
+    #[serde(default)]
+    pub drop_operations: DropOperations,
+    /// Only keeps these operations (all by default), after `drop_operations`.
+    #[serde(default)]
+    pub include: Selection,
+    /// Drops these operations, after `include`.
+    #[serde(default)]
+    pub exclude: Selection,
+    /// Names anonymous inline object schemas, in `pig.components.schemas`.
+    #[serde(default)]
+    pub promote_schemas: bool,
+    /// Who templates generate for.
+    #[serde(default)]
+    pub view: View,
+    /// Where items' lifecycle versions are.
+    #[serde(default)]
+    pub lifecycle: Lifecycle,
+    /// Headers sent when downloading remote files, by host (with or without a port).
+    #[serde(default)]
+    pub headers: BTreeMap<String, BTreeMap<String, String>>,
+    /// Forbids network access, using previously downloaded remote files.
+    #[serde(default)]
+    pub offline: bool,
+    /// Proxy for downloading remote files (defaults to `HTTPS_PROXY`, `HTTP_PROXY` or `ALL_PROXY`).
+    pub proxy: Option<String>,
+    /// TLS settings for downloading remote files, by host.
+    #[serde(default)]
+    pub tls: BTreeMap<String, Tls>,
+    /// How long downloaded remote files are reused (in seconds).
+    pub remote_ttl: Option<u64>,
+    /// Whether to download remote files regardless of `remote_ttl`.
+    #[serde(skip)]
+    pub refresh: bool,
+    /// Context overrides (`--set`), by dot-separated path.
+    #[serde(default, skip_deserializing)]
+    pub set: Vec<(String, Json)>,
+    /// Lints `summary` and `description` fields.
+    pub lint: Option<Lint>,
+    /// Warns about `OpenAPI` documents spanning more files than this.
+    pub max_dependencies: Option<usize>,
+    /// Inlines `externalValue` files up to this size (in bytes).
+    pub max_example_size: Option<u64>,
+    /// Warns about operations and schemas in no generated file, and loops over empty values.
+    #[serde(default)]
+    pub warn_unused: bool,
+    /// Warns about generated files larger than this (in bytes).
+    pub max_file_size: Option<u64>,
+    /// Warns when generated files are larger than this in total (in bytes).
+    pub max_total_size: Option<u64>,
+}
+
+/// User-wide defaults, read from `$XDG_CONFIG_HOME/pig/config.yaml`.
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+pub struct GlobalConfig {
+    #[serde(default)]
+    pub deny_warnings: bool,
+    #[serde(default)]
+    pub vars: BTreeMap<String, Json>,
+    pub cache: Option<PathBuf>,
+    #[serde(default)]
+    pub offline: bool,
+    pub color: Option<Color>,
+    /// Default for `--force`.
+    #[serde(default)]
+    pub force: bool,
+    /// Default for every entry's `shared_out`.
+    #[serde(default)]
+    pub shared_out: bool,
+}
+
+impl GlobalConfig {
+    const FILE: &'static str = "config.yaml";
+
+    pub fn new() -> PigResult<Self> {
+        let Some(folder) = xdg("XDG_CONFIG_HOME", ".config") else {
+            return Ok(Self::default());
+        };
+
+        match std::fs::read_to_string(folder.join(Self::FILE)) {
+            Ok(config) => {
+                let mut config = serde_yaml::from_str::<Self>(&config)?;
+                config.cache = config.cache.map(|cache| folder.join(cache));
+
+                Ok(config)
+            }
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Config {
     pub file: PathBuf,
+    pub cache: PathBuf,
     pub watch: bool,
+    pub once_then_watch: bool,
+    pub ready_message: Option<String>,
+    pub ready_fd: Option<u32>,
+    pub if_changed: bool,
+    pub archive: Option<PathBuf>,
+    pub deny_warnings: bool,
+    pub offline: bool,
+    pub refresh: bool,
+    pub preview: Option<String>,
+    pub force: bool,
+    pub allow_empty: bool,
+    pub set: Vec<(String, Json)>,
+    pub verbose: u8,
+    pub jobs: usize,
     pub entries: Vec<ConfigEntry>,
 }
 
 impl Config {
     const FILE: &'static str = "pig.yaml";
+    /// The default maximum of threads.
+    const JOBS: usize = 8;
 
     pub fn new(args: Args) -> PigResult<Self> {
+        Self::with_global(args, GlobalConfig::new()?)
+    }
+
+    /// The config of `args`, with the user-wide defaults of `global`.
+    pub(crate) fn with_global(args: Args, global: GlobalConfig) -> PigResult<Self> {
         let file = if let Some(file) = args.config {
             if !file.is_file() {
                 return Err(PigError::NotAFile(file));
@@ -43,35 +462,138 @@ impl Config {
             file
         };
 
-        let config = std::fs::read_to_string(&file);
+        let config = match std::fs::read_to_string(&file) {
+            Ok(config) => config,
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                return Err(PigError::ConfigNotFound(file))
+            }
+            Err(err) => return Err(err.into()),
+        };
+        let file = file.canonicalize()?;
+        let mut entries = serde_yaml::from_str::<Vec<ConfigEntry>>(&config)?;
 
-        match config {
-            Ok(config) => Ok(Self {
-                file: file.canonicalize()?,
-                watch: args.watch,
-                entries: serde_yaml::from_str::<Vec<ConfigEntry>>(&config)?,
+        for entry in &mut entries {
+            entry.shared_out |= global.shared_out;
+
+            for (name, var) in &global.vars {
+                entry
+                    .vars
+                    .entry(name.clone())
+                    .or_insert_with(|| var.clone());
             }
-            .validate()?),
-            Err(err) if err.kind() == ErrorKind::NotFound => Err(PigError::ConfigNotFound(file)),
-            Err(err) => Err(err.into()),
         }
+
+        let folder = file
+            .parent()
+            .ok_or_else(|| PigError::InvalidPath(file.clone()))?
+            .to_path_buf();
+
+        Self {
+            cache: global
+                .cache
+                .or_else(|| xdg("XDG_CACHE_HOME", ".cache"))
+                .unwrap_or_else(|| folder.join(".pig.cache")),
+            file,
+            watch: args.watch,
+            once_then_watch: args.once_then_watch,
+            ready_message: args.ready_message,
+            ready_fd: args.ready_fd,
+            if_changed: args.if_changed,
+            archive: args
+                .archive_contexts
+                .map(|archive| Ok::<_, PigError>(std::env::current_dir()?.join(archive)))
+                .transpose()?,
+            deny_warnings: args.deny_warnings || global.deny_warnings,
+            offline: args.offline || global.offline,
+            refresh: args.refresh,
+            preview: args.preview,
+            force: args.force || global.force,
+            allow_empty: args.allow_empty,
+            // Values are YAML, falling back to strings
+            set: args
+                .set
+                .into_iter()
+                .map(|(key, value)| {
+                    let value = serde_yaml::from_str(&value).unwrap_or(Json::String(value));
+                    (key, value)
+                })
+                .collect(),
+            verbose: args.verbose,
+            jobs: args.jobs.map_or_else(
+                || {
+                    std::thread::available_parallelism()
+                        .map_or(1, NonZeroUsize::get)
+                        .min(Self::JOBS)
+                },
+                usize::from,
+            ),
+            entries,
+        }
+        .validate(
+            args.out_override
+                .into_iter()
+                .map(|(entry, output)| Ok((entry, std::env::current_dir()?.join(output))))
+                .collect::<PigResult<_>>()?,
+        )
     }
 
-    fn validate(mut self) -> PigResult<Self> {
-        let folder = self.file.parent().unwrap();
+    fn validate(mut self, outputs: BTreeMap<String, PathBuf>) -> PigResult<Self> {
+        let folder = self
+            .file
+            .parent()
+            .ok_or_else(|| PigError::InvalidPath(self.file.clone()))?;
+        let mut names = HashSet::new();
 
-        for entry in &mut self.entries {
-            entry.openapi = {
-                if entry.openapi.is_relative() {
-                    entry.openapi = folder.join(&entry.openapi);
-                }
+        for (i, entry) in self.entries.iter_mut().enumerate() {
+            if entry.name.is_empty() {
+                entry.name = i.to_string();
+            }
+
+            entry.deny_warnings |= self.deny_warnings;
+            entry.offline |= self.offline;
 
-                if !entry.openapi.is_file() {
-                    return Err(PigError::NotAFile(entry.openapi.clone()));
+            for rule in entry.lint.iter().flat_map(|lint| lint.rules.keys()) {
+                if !Lint::RULES.contains(&rule.as_str()) {
+                    return Err(PigError::UnknownLintRule(rule.clone()));
                 }
+            }
 
-                entry.openapi.canonicalize()?
-            };
+            if let Some(output) = outputs.get(&entry.name) {
+                entry.output = output.clone();
+            }
+
+            let mut components = Path::new(&entry.name).components();
+
+            if !matches!(
+                (components.next(), components.next()),
+                (Some(Component::Normal(_)), None)
+            ) {
+                return Err(PigError::InvalidEntryName(entry.name.clone()));
+            }
+
+            if !names.insert(entry.name.clone()) {
+                return Err(PigError::DuplicateEntryName(entry.name.clone()));
+            }
+
+            entry.file = self.file.clone();
+            entry.cache = self.cache.clone();
+            entry.refresh = self.refresh;
+            entry.set = self.set.clone();
+
+            if let Api::File(openapi) = &mut entry.openapi {
+                *openapi = {
+                    if openapi.is_relative() {
+                        *openapi = folder.join(&openapi);
+                    }
+
+                    // A directory of partial documents, merged into one
+                    if !openapi.is_file() && !openapi.is_dir() {
+                        return Err(PigError::NotAFile(openapi.clone()));
+                    }
+
+                    openapi.canonicalize()?
+                };
+            }
 
             entry.input = {
                 if entry.input.is_relative() {
@@ -85,6 +607,60 @@ impl Config {
                 entry.input.canonicalize()?
             };
 
+            for overlay in &mut entry.overlays {
+                *overlay = {
+                    if overlay.is_relative() {
+                        *overlay = folder.join(&overlay);
+                    }
+
+                    if !overlay.is_file() {
+                        return Err(PigError::NotAFile(overlay.clone()));
+                    }
+
+                    overlay.canonicalize()?
+                };
+            }
+
+            for patch in &mut entry.patches {
+                *patch = {
+                    if patch.is_relative() {
+                        *patch = folder.join(&patch);
+                    }
+
+                    if !patch.is_file() {
+                        return Err(PigError::NotAFile(patch.clone()));
+                    }
+
+                    patch.canonicalize()?
+                };
+            }
+
+            for tls in entry.tls.values_mut() {
+                if let Some(ca) = &mut tls.ca {
+                    if ca.is_relative() {
+                        *ca = folder.join(&ca);
+                    }
+
+                    if !ca.is_file() {
+                        return Err(PigError::NotAFile(ca.clone()));
+                    }
+                }
+            }
+
+            for root in &mut entry.ref_roots {
+                *root = {
+                    if root.is_relative() {
+                        *root = folder.join(&root);
+                    }
+
+                    if !root.is_dir() {
+                        return Err(PigError::NotADirectory(root.clone()));
+                    }
+
+                    root.canonicalize()?
+                };
+            }
+
             entry.output = {
                 if entry.output.is_relative() {
                     entry.output = folder.join(&entry.output);
@@ -102,6 +678,138 @@ impl Config {
             };
         }
 
+        if let Some(entry) = outputs.keys().find(|entry| !names.contains(*entry)) {
+            return Err(PigError::UnknownEntry(entry.clone()));
+        }
+
+        if let Some(entry) = self
+            .entries
+            .iter()
+            .flat_map(|entry| &entry.after)
+            .find(|entry| !names.contains(*entry))
+        {
+            return Err(PigError::UnknownEntry(entry.clone()));
+        }
+
+        self.order()?;
+
         Ok(self)
     }
+
+    /// Sorts entries after those in their `after`, keeping the order of the file otherwise.
+    fn order(&mut self) -> PigResult<()> {
+        let mut entries = std::mem::take(&mut self.entries);
+
+        while !entries.is_empty() {
+            let Some(i) = entries.iter().position(|entry| {
+                entry
+                    .after
+                    .iter()
+                    .all(|after| self.entries.iter().any(|entry| entry.name == *after))
+            }) else {
+                return Err(PigError::EntryCycle(
+                    entries.into_iter().map(|entry| entry.name).collect(),
+                ));
+            };
+
+            self.entries.push(entries.remove(i));
+        }
+
+        Ok(())
+    }
+
+    /// Entries that can generate together, in order: each stage comes after the stages of the
+    /// entries in its entries' `after`.
+    pub fn stages(&self) -> Vec<Vec<usize>> {
+        let mut stages = Vec::<Vec<usize>>::new();
+        let mut levels = Vec::new();
+
+        for entry in &self.entries {
+            let level = entry
+                .after
+                .iter()
+                .filter_map(|after| self.entries.iter().position(|entry| entry.name == *after))
+                .map(|i| levels[i] + 1)
+                .max()
+                .unwrap_or(0);
+
+            levels.push(level);
+            match stages.get_mut(level) {
+                Some(stage) => stage.push(levels.len() - 1),
+                None => stages.push(vec![levels.len() - 1]),
+            }
+        }
+
+        stages
+    }
+
+    /// The entries after entry `i`, directly or not, in order.
+    pub fn downstream(&self, i: usize) -> Vec<usize> {
+        let mut names = vec![&self.entries[i].name];
+
+        (i + 1..self.entries.len())
+            .filter(|&j| {
+                let entry = &self.entries[j];
+                let is_after = entry.after.iter().any(|after| names.contains(&after));
+
+                if is_after {
+                    names.push(&entry.name);
+                }
+
+                is_after
+            })
+            .collect()
+    }
+}
+
+/// `pig`'s folder in `$var`, or in `$HOME/fallback`.
+fn xdg(var: &str, fallback: &str) -> Option<PathBuf> {
+    std::env::var_os(var)
+        .filter(|folder| !folder.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(fallback)))
+        .map(|folder| folder.join("pig"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use serde_json::json;
+    use std::fs::{create_dir_all, remove_dir_all, write};
+
+    #[test]
+    fn global_defaults() {
+        let dir = std::env::temp_dir().join(format!("pig-config-global-{}", std::process::id()));
+        let _ = remove_dir_all(&dir);
+        create_dir_all(dir.join("a")).unwrap();
+        let file = dir.join("pig.yaml");
+        write(dir.join("a.yaml"), "openapi: 3.0.3").unwrap();
+        write(
+            &file,
+            "- { name: a, api: a.yaml, in: a, out: a, vars: { lang: rust } }",
+        )
+        .unwrap();
+        let global = GlobalConfig {
+            vars: BTreeMap::from([
+                ("lang".into(), json!("go")),
+                ("license".into(), json!("MIT")),
+            ]),
+            cache: Some(dir.join("cache")),
+            shared_out: true,
+            ..Default::default()
+        };
+        let config =
+            Config::with_global(Args::parse_from(["pig".as_ref(), file.as_os_str()]), global)
+                .unwrap();
+        let entry = &config.entries[0];
+
+        // Entries win over user-wide defaults
+        assert_eq!(entry.vars["lang"], "rust");
+        assert_eq!(entry.vars["license"], "MIT");
+        assert!(entry.shared_out);
+        assert_eq!(config.cache, dir.join("cache"));
+
+        remove_dir_all(dir).unwrap();
+    }
 }
@@ -0,0 +1,1711 @@
+use crate::{
+    config::{Case, Lifecycle, View},
+    resolver::Reference,
+};
+use serde_json::{json, Map, Value as Json};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+#[derive(Debug)]
+pub enum Derive {}
+
+impl Derive {
+    /// Version of the context structure, to bump when renaming, removing or changing the meaning
+    /// of derived data (not when adding some). `1` is the context documented in the README.
+    pub const CONTEXT_VERSION: u64 = 1;
+
+    /// The tag of untagged operations.
+    const DEFAULT_TAG: &'static str = "default";
+
+    pub const METHODS: [&'static str; 8] = [
+        "get", "put", "post", "delete", "options", "head", "patch", "trace",
+    ];
+
+    pub fn derive(openapi: &mut Json, prefix: &str) {
+        Self::nullable(openapi);
+        Self::body_kinds(openapi);
+        Self::responses(openapi);
+        Self::properties(openapi);
+        Self::uploads(openapi);
+        Self::security(openapi);
+        Self::pagination(openapi);
+        Self::parameter_groups(openapi, prefix);
+    }
+
+    /// Drops the properties of request and response bodies that `view` never sees on the wire:
+    /// `readOnly` ones from requests and `writeOnly` ones from responses.
+    pub fn view(openapi: &mut Json, view: View) {
+        if view == View::Docs {
+            return;
+        }
+
+        Self::operations(openapi, |_, _, _, operation| {
+            let requests = operation
+                .get_mut("requestBody")
+                .and_then(|body| body.get_mut("content"))
+                .and_then(Json::as_object_mut)
+                .into_iter()
+                .flatten();
+
+            for (_, media) in requests {
+                if let Some(schema) = media.get_mut("schema") {
+                    Self::drop_properties(schema, "readOnly");
+                }
+            }
+
+            let responses = operation
+                .get_mut("responses")
+                .and_then(Json::as_object_mut)
+                .into_iter()
+                .flatten()
+                .filter_map(|(_, response)| response.get_mut("content"))
+                .filter_map(Json::as_object_mut)
+                .flatten();
+
+            for (_, media) in responses {
+                if let Some(schema) = media.get_mut("schema") {
+                    Self::drop_properties(schema, "writeOnly");
+                }
+            }
+        });
+    }
+
+    /// Removes properties flagged with `flag` (e.g. `readOnly`) from a schema and its
+    /// subschemas, and from their `required`.
+    fn drop_properties(schema: &mut Json, flag: &str) {
+        Self::schema(schema, &mut |object| {
+            let Some(Json::Object(properties)) = object.get_mut("properties") else {
+                return;
+            };
+            let dropped = properties
+                .iter()
+                .filter(|(_, property)| property.get(flag).and_then(Json::as_bool) == Some(true))
+                .map(|(name, _)| name.clone())
+                .collect::<HashSet<_>>();
+
+            if dropped.is_empty() {
+                return;
+            }
+
+            properties.retain(|name, _| !dropped.contains(name));
+
+            if let Some(Json::Array(required)) = object.get_mut("required") {
+                required.retain(|name| name.as_str().is_none_or(|name| !dropped.contains(name)));
+            }
+        });
+    }
+
+    /// Every operation in a flat list, with its `path`, `method` and `parameters` (the path
+    /// item's ones included), and `operationId` (`null`), `tags` (`[]`) and `requestBody` (`null`)
+    /// even when missing.
+    pub fn operation_list(openapi: &mut Json) -> Json {
+        let mut list = Vec::new();
+        Self::operations(openapi, |path, method, parameters, operation| {
+            let mut flat = operation.clone();
+            let parameters = Self::parameters(parameters, operation)
+                .into_iter()
+                .cloned()
+                .collect::<Vec<_>>();
+
+            flat.insert("path".into(), path.into());
+            flat.insert("method".into(), method.into());
+            flat.insert("parameters".into(), parameters.into());
+            flat.entry("operationId").or_insert(Json::Null);
+            flat.entry("tags").or_insert_with(|| json!([]));
+            flat.entry("requestBody").or_insert(Json::Null);
+            list.push(Json::Object(flat));
+        });
+
+        list.into()
+    }
+
+    /// Groups `operations` (from [`Derive::operation_list`]) by tag, each group being its tag
+    /// object (or `{ name }` for undeclared tags) with `operations`: declared tags first, then
+    /// others, then untagged operations under `default`.
+    pub fn tag_groups(openapi: &Json, operations: &Json) -> Json {
+        let mut groups = openapi["tags"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|tag| tag["name"].is_string())
+            .map(|tag| {
+                let mut tag = tag.clone();
+                tag["operations"] = json!([]);
+                (tag["name"].as_str().unwrap().to_string(), tag)
+            })
+            .collect::<Vec<_>>();
+        let mut group = |name: &str, operation: &Json| {
+            let i = match groups.iter().position(|(other, _)| other == name) {
+                Some(i) => i,
+                None => {
+                    groups.push((name.into(), json!({ "name": name, "operations": [] })));
+                    groups.len() - 1
+                }
+            };
+
+            groups[i].1["operations"]
+                .as_array_mut()
+                .unwrap()
+                .push(operation.clone());
+        };
+        let mut untagged = Vec::new();
+
+        for operation in operations.as_array().into_iter().flatten() {
+            let tags = operation["tags"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(Json::as_str)
+                .collect::<Vec<_>>();
+
+            if tags.is_empty() {
+                untagged.push(operation);
+            }
+
+            for tag in tags {
+                group(tag, operation);
+            }
+        }
+
+        for operation in untagged {
+            group(Self::DEFAULT_TAG, operation);
+        }
+
+        groups.into_iter().map(|(_, tag)| tag).collect()
+    }
+
+    /// The error model of `operations` (from [`Derive::operation_list`]): the `schema` of the
+    /// error responses (`4xx`, `5xx` and `default`) of most operations having some, with its
+    /// `name` (if referenced), and `deviations`, the operations with error responses of other
+    /// schemas (or none) as `{ path, method, operationId, statuses }`. `null` without a majority.
+    pub fn error_model(operations: &Json, prefix: &str) -> Json {
+        let operations = operations
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|operation| {
+                let errors = operation["error_responses"]
+                    .as_array()
+                    .map(Vec::as_slice)
+                    .unwrap_or_default();
+                (operation, errors)
+            })
+            .filter(|(_, errors)| !errors.is_empty())
+            .collect::<Vec<_>>();
+
+        // Operations by schema, first come first on ties
+        let mut counts = Vec::<(&Json, &Json, usize)>::new();
+        for (_, errors) in &operations {
+            let mut seen = Vec::new();
+
+            for (identity, schema) in errors
+                .iter()
+                .filter_map(|response| Self::error_schema(response, prefix))
+            {
+                if seen.contains(&identity) {
+                    continue;
+                }
+                seen.push(identity);
+
+                match counts.iter_mut().find(|(other, _, _)| *other == identity) {
+                    Some((_, _, count)) => *count += 1,
+                    None => counts.push((identity, schema, 1)),
+                }
+            }
+        }
+
+        let Some((identity, schema, _)) = counts
+            .into_iter()
+            .rev()
+            .max_by_key(|(_, _, count)| *count)
+            .filter(|(_, _, count)| count * 2 > operations.len())
+        else {
+            return Json::Null;
+        };
+
+        let deviations = operations
+            .iter()
+            .filter_map(|(operation, errors)| {
+                let statuses = errors
+                    .iter()
+                    .filter(|response| {
+                        Self::error_schema(response, prefix).map(|(other, _)| other)
+                            != Some(identity)
+                    })
+                    .map(|response| response["status"].clone())
+                    .collect::<Vec<_>>();
+
+                (!statuses.is_empty()).then(|| {
+                    json!({
+                        "path": operation["path"],
+                        "method": operation["method"],
+                        "operationId": operation["operationId"],
+                        "statuses": statuses,
+                    })
+                })
+            })
+            .collect::<Vec<_>>();
+
+        json!({
+            "schema": schema,
+            "name": schema.get(format!("{prefix}name")),
+            "deviations": deviations,
+        })
+    }
+
+    /// The schema of an error response (of its JSON content, if any) and its identity: its
+    /// reference, or itself when inline.
+    fn error_schema<'a>(response: &'a Json, prefix: &str) -> Option<(&'a Json, &'a Json)> {
+        let content = response["content"].as_object()?;
+        let (_, media) = content
+            .iter()
+            .find(|(media, _)| Self::is_json(media))
+            .or_else(|| content.iter().next())?;
+        let schema = media.get("schema")?;
+        let identity = schema
+            .get(format!("{prefix}ref"))
+            .or_else(|| schema.get(format!("{prefix}name")))
+            .unwrap_or(schema);
+
+        Some((identity, schema))
+    }
+
+    /// Adds `<prefix>deps` (the schemas referenced, by `<prefix>name`) and `<prefix>dependents`
+    /// (the schemas referencing it) to each schema of `components`, and returns their names
+    /// dependencies first (cycles broken in name order), e.g. to declare types in that order.
+    pub fn schema_deps(openapi: &mut Json, prefix: &str) -> Json {
+        let key = |name: &str| format!("{prefix}{name}");
+        let Some(schemas) = openapi
+            .pointer_mut("/components/schemas")
+            .and_then(Json::as_object_mut)
+        else {
+            return json!([]);
+        };
+        let graph = schemas
+            .iter()
+            .map(|(name, schema)| {
+                let mut deps = BTreeSet::new();
+                for value in schema.as_object().into_iter().flat_map(Map::values) {
+                    Self::schema_refs(value, &key("name"), &mut deps);
+                }
+
+                (name.clone(), deps)
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        for (name, schema) in schemas.iter_mut() {
+            let Some(schema) = schema.as_object_mut() else {
+                continue;
+            };
+            let dependents = graph
+                .iter()
+                .filter(|(_, deps)| deps.contains(name))
+                .map(|(dependent, _)| dependent)
+                .collect::<Vec<_>>();
+
+            schema.insert(key("deps"), json!(graph[name]));
+            schema.insert(key("dependents"), json!(dependents));
+        }
+
+        let mut seen = HashSet::new();
+        let mut order = Vec::new();
+        for name in graph.keys() {
+            Self::schema_order(name, &graph, &mut seen, &mut order);
+        }
+
+        json!(order)
+    }
+
+    /// The names (`key`) of the schemas referenced in `value`, not looking into them.
+    fn schema_refs(value: &Json, key: &str, names: &mut BTreeSet<String>) {
+        match value {
+            Json::Array(values) => {
+                for value in values {
+                    Self::schema_refs(value, key, names);
+                }
+            }
+            Json::Object(object) => {
+                // Referenced schemas have their own dependencies
+                if let Some(name) = object.get(key).and_then(Json::as_str) {
+                    names.insert(name.into());
+                    return;
+                }
+
+                for value in object.values() {
+                    Self::schema_refs(value, key, names);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Pushes the dependencies of `name`, depth first, then `name`.
+    fn schema_order<'a>(
+        name: &'a str,
+        graph: &'a BTreeMap<String, BTreeSet<String>>,
+        seen: &mut HashSet<&'a str>,
+        order: &mut Vec<&'a str>,
+    ) {
+        if !seen.insert(name) {
+            return;
+        }
+
+        for dep in graph.get(name).into_iter().flatten() {
+            if graph.contains_key(dep) {
+                Self::schema_order(dep, graph, seen, order);
+            }
+        }
+
+        order.push(name);
+    }
+
+    /// Adds `<prefix>operation` to links, their target operation (as in
+    /// [`Derive::operation_list`]) by `operationId` or local `operationRef`, returning the
+    /// `(link, target)`s of others.
+    pub fn links(openapi: &mut Json, root: &str, prefix: &str) -> Vec<(String, String)> {
+        // Not within targets, which have links too
+        fn objects(value: &mut Json, skip: &str, f: &mut impl FnMut(&mut Map<String, Json>)) {
+            match value {
+                Json::Array(values) => {
+                    for value in values {
+                        objects(value, skip, f);
+                    }
+                }
+                Json::Object(object) => {
+                    f(object);
+
+                    for (key, value) in object.iter_mut() {
+                        if key != skip {
+                            objects(value, skip, f);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let operations = Self::operation_list(openapi);
+        let operations = operations.as_array().map(Vec::as_slice).unwrap_or_default();
+        let by_ref = |reference: &str| {
+            let (file, pointer) = reference.split_once('#')?;
+            let file = file.trim_start_matches("./");
+            let pointer = Reference::percent_decode(pointer);
+            let mut keys = pointer
+                .strip_prefix("/paths/")?
+                .split('/')
+                .map(|key| key.replace("~1", "/").replace("~0", "~"));
+            let (path, method) = (keys.next()?, keys.next()?);
+
+            if !(file.is_empty() || file == root) || keys.next().is_some() {
+                return None;
+            }
+
+            operations
+                .iter()
+                .find(|operation| operation["path"] == *path && operation["method"] == *method)
+        };
+        let by_id = |id: &str| {
+            operations
+                .iter()
+                .find(|operation| operation["operationId"] == *id)
+        };
+        let key = format!("{prefix}operation");
+        let mut unresolved = BTreeSet::new();
+
+        objects(openapi, &key.clone(), &mut |object| {
+            let Some(links) = object.get_mut("links").and_then(Json::as_object_mut) else {
+                return;
+            };
+
+            for (name, link) in links {
+                let Some(link) = link.as_object_mut() else {
+                    continue;
+                };
+                let (target, operation) = match (
+                    link.get("operationId").and_then(Json::as_str),
+                    link.get("operationRef").and_then(Json::as_str),
+                ) {
+                    (Some(id), _) => (id.to_string(), by_id(id)),
+                    (None, Some(reference)) => (reference.to_string(), by_ref(reference)),
+                    (None, None) => continue,
+                };
+
+                match operation {
+                    Some(operation) => {
+                        link.insert(key.clone(), operation.clone());
+                    }
+                    None => {
+                        unresolved.insert((name.clone(), target));
+                    }
+                }
+            }
+        });
+
+        unresolved.into_iter().collect()
+    }
+
+    /// Adds `<prefix>url` (with the defaults of `variables`) and `<prefix>urls` (with every
+    /// combination of their `enum`s, defaults first) to the servers of the document, path items
+    /// and operations.
+    pub fn server_urls(openapi: &mut Json, prefix: &str) {
+        let Some(document) = openapi.as_object_mut() else {
+            return;
+        };
+        let mut servers = Vec::new();
+        let mut items = Vec::new();
+
+        for (key, value) in document {
+            match key.as_str() {
+                "servers" => servers.push(value),
+                "paths" => items.extend(
+                    value
+                        .as_object_mut()
+                        .into_iter()
+                        .flat_map(Map::values_mut)
+                        .filter_map(Json::as_object_mut),
+                ),
+                _ => {}
+            }
+        }
+
+        for item in items {
+            for (key, value) in item {
+                if key == "servers" {
+                    servers.push(value);
+                } else if Self::METHODS.contains(&key.as_str()) {
+                    if let Some(value) = value.get_mut("servers") {
+                        servers.push(value);
+                    }
+                }
+            }
+        }
+
+        for server in servers
+            .into_iter()
+            .filter_map(Json::as_array_mut)
+            .flatten()
+            .filter_map(Json::as_object_mut)
+        {
+            let Some(url) = server.get("url").and_then(Json::as_str) else {
+                continue;
+            };
+            let mut urls = vec![url.to_string()];
+
+            for (name, variable) in server
+                .get("variables")
+                .and_then(Json::as_object)
+                .into_iter()
+                .flatten()
+            {
+                let default = variable.get("default").and_then(Json::as_str);
+                let values = default
+                    .into_iter()
+                    .chain(
+                        variable
+                            .get("enum")
+                            .and_then(Json::as_array)
+                            .into_iter()
+                            .flatten()
+                            .filter_map(Json::as_str)
+                            .filter(|value| Some(*value) != default),
+                    )
+                    .collect::<Vec<_>>();
+                let placeholder = format!("{{{name}}}");
+
+                if values.is_empty() {
+                    continue;
+                }
+
+                urls = urls
+                    .iter()
+                    .flat_map(|url| values.iter().map(|value| url.replace(&placeholder, value)))
+                    .collect();
+            }
+
+            server.insert(format!("{prefix}url"), urls[0].clone().into());
+            server.insert(format!("{prefix}urls"), urls.into());
+        }
+    }
+
+    /// Adds `lifecycle` (`added_in`, `deprecated_in`, `removed_in` and `is_deprecated`) to items
+    /// (operations, parameters, schemas, ...) that are `deprecated` or have `lifecycle`'s
+    /// extensions, versions as strings.
+    pub fn lifecycle(openapi: &mut Json, lifecycle: &Lifecycle) {
+        Self::objects(openapi, &mut |object| {
+            let version = |key: &str| match object.get(key) {
+                Some(Json::String(version)) => Some(version.clone()),
+                Some(Json::Number(version)) => Some(version.to_string()),
+                _ => None,
+            };
+            let added_in = version(&lifecycle.added);
+            let deprecated_in = version(&lifecycle.deprecated);
+            let removed_in = version(&lifecycle.removed);
+            let deprecated = object.get("deprecated").and_then(Json::as_bool);
+
+            if deprecated.is_none()
+                && added_in.is_none()
+                && deprecated_in.is_none()
+                && removed_in.is_none()
+            {
+                return;
+            }
+
+            let is_deprecated = deprecated == Some(true) || deprecated_in.is_some();
+            object.insert(
+                "lifecycle".into(),
+                json!({
+                    "added_in": added_in,
+                    "deprecated_in": deprecated_in,
+                    "removed_in": removed_in,
+                    "is_deprecated": is_deprecated,
+                }),
+            );
+        });
+    }
+
+    /// Generates the missing `operationId`s from methods and paths, e.g. `getPetsById` for
+    /// `GET /pets/{id}`, numbered when taken (e.g. `getPets2`).
+    pub fn operation_ids(openapi: &mut Json, case: Case) {
+        let mut taken = HashSet::new();
+        Self::operations(openapi, |_, _, _, operation| {
+            if let Some(id) = operation.get("operationId").and_then(Json::as_str) {
+                taken.insert(id.to_string());
+            }
+        });
+
+        Self::operations(openapi, |path, method, _, operation| {
+            if operation.contains_key("operationId") {
+                return;
+            }
+
+            let id = case.join(&Self::operation_words(path, method));
+            let id = (1..)
+                .map(|i| match i {
+                    1 => id.clone(),
+                    i => format!("{id}{i}"),
+                })
+                .find(|id| !taken.contains(id))
+                .unwrap();
+
+            taken.insert(id.clone());
+            operation.insert("operationId".into(), id.into());
+        });
+    }
+
+    /// The words naming an operation without `operationId`, e.g. `get pets by id` for
+    /// `GET /pets/{id}`.
+    pub(crate) fn operation_words(path: &str, method: &str) -> Vec<String> {
+        let mut words = vec![method.to_string()];
+
+        for segment in path.split('/').filter(|segment| !segment.is_empty()) {
+            match segment
+                .strip_prefix('{')
+                .and_then(|segment| segment.strip_suffix('}'))
+            {
+                Some(parameter) => {
+                    words.push("by".into());
+                    words.extend(Self::words(parameter));
+                }
+                None => words.extend(Self::words(segment)),
+            }
+        }
+
+        words
+    }
+
+    /// The lowercase words of `text`, split on non-alphanumeric characters and case changes.
+    pub(crate) fn words(text: &str) -> Vec<String> {
+        let mut words = Vec::new();
+        let mut word = String::new();
+        let mut previous = None::<char>;
+
+        for char in text.chars() {
+            if !char.is_alphanumeric() {
+                words.push(std::mem::take(&mut word));
+            } else {
+                if char.is_uppercase() && previous.is_some_and(char::is_lowercase) {
+                    words.push(std::mem::take(&mut word));
+                }
+
+                word.extend(char.to_lowercase());
+            }
+
+            previous = Some(char);
+        }
+
+        words.push(word);
+        words.retain(|word| !word.is_empty());
+        words
+    }
+
+    /// Calls `f` on every object, depth first.
+    fn objects(value: &mut Json, f: &mut impl FnMut(&mut Map<String, Json>)) {
+        match value {
+            Json::Null | Json::Bool(_) | Json::Number(_) | Json::String(_) => {}
+            Json::Array(values) => {
+                for value in values {
+                    Self::objects(value, f);
+                }
+            }
+            Json::Object(object) => {
+                f(object);
+
+                for value in object.values_mut() {
+                    Self::objects(value, f);
+                }
+            }
+        }
+    }
+
+    /// Calls `f` on every schema, depth first: those of `components`, the `schema`s of parameters,
+    /// headers and media types, and their subschemas. Examples and extensions are payloads, not
+    /// looked into.
+    fn schemas(value: &mut Json, f: &mut impl FnMut(&mut Map<String, Json>)) {
+        match value {
+            Json::Null | Json::Bool(_) | Json::Number(_) | Json::String(_) => {}
+            Json::Array(values) => {
+                for value in values {
+                    Self::schemas(value, f);
+                }
+            }
+            Json::Object(object) => {
+                for (key, value) in object.iter_mut() {
+                    match (key.as_str(), value) {
+                        ("example" | "examples", _) => {}
+                        (key, _) if key.starts_with("x-") => {}
+                        ("schema", schema) => Self::schema(schema, f),
+                        ("schemas", Json::Object(schemas)) => {
+                            for schema in schemas.values_mut() {
+                                Self::schema(schema, f);
+                            }
+                        }
+                        (_, value) => Self::schemas(value, f),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Calls `f` on `schema` and its subschemas, depth first.
+    fn schema(schema: &mut Json, f: &mut impl FnMut(&mut Map<String, Json>)) {
+        let Some(object) = schema.as_object_mut() else {
+            return;
+        };
+
+        f(object);
+
+        for (key, value) in object.iter_mut() {
+            match (key.as_str(), value) {
+                ("properties" | "patternProperties", Json::Object(schemas)) => {
+                    for schema in schemas.values_mut() {
+                        Self::schema(schema, f);
+                    }
+                }
+                ("allOf" | "anyOf" | "oneOf" | "prefixItems" | "items", Json::Array(schemas)) => {
+                    for schema in schemas {
+                        Self::schema(schema, f);
+                    }
+                }
+                ("items" | "additionalProperties" | "not", schema) => Self::schema(schema, f),
+                _ => {}
+            }
+        }
+    }
+
+    /// Calls `f` with the path, method, path item parameters and object of each operation.
+    pub(crate) fn operations(
+        openapi: &mut Json,
+        mut f: impl FnMut(&str, &str, &[Json], &mut Map<String, Json>),
+    ) {
+        let Some(paths) = openapi.get_mut("paths").and_then(Json::as_object_mut) else {
+            return;
+        };
+
+        for (path, item) in paths {
+            let Some(item) = item.as_object_mut() else {
+                continue;
+            };
+            let parameters = item
+                .get("parameters")
+                .and_then(Json::as_array)
+                .cloned()
+                .unwrap_or_default();
+
+            for (method, operation) in item {
+                if !Self::METHODS.contains(&method.as_str()) {
+                    continue;
+                }
+
+                if let Some(operation) = operation.as_object_mut() {
+                    f(path, method, &parameters, operation);
+                }
+            }
+        }
+    }
+
+    /// The status code of the success response: lowest `2xx`, then `2XX`, then `default`.
+    pub(crate) fn success_response(responses: &Map<String, Json>) -> Option<&str> {
+        responses
+            .keys()
+            .filter(|status| status.len() == 3 && status.starts_with('2'))
+            .min_by_key(|status| (status.as_str() == "2XX", status.as_str()))
+            .or_else(|| responses.keys().find(|status| *status == "default"))
+            .map(String::as_str)
+    }
+
+    fn body_kinds(openapi: &mut Json) {
+        Self::operations(openapi, |_, _, _, operation| {
+            let request_kind = Self::body_kind(
+                operation
+                    .get("requestBody")
+                    .and_then(|body| body.get("content")),
+            );
+            let response_kind = Self::body_kind(
+                operation
+                    .get("responses")
+                    .and_then(Json::as_object)
+                    .and_then(|responses| {
+                        responses
+                            .get(Self::success_response(responses)?)?
+                            .get("content")
+                    }),
+            );
+
+            operation.insert("request_kind".into(), request_kind.into());
+            operation.insert("response_kind".into(), response_kind.into());
+        });
+    }
+
+    /// Picks the success response and lists error ones, each with its `status`.
+    ///
+    /// Error responses are `4xx` and `5xx` ones (`class: "client"` or `"server"`, ranges after
+    /// codes) then `default` (`class: "default"`) unless it is the success response.
+    fn responses(openapi: &mut Json) {
+        Self::operations(openapi, |_, _, _, operation| {
+            let responses = operation
+                .get("responses")
+                .and_then(Json::as_object)
+                .cloned()
+                .unwrap_or_default();
+            let success = Self::success_response(&responses);
+            let response = |status: &str, class: Option<&str>| {
+                let mut response = responses[status].as_object().cloned().unwrap_or_default();
+                response.insert("status".into(), status.into());
+                if let Some(class) = class {
+                    response.insert("class".into(), class.into());
+                }
+                Json::Object(response)
+            };
+
+            let mut errors = responses
+                .keys()
+                .filter(|status| Some(status.as_str()) != success)
+                .filter_map(|status| match status.as_bytes() {
+                    [b'4', ..] => Some((status, "client")),
+                    [b'5', ..] => Some((status, "server")),
+                    _ if status == "default" => Some((status, "default")),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            errors.sort_by_key(|(status, _)| {
+                (
+                    *status == "default",
+                    status.chars().next(),
+                    status.ends_with("XX"),
+                    status.as_str(),
+                )
+            });
+            let errors = errors
+                .into_iter()
+                .map(|(status, class)| response(status, Some(class)))
+                .collect::<Vec<_>>();
+
+            operation.insert(
+                "success_response".into(),
+                success.map_or(Json::Null, |status| response(status, None)),
+            );
+            operation.insert("error_responses".into(), errors.into());
+        });
+    }
+
+    /// Flags schemas with `is_nullable`, from `nullable: true` (also set by the `v3.1.x` loader for
+    /// `null` among `type`s) or `type: "null"`.
+    fn nullable(openapi: &mut Json) {
+        Self::schemas(openapi, &mut |schema| {
+            let is_nullable = schema.get("nullable") == Some(&Json::Bool(true))
+                || schema.get("type") == Some(&json!("null"));
+
+            schema.insert("is_nullable".into(), is_nullable.into());
+        });
+    }
+
+    /// Classifies a `content` map, favoring JSON media types.
+    fn body_kind(content: Option<&Json>) -> &'static str {
+        let Some(content) = content.and_then(Json::as_object) else {
+            return "no-body";
+        };
+        let media = |media: &str| {
+            media
+                .split(';')
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .to_ascii_lowercase()
+        };
+        let Some((media, object)) = content
+            .iter()
+            .find(|(key, _)| Self::is_json(&media(key)))
+            .or_else(|| content.iter().next())
+            .map(|(key, object)| (media(key), object))
+        else {
+            return "no-body";
+        };
+
+        match media.as_str() {
+            "text/event-stream"
+            | "application/x-ndjson"
+            | "application/jsonl"
+            | "application/stream+json" => "stream",
+            media if Self::is_json(media) => {
+                if object.pointer("/schema/type").and_then(Json::as_str) == Some("array") {
+                    "json-array"
+                } else {
+                    "json-object"
+                }
+            }
+            "application/x-www-form-urlencoded" => "urlencoded-form",
+            media if media.starts_with("multipart/") => "multipart-form",
+            media if media.starts_with("text/") => "text",
+            _ => "binary",
+        }
+    }
+
+    fn is_json(media: &str) -> bool {
+        media == "application/json" || media.ends_with("+json")
+    }
+
+    /// Annotates every schema property with its requiredness and access flags.
+    fn properties(openapi: &mut Json) {
+        Self::schemas(openapi, &mut |schema| {
+            let required = schema
+                .get("required")
+                .and_then(Json::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(Json::as_str)
+                .map(String::from)
+                .collect::<HashSet<_>>();
+            let Some(Json::Object(properties)) = schema.get_mut("properties") else {
+                return;
+            };
+
+            for (name, property) in properties {
+                if let Some(property) = property.as_object_mut() {
+                    Self::property(property, required.contains(name));
+                }
+            }
+        });
+    }
+
+    fn property(property: &mut Map<String, Json>, is_required: bool) {
+        let flag = |key| property.get(key).and_then(Json::as_bool).unwrap_or(false);
+        let has_default = property.contains_key("default");
+        let is_readonly = flag("readOnly");
+        let is_writeonly = flag("writeOnly");
+        let is_deprecated = flag("deprecated");
+        let optionality = match (is_required, has_default) {
+            (true, _) => "required",
+            (false, true) => "optional-with-default",
+            (false, false) => "optional",
+        };
+
+        property.insert("is_required".into(), is_required.into());
+        property.insert("has_default".into(), has_default.into());
+        property.insert("is_readonly".into(), is_readonly.into());
+        property.insert("is_writeonly".into(), is_writeonly.into());
+        property.insert("is_deprecated".into(), is_deprecated.into());
+        property.insert("optionality".into(), optionality.into());
+    }
+
+    /// Flags binary and base64 strings, and describes the parts of form request bodies.
+    fn uploads(openapi: &mut Json) {
+        Self::schemas(openapi, &mut |object| {
+            let flag = match (
+                object.get("type").and_then(Json::as_str),
+                object.get("format").and_then(Json::as_str),
+            ) {
+                (Some("string"), Some("binary")) => "is_binary",
+                (Some("string"), Some("byte")) => "is_base64",
+                _ => return,
+            };
+
+            object.insert(flag.into(), true.into());
+        });
+
+        Self::operations(openapi, |_, _, _, operation| {
+            let Some(content) = operation
+                .get_mut("requestBody")
+                .and_then(|body| body.get_mut("content"))
+                .and_then(Json::as_object_mut)
+            else {
+                return;
+            };
+
+            for (media, object) in content {
+                let media = media.to_ascii_lowercase();
+
+                if !media.starts_with("multipart/")
+                    && !media.starts_with("application/x-www-form-urlencoded")
+                {
+                    continue;
+                }
+
+                if let Some(object) = object.as_object_mut() {
+                    let parts = Self::parts(object);
+                    object.insert("parts".into(), parts.into());
+                }
+            }
+        });
+    }
+
+    fn parts(media: &Map<String, Json>) -> Vec<Json> {
+        let Some(schema) = media.get("schema") else {
+            return Vec::new();
+        };
+        let required = schema
+            .get("required")
+            .and_then(Json::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Json::as_str)
+            .collect::<HashSet<_>>();
+
+        schema
+            .get("properties")
+            .and_then(Json::as_object)
+            .into_iter()
+            .flatten()
+            .map(|(name, property)| {
+                let is_array = property.get("type").and_then(Json::as_str) == Some("array");
+                let item = if is_array {
+                    property.get("items").unwrap_or(&Json::Null)
+                } else {
+                    property
+                };
+                let flag = |key| item.get(key).and_then(Json::as_bool).unwrap_or(false);
+                let is_file = flag("is_binary");
+                let is_base64 = flag("is_base64");
+                let content_type = media
+                    .get("encoding")
+                    .and_then(|encoding| encoding.get(name))
+                    .and_then(|encoding| encoding.get("contentType"))
+                    .and_then(Json::as_str)
+                    .unwrap_or(match item.get("type").and_then(Json::as_str) {
+                        _ if is_file => "application/octet-stream",
+                        Some("object") | Some("array") => "application/json",
+                        _ => "text/plain",
+                    });
+
+                json!({
+                    "name": name,
+                    "is_file": is_file,
+                    "is_base64": is_base64,
+                    "is_array": is_array,
+                    "is_required": required.contains(name.as_str()),
+                    "content_type": content_type,
+                })
+            })
+            .collect()
+    }
+
+    /// Joins each operation's effective security requirements with their schemes.
+    ///
+    /// Requirements are alternatives, each a list of `{ name, scopes, scheme }`. Operations also
+    /// get the names of the schemes they use, and whether they can be called anonymously (no
+    /// requirement, or an empty one).
+    fn security(openapi: &mut Json) {
+        let global = openapi
+            .get("security")
+            .cloned()
+            .unwrap_or_else(|| json!([]));
+        let schemes = openapi
+            .pointer("/components/securitySchemes")
+            .cloned()
+            .unwrap_or_else(|| json!({}));
+
+        Self::operations(openapi, |_, _, _, operation| {
+            let resolved = operation
+                .get("security")
+                .unwrap_or(&global)
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(Json::as_object)
+                .map(|requirement| {
+                    requirement
+                        .iter()
+                        .map(|(name, scopes)| {
+                            json!({
+                                "name": name,
+                                "scopes": scopes,
+                                "scheme": schemes.get(name),
+                            })
+                        })
+                        .collect::<Json>()
+                })
+                .collect::<Json>();
+
+            let is_public = resolved.as_array().is_none_or(|requirements| {
+                requirements.is_empty() || requirements.contains(&json!([]))
+            });
+            let names = resolved
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(Json::as_array)
+                .flatten()
+                .filter_map(|requirement| requirement["name"].as_str())
+                .map(String::from)
+                .collect::<BTreeSet<_>>();
+
+            operation.insert("security_resolved".into(), resolved);
+            operation.insert("security_schemes".into(), json!(names));
+            operation.insert("is_public".into(), is_public.into());
+        });
+    }
+
+    /// Groups parameters shared by several operations: path item parameters (`kind: "path"`)
+    /// and identical sets of `components.parameters` (`kind: "components"`), known by the
+    /// `<prefix>keys` (else `<prefix>name`) of references to them.
+    ///
+    /// Adds `parameter_groups` to the document, and the names of their groups to operations.
+    fn parameter_groups(openapi: &mut Json, prefix: &str) {
+        let key = |name: &str| format!("{prefix}{name}");
+        let components = openapi
+            .pointer("/components/parameters")
+            .and_then(Json::as_object)
+            .into_iter()
+            .flatten()
+            .map(|(name, _)| name.clone())
+            .collect::<BTreeSet<_>>();
+        let component = |parameter: &Json| {
+            let name = match parameter.get(key("keys")).and_then(Json::as_array) {
+                Some(keys) => match &keys[..] {
+                    [components, parameters, name]
+                        if components == "components" && parameters == "parameters" =>
+                    {
+                        name.as_str()?
+                    }
+                    _ => return None,
+                },
+                None => parameter.get(key("name"))?.as_str()?,
+            };
+
+            components.get(name).cloned()
+        };
+        let groups = |path: &str, parameters: &[Json], operation: &Map<String, Json>| {
+            let mut groups = Vec::new();
+
+            if !parameters.is_empty() {
+                groups.push((path.to_string(), "path", parameters.to_vec()));
+            }
+
+            let mut shared = Self::parameters(parameters, operation)
+                .into_iter()
+                .filter_map(|parameter| Some((component(parameter)?, parameter.clone())))
+                .collect::<Vec<_>>();
+            shared.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+            if !shared.is_empty() {
+                groups.push((
+                    shared
+                        .iter()
+                        .map(|(name, _)| name.as_str())
+                        .collect::<Vec<_>>()
+                        .join("+"),
+                    "components",
+                    shared.into_iter().map(|(_, parameter)| parameter).collect(),
+                ));
+            }
+
+            groups
+        };
+
+        let mut shared = BTreeMap::<String, (&str, Vec<Json>, Vec<Json>)>::new();
+
+        Self::operations(openapi, |path, method, parameters, operation| {
+            for (name, kind, parameters) in groups(path, parameters, operation) {
+                shared
+                    .entry(name)
+                    .or_insert_with(|| (kind, parameters, Vec::new()))
+                    .2
+                    .push(json!({
+                        "path": path,
+                        "method": method,
+                        "operationId": operation.get("operationId"),
+                    }));
+            }
+        });
+        shared.retain(|_, (_, _, operations)| operations.len() > 1);
+
+        Self::operations(openapi, |path, _, parameters, operation| {
+            let names = groups(path, parameters, operation)
+                .into_iter()
+                .map(|(name, _, _)| name)
+                .filter(|name| shared.contains_key(name))
+                .collect::<Vec<_>>();
+
+            operation.insert("parameter_groups".into(), names.into());
+        });
+
+        openapi["parameter_groups"] = shared
+            .into_iter()
+            .map(|(name, (kind, parameters, operations))| {
+                json!({
+                    "name": name,
+                    "kind": kind,
+                    "parameters": parameters,
+                    "operations": operations,
+                })
+            })
+            .collect();
+    }
+
+    /// The operation's parameters, overriding the path item's ones.
+    fn parameters<'a>(parameters: &'a [Json], operation: &'a Map<String, Json>) -> Vec<&'a Json> {
+        let key = |parameter: &Json| (parameter.get("name").cloned(), parameter.get("in").cloned());
+        let operation = operation
+            .get("parameters")
+            .and_then(Json::as_array)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+
+        parameters
+            .iter()
+            .filter(|parameter| !operation.iter().any(|other| key(other) == key(parameter)))
+            .chain(operation)
+            .collect()
+    }
+
+    /// Detects the pagination style of each operation, overridable with `x-pig-pagination`.
+    ///
+    /// Kinds are `cursor`, `link`, `page` or `offset`, along with the relevant parameter names.
+    fn pagination(openapi: &mut Json) {
+        const CURSORS: [&str; 6] = [
+            "cursor",
+            "after",
+            "page_token",
+            "pagetoken",
+            "continuation_token",
+            "continuationtoken",
+        ];
+        const NEXTS: [&str; 5] = [
+            "nextcursor",
+            "next_cursor",
+            "nextpagetoken",
+            "next_page_token",
+            "next",
+        ];
+        const LIMITS: [&str; 6] = [
+            "limit",
+            "per_page",
+            "perpage",
+            "page_size",
+            "pagesize",
+            "size",
+        ];
+
+        Self::operations(openapi, |_, _, parameters, operation| {
+            let query = Self::parameters(parameters, operation)
+                .into_iter()
+                .filter(|parameter| parameter.get("in").and_then(Json::as_str) == Some("query"))
+                .filter_map(|parameter| parameter.get("name").and_then(Json::as_str))
+                .map(String::from)
+                .collect::<Vec<_>>();
+            let find = |names: &[&str]| {
+                query
+                    .iter()
+                    .find(|param| names.contains(&param.to_ascii_lowercase().as_str()))
+                    .cloned()
+            };
+            let success = operation
+                .get("responses")
+                .and_then(Json::as_object)
+                .and_then(|responses| responses.get(Self::success_response(responses)?));
+            let next = success
+                .and_then(|response| response.get("content"))
+                .and_then(Json::as_object)
+                .and_then(|content| content.iter().find(|(media, _)| Self::is_json(media)))
+                .and_then(|(_, media)| media.pointer("/schema/properties"))
+                .and_then(Json::as_object)
+                .and_then(|properties| {
+                    properties
+                        .keys()
+                        .find(|name| NEXTS.contains(&name.to_ascii_lowercase().as_str()))
+                });
+            let link = success
+                .and_then(|response| response.get("headers"))
+                .and_then(Json::as_object)
+                .is_some_and(|headers| {
+                    headers.keys().any(|name| name.eq_ignore_ascii_case("link"))
+                });
+            let limit = find(&LIMITS);
+
+            let mut pagination = if let Some(cursor) = find(&CURSORS) {
+                json!({ "kind": "cursor", "cursor_param": cursor, "limit_param": limit, "next_field": next })
+            } else if link {
+                json!({ "kind": "link", "limit_param": limit })
+            } else if let Some(page) = find(&["page", "page_number", "pagenumber"]) {
+                json!({ "kind": "page", "page_param": page, "limit_param": limit })
+            } else if let (Some(offset), Some(limit)) = (find(&["offset", "skip"]), &limit) {
+                json!({ "kind": "offset", "offset_param": offset, "limit_param": limit })
+            } else {
+                Json::Null
+            };
+
+            match operation.get("x-pig-pagination") {
+                Some(Json::Bool(false)) => pagination = Json::Null,
+                Some(Json::Object(overrides)) => {
+                    if !pagination.is_object() {
+                        pagination = json!({});
+                    }
+
+                    for (key, value) in overrides {
+                        pagination[key] = value.clone();
+                    }
+                }
+                _ => {}
+            }
+
+            operation.insert("pagination".into(), pagination);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn openapi() -> Json {
+        json!({
+            "paths": { "/pets": { "post": { "requestBody": { "content": {
+                "application/json": {
+                    "schema": { "$ref": "#/components/schemas/Pet" },
+                    "example": { "properties": { "name": { "type": "string" } } },
+                },
+            } } } } },
+            "components": { "schemas": { "Pet": {
+                "required": ["name"],
+                "properties": {
+                    "name": { "type": "string", "default": "Rex" },
+                    "tags": { "type": "array", "items": {
+                        "properties": { "label": { "type": "string", "readOnly": true } },
+                    } },
+                },
+                "x-payload": { "properties": { "name": { "type": "string" } } },
+            } } },
+        })
+    }
+
+    #[test]
+    fn properties() {
+        let original = openapi();
+        let mut openapi = original.clone();
+        Derive::properties(&mut openapi);
+        let pet = &openapi["components"]["schemas"]["Pet"];
+
+        assert_eq!(pet["properties"]["name"]["optionality"], "required");
+        assert_eq!(pet["properties"]["tags"]["optionality"], "optional");
+        let label = &pet["properties"]["tags"]["items"]["properties"]["label"];
+        assert_eq!(label["is_readonly"], true);
+
+        // Payloads are not schemas
+        let example = "/paths/~1pets/post/requestBody/content/application~1json/example";
+        assert_eq!(openapi.pointer(example), original.pointer(example));
+        let extension = "/components/schemas/Pet/x-payload";
+        assert_eq!(openapi.pointer(extension), original.pointer(extension));
+    }
+
+    #[test]
+    fn uploads() {
+        let mut openapi = json!({
+            "paths": { "/pets": { "post": { "requestBody": { "content": {
+                "multipart/form-data": {
+                    "schema": { "required": ["photo"], "properties": {
+                        "photo": { "type": "string", "format": "binary" },
+                        "thumbnail": { "type": "string", "format": "byte" },
+                    } },
+                    "example": { "type": "string", "format": "binary" },
+                },
+            } } } } },
+            "x-file": { "type": "string", "format": "binary" },
+        });
+        Derive::uploads(&mut openapi);
+        let media =
+            &openapi["paths"]["/pets"]["post"]["requestBody"]["content"]["multipart/form-data"];
+        let properties = &media["schema"]["properties"];
+
+        assert_eq!(properties["photo"]["is_binary"], true);
+        assert_eq!(properties["thumbnail"]["is_base64"], true);
+        assert_eq!(media["parts"].as_array().map(Vec::len), Some(2));
+        // Payloads are not schemas
+        assert_eq!(media["example"].get("is_binary"), None);
+        assert_eq!(openapi["x-file"].get("is_binary"), None);
+    }
+
+    #[test]
+    fn body_kinds() {
+        let mut openapi = json!({ "paths": { "/pets": {
+            "get": { "responses": {
+                "200": { "content": {
+                    "text/plain": {},
+                    "application/vnd.pet+json; charset=utf-8": {
+                        "schema": { "type": "array" },
+                    },
+                } },
+            } },
+            "post": {
+                "requestBody": { "content": { "multipart/form-data": {} } },
+                "responses": { "201": { "content": { "text/event-stream": {} } } },
+            },
+            "put": {
+                "requestBody": { "content": { "application/x-www-form-urlencoded": {} } },
+                "responses": { "default": { "content": { "application/octet-stream": {} } } },
+            },
+            "delete": { "responses": { "204": {} } },
+        } } });
+        Derive::body_kinds(&mut openapi);
+        let kinds = |method: &str| {
+            let operation = &openapi["paths"]["/pets"][method];
+            (
+                operation["request_kind"].as_str().unwrap_or_default(),
+                operation["response_kind"].as_str().unwrap_or_default(),
+            )
+        };
+
+        assert_eq!(kinds("get"), ("no-body", "json-array"));
+        assert_eq!(kinds("post"), ("multipart-form", "stream"));
+        assert_eq!(kinds("put"), ("urlencoded-form", "binary"));
+        assert_eq!(kinds("delete"), ("no-body", "no-body"));
+    }
+
+    #[test]
+    fn responses() {
+        let mut openapi = json!({ "paths": { "/pets": {
+            "get": { "responses": {
+                "default": {},
+                "5XX": {},
+                "404": {},
+                "201": {},
+                "200": {},
+                "4XX": {},
+                "400": {},
+            } },
+            "post": { "responses": { "default": { "description": "d" }, "400": {} } },
+        } } });
+        Derive::responses(&mut openapi);
+        let statuses = |method: &str| {
+            openapi["paths"]["/pets"][method]["error_responses"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(|response| format!("{} {}", response["status"], response["class"]))
+                .collect::<Vec<_>>()
+        };
+        let get = &openapi["paths"]["/pets"]["get"];
+        let post = &openapi["paths"]["/pets"]["post"];
+
+        assert_eq!(get["success_response"]["status"], "200");
+        assert_eq!(
+            statuses("get"),
+            [
+                r#""400" "client""#,
+                r#""404" "client""#,
+                r#""4XX" "client""#,
+                r#""5XX" "server""#,
+                r#""default" "default""#,
+            ]
+        );
+        // Without `2xx`, `default` is the success response
+        assert_eq!(
+            post["success_response"],
+            json!({ "description": "d", "status": "default" })
+        );
+        assert_eq!(statuses("post"), [r#""400" "client""#]);
+    }
+
+    #[test]
+    fn security() {
+        let mut openapi = json!({
+            "security": [{ "key": [] }],
+            "paths": { "/pets": {
+                "get": { "responses": {} },
+                "post": { "security": [{ "oauth": ["write"], "key": [] }, {}] },
+                "delete": { "security": [] },
+            } },
+            "components": { "securitySchemes": {
+                "key": { "type": "apiKey", "in": "header", "name": "X-Key" },
+                "oauth": { "type": "oauth2" },
+            } },
+        });
+        Derive::security(&mut openapi);
+        let operation = |method: &str| &openapi["paths"]["/pets"][method];
+
+        // The global requirements apply, unless overridden
+        assert_eq!(
+            operation("get")["security_resolved"],
+            json!([[{
+                "name": "key",
+                "scopes": [],
+                "scheme": { "type": "apiKey", "in": "header", "name": "X-Key" },
+            }]])
+        );
+        assert_eq!(operation("get")["is_public"], false);
+        assert_eq!(
+            operation("post")["security_schemes"],
+            json!(["key", "oauth"])
+        );
+        assert_eq!(
+            operation("post")["security_resolved"][0][1]["scopes"],
+            json!(["write"])
+        );
+        assert_eq!(operation("post")["is_public"], true);
+        assert_eq!(operation("delete")["security_resolved"], json!([]));
+        assert_eq!(operation("delete")["is_public"], true);
+    }
+
+    #[test]
+    fn pagination() {
+        let query = |names: &[&str]| {
+            names
+                .iter()
+                .map(|name| json!({ "name": name, "in": "query" }))
+                .collect::<Json>()
+        };
+        let mut openapi = json!({ "paths": {
+            "/cursor": {
+                "parameters": [{ "name": "limit", "in": "query" }],
+                "get": {
+                    "parameters": query(&["after"]),
+                    "responses": { "200": { "content": { "application/json": {
+                        "schema": { "properties": { "nextCursor": {} } },
+                    } } } },
+                },
+            },
+            "/link": { "get": { "responses": { "200": { "headers": { "Link": {} } } } } },
+            "/page": { "get": { "parameters": query(&["page", "per_page"]) } },
+            "/offset": { "get": { "parameters": query(&["offset", "limit"]) } },
+            "/overridden": { "get": {
+                "parameters": query(&["offset"]),
+                "x-pig-pagination": { "kind": "offset", "offset_param": "offset" },
+            } },
+            "/none": { "get": { "parameters": query(&["page"]), "x-pig-pagination": false } },
+        } });
+        Derive::pagination(&mut openapi);
+        let pagination = |path: &str| &openapi["paths"][path]["get"]["pagination"];
+
+        assert_eq!(
+            pagination("/cursor"),
+            &json!({
+                "kind": "cursor",
+                "cursor_param": "after",
+                "limit_param": "limit",
+                "next_field": "nextCursor",
+            })
+        );
+        assert_eq!(
+            pagination("/link"),
+            &json!({ "kind": "link", "limit_param": null })
+        );
+        assert_eq!(
+            pagination("/page"),
+            &json!({ "kind": "page", "page_param": "page", "limit_param": "per_page" })
+        );
+        assert_eq!(
+            pagination("/offset"),
+            &json!({ "kind": "offset", "offset_param": "offset", "limit_param": "limit" })
+        );
+        assert_eq!(
+            pagination("/overridden"),
+            &json!({ "kind": "offset", "offset_param": "offset" })
+        );
+        assert_eq!(pagination("/none"), &Json::Null);
+    }
+
+    #[test]
+    fn views() {
+        let pet = json!({
+            "required": ["id", "name", "password"],
+            "properties": {
+                "id": { "readOnly": true },
+                "name": {},
+                "password": { "writeOnly": true },
+                "owner": { "properties": { "id": { "readOnly": true } } },
+            },
+            "example": { "properties": { "id": { "readOnly": true } } },
+        });
+        let openapi = json!({ "paths": { "/pets": { "post": {
+            "requestBody": { "content": { "application/json": { "schema": pet } } },
+            "responses": { "200": { "content": { "application/json": { "schema": pet } } } },
+        } } } });
+        let view = |view: View| {
+            let mut openapi = openapi.clone();
+            Derive::view(&mut openapi, view);
+            let post = &openapi["paths"]["/pets"]["post"];
+            let schema = |pointer: &str| {
+                let schema = post.pointer(pointer).unwrap();
+                let keys = |value: &Json| {
+                    value
+                        .as_object()
+                        .map(|object| object.keys().cloned().collect::<Vec<_>>())
+                        .unwrap_or_default()
+                };
+
+                (
+                    keys(&schema["properties"]),
+                    schema["required"].clone(),
+                    keys(&schema["properties"]["owner"]["properties"]),
+                    keys(&schema["example"]["properties"]),
+                )
+            };
+
+            (
+                schema("/requestBody/content/application~1json/schema"),
+                schema("/responses/200/content/application~1json/schema"),
+            )
+        };
+
+        let (request, response) = view(View::Client);
+        assert_eq!(request.0, ["name", "owner", "password"]);
+        assert_eq!(request.1, json!(["name", "password"]));
+        assert!(request.2.is_empty());
+        // Payloads are not schemas
+        assert_eq!(request.3, ["id"]);
+        assert_eq!(response.0, ["id", "name", "owner"]);
+        assert_eq!(response.1, json!(["id", "name"]));
+        assert_eq!(response.2, ["id"]);
+        assert_eq!(view(View::Server), view(View::Client));
+
+        let (request, response) = view(View::Docs);
+        assert_eq!(request.0, ["id", "name", "owner", "password"]);
+        assert_eq!(response.0, request.0);
+    }
+
+    #[test]
+    fn lifecycle() {
+        let mut openapi = json!({
+            "paths": { "/pets": { "get": {
+                "x-since": 1.2,
+                "parameters": [{ "name": "q", "deprecated": true }],
+            } } },
+            "components": { "schemas": {
+                "Pet": { "x-deprecated-in": "2.0", "x-sunset": "3.0" },
+                "Tag": { "deprecated": false },
+                "Owner": {},
+            } },
+        });
+        Derive::lifecycle(&mut openapi, &Lifecycle::default());
+        let get = &openapi["paths"]["/pets"]["get"];
+        let schemas = &openapi["components"]["schemas"];
+
+        assert_eq!(
+            get["lifecycle"],
+            json!({
+                "added_in": "1.2",
+                "deprecated_in": null,
+                "removed_in": null,
+                "is_deprecated": false,
+            })
+        );
+        assert_eq!(get["parameters"][0]["lifecycle"]["is_deprecated"], true);
+        assert_eq!(
+            schemas["Pet"]["lifecycle"],
+            json!({
+                "added_in": null,
+                "deprecated_in": "2.0",
+                "removed_in": "3.0",
+                "is_deprecated": true,
+            })
+        );
+        assert_eq!(schemas["Tag"]["lifecycle"]["is_deprecated"], false);
+        assert_eq!(schemas["Owner"].get("lifecycle"), None);
+    }
+
+    #[test]
+    fn server_urls() {
+        let mut openapi = json!({
+            "servers": [{
+                "url": "https://{region}.example.com/{version}",
+                "variables": {
+                    "region": { "default": "eu", "enum": ["us", "eu"] },
+                    "version": { "default": "v1" },
+                },
+            }],
+            "paths": { "/pets": {
+                "servers": [{ "url": "/pets" }],
+                "get": { "servers": [{ "url": "{scheme}://pets", "variables": { "scheme": {} } }] },
+            } },
+        });
+        Derive::server_urls(&mut openapi, "$");
+        let server = &openapi["servers"][0];
+        let item = &openapi["paths"]["/pets"];
+
+        assert_eq!(server["$url"], "https://eu.example.com/v1");
+        assert_eq!(
+            server["$urls"],
+            json!(["https://eu.example.com/v1", "https://us.example.com/v1"])
+        );
+        assert_eq!(item["servers"][0]["$urls"], json!(["/pets"]));
+        // Variables without values stay as is
+        assert_eq!(item["get"]["servers"][0]["$url"], "{scheme}://pets");
+    }
+
+    #[test]
+    fn error_model() {
+        let error = json!({ "$ref": "#/components/schemas/Error", "$name": "Error" });
+        let operation = |path: &str, schemas: &[(&str, &Json)]| {
+            let errors = schemas
+                .iter()
+                .map(|(status, schema)| {
+                    json!({
+                        "status": status,
+                        "content": { "application/json": { "schema": schema } },
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            json!({
+                "path": path,
+                "method": "get",
+                "operationId": null,
+                "error_responses": errors,
+            })
+        };
+        let problem = json!({ "type": "object" });
+        let operations = json!([
+            operation("/a", &[("404", &error), ("default", &error)]),
+            operation("/b", &[("400", &problem), ("500", &error)]),
+            operation("/c", &[("404", &error)]),
+            operation("/d", &[]),
+        ]);
+
+        assert_eq!(
+            Derive::error_model(&operations, "$"),
+            json!({
+                "schema": error,
+                "name": "Error",
+                "deviations": [{
+                    "path": "/b",
+                    "method": "get",
+                    "operationId": null,
+                    "statuses": ["400"],
+                }],
+            })
+        );
+        // No majority
+        let operations = json!([
+            operation("/a", &[("404", &error)]),
+            operation("/b", &[("404", &problem)]),
+        ]);
+        assert_eq!(Derive::error_model(&operations, "$"), Json::Null);
+    }
+}
@@ -0,0 +1,87 @@
+use serde_json::Value as Json;
+
+/// A difference between two JSON values, at a JSON pointer.
+#[derive(Clone, Debug)]
+pub enum Change {
+    Added(String, Json),
+    Removed(String, Json),
+    Changed(String, Json, Json),
+}
+
+impl std::fmt::Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn short(value: &Json) -> String {
+            let value = value.to_string();
+
+            if value.chars().count() > 60 {
+                format!("{}…", value.chars().take(60).collect::<String>())
+            } else {
+                value
+            }
+        }
+
+        match self {
+            Self::Added(pointer, value) => write!(f, "+ {pointer}: {}", short(value)),
+            Self::Removed(pointer, value) => write!(f, "- {pointer}: {}", short(value)),
+            Self::Changed(pointer, old, new) => {
+                write!(f, "~ {pointer}: {} -> {}", short(old), short(new))
+            }
+        }
+    }
+}
+
+impl Change {
+    /// The JSON pointer of the change.
+    pub fn pointer(&self) -> &str {
+        match self {
+            Self::Added(pointer, _) | Self::Removed(pointer, _) | Self::Changed(pointer, ..) => {
+                pointer
+            }
+        }
+    }
+}
+
+/// Lists the changes from `old` to `new`, depth first.
+pub fn diff(old: &Json, new: &Json) -> Vec<Change> {
+    fn diff(old: &Json, new: &Json, pointer: &str, changes: &mut Vec<Change>) {
+        let child = |key: &str| format!("{pointer}/{}", key.replace('~', "~0").replace('/', "~1"));
+
+        match (old, new) {
+            (Json::Object(old), Json::Object(new)) => {
+                for (key, old) in old {
+                    match new.get(key) {
+                        Some(new) => diff(old, new, &child(key), changes),
+                        None => changes.push(Change::Removed(child(key), old.clone())),
+                    }
+                }
+
+                for (key, new) in new {
+                    if !old.contains_key(key) {
+                        changes.push(Change::Added(child(key), new.clone()));
+                    }
+                }
+            }
+            (Json::Array(old), Json::Array(new)) => {
+                for (i, old) in old.iter().enumerate() {
+                    match new.get(i) {
+                        Some(new) => diff(old, new, &child(&i.to_string()), changes),
+                        None => changes.push(Change::Removed(child(&i.to_string()), old.clone())),
+                    }
+                }
+
+                for (i, new) in new.iter().enumerate().skip(old.len()) {
+                    changes.push(Change::Added(child(&i.to_string()), new.clone()));
+                }
+            }
+            (old, new) if old != new => {
+                changes.push(Change::Changed(pointer.into(), old.clone(), new.clone()))
+            }
+            _ => {}
+        }
+    }
+
+    let mut changes = Vec::new();
+    diff(old, new, "", &mut changes);
+
+    changes
+}
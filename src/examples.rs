@@ -0,0 +1,104 @@
+use crate::PigWarning;
+use serde_json::Value as Json;
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+/// Inlines the local files of examples' `externalValue`, for templates that can not read them.
+#[derive(Debug)]
+pub struct Examples<'a> {
+    /// The files of the document, by `$file`.
+    files: &'a BTreeMap<String, PathBuf>,
+    /// The prefix of metadata keys.
+    prefix: String,
+    /// The maximum size of inlined files (in bytes).
+    max: u64,
+    /// Inlined files.
+    pub dependencies: HashSet<PathBuf>,
+    pub warnings: Vec<PigWarning>,
+}
+
+impl<'a> Examples<'a> {
+    /// The default maximum size of inlined files (in bytes).
+    pub const MAX: u64 = 1_000_000;
+
+    pub fn new(files: &'a BTreeMap<String, PathBuf>, prefix: Option<&str>, max: u64) -> Self {
+        Self {
+            files,
+            prefix: prefix.unwrap_or("$").into(),
+            max,
+            dependencies: HashSet::new(),
+            warnings: Vec::new(),
+        }
+    }
+
+    fn key(&self, name: &str) -> String {
+        format!("{}{name}", self.prefix)
+    }
+
+    /// Adds `$value` next to local `externalValue`s (relative to the file of the example): JSON
+    /// and YAML files parsed, other text files as strings.
+    pub fn inline(&mut self, value: &mut Json, file: &Path) {
+        match value {
+            Json::Array(values) => {
+                for value in values {
+                    self.inline(value, file);
+                }
+            }
+            Json::Object(object) => {
+                // Referenced objects are relative to their own file
+                let file = object
+                    .get(&self.key("file"))
+                    .and_then(Json::as_str)
+                    .and_then(|name| self.files.get(name))
+                    .map_or(file.to_path_buf(), PathBuf::clone);
+
+                if let Some(external) = object.get("externalValue").and_then(Json::as_str) {
+                    if let Some(value) = self.read(&file, external) {
+                        object.insert(self.key("value"), value);
+                    }
+                }
+
+                for value in object.values_mut() {
+                    self.inline(value, &file);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The content of `external`, relative to `file`, unless remote, missing or too large.
+    fn read(&mut self, file: &Path, external: &str) -> Option<Json> {
+        if external.contains("://") {
+            return None;
+        }
+
+        let path = file.parent()?.join(external);
+        let size = match path.metadata() {
+            Ok(metadata) => metadata.len(),
+            Err(_) => {
+                self.warnings.push(PigWarning::MissingExample(path));
+                return None;
+            }
+        };
+
+        if size > self.max {
+            self.warnings
+                .push(PigWarning::ExampleTooLarge(path, size, self.max));
+            return None;
+        }
+
+        let content = std::fs::read_to_string(&path).ok()?;
+        self.dependencies.insert(path.clone());
+
+        let extension = path.extension().and_then(|extension| extension.to_str());
+        let value = match extension {
+            Some("json") => serde_json::from_str(&content).ok(),
+            Some("yaml" | "yml") => serde_yaml::from_str(&content).ok(),
+            _ => None,
+        };
+
+        Some(value.unwrap_or(Json::String(content)))
+    }
+}
@@ -0,0 +1,518 @@
+use crate::{functions::VersionCmp, output::Output, PigError, PigResult};
+use std::error::Error;
+use tera::{ErrorKind, Tera};
+
+/// An error code, with what to try and a longer explanation.
+#[derive(Debug)]
+pub struct Explanation {
+    pub code: &'static str,
+    pub title: &'static str,
+    pub hint: &'static str,
+    pub details: &'static str,
+}
+
+/// Error codes, hints and explanations (`pig explain`).
+#[derive(Debug)]
+pub enum Explain {}
+
+impl Explain {
+    /// Codes never change meaning: new errors get new codes.
+    pub const ERRORS: [Explanation; 48] = [
+        Explanation {
+            code: "E001",
+            title: "I/O error",
+            hint: "check that the file exists and that pig can read (or write) it",
+            details:
+                "A file or directory could not be read or written, e.g. a missing `api` file, \
+                      a read-only output directory or a full disk.",
+        },
+        Explanation {
+            code: "E002",
+            title: "Invalid YAML",
+            hint: "check the syntax of `pig.yaml` and of the YAML files of the document",
+            details: "A YAML file could not be parsed, or does not have the expected shape, e.g. \
+                      `in: [templates]` instead of `in: \"templates\"` in `pig.yaml`.",
+        },
+        Explanation {
+            code: "E003",
+            title: "Invalid JSON",
+            hint: "check the syntax of the JSON files of the document",
+            details: "A JSON file (document, patch or archived context) could not be parsed.",
+        },
+        Explanation {
+            code: "E004",
+            title: "Template error",
+            hint: "check the template named in the error, and the context keys it uses",
+            details: "A template could not be parsed or rendered, e.g. `{{ schema.nmae }}` for a \
+                      missing key, or an unknown filter. `pig --preview <GLOB>` renders matching \
+                      templates without writing them.",
+        },
+        Explanation {
+            code: "E005",
+            title: "Directory walk error",
+            hint: "check the permissions and symbolic links of the `in` directory",
+            details: "A directory could not be walked, e.g. an unreadable subdirectory or a \
+                      symbolic link loop.",
+        },
+        Explanation {
+            code: "E006",
+            title: "Watch error",
+            hint: "check the limits of file watchers (e.g. `fs.inotify.max_user_watches`)",
+            details: "Files could not be watched in watch mode, often because of system limits \
+                      on large trees.",
+        },
+        Explanation {
+            code: "E007",
+            title: "Write error",
+            hint: "check the permissions of the listed files and of the `out` directory",
+            details: "Some generated files could not be written. Others were written.",
+        },
+        Explanation {
+            code: "E008",
+            title: "Denied warnings",
+            hint: "fix the warnings above, or drop `deny_warnings` / `--deny-warnings`",
+            details: "An entry with `deny_warnings: true` (or a run with `--deny-warnings`) had \
+                      warnings, e.g. duplicate `operationId`s or lint warnings.",
+        },
+        Explanation {
+            code: "E009",
+            title: "Config not found",
+            hint: "run pig from a directory containing `pig.yaml` (or below it), or pass its path",
+            details: "No `pig.yaml` was found in the current directory or its parents, or the \
+                      given config path does not exist, e.g. `pig ../api/pig.yaml`.",
+        },
+        Explanation {
+            code: "E010",
+            title: "Not a file",
+            hint: "check the path, relative to the directory of `pig.yaml`",
+            details: "A path expected to be a file is not, e.g. `api`, `overlays`, `patches` or a \
+                      TLS `ca`. Relative paths resolve against the directory of `pig.yaml`, not \
+                      the current directory.",
+        },
+        Explanation {
+            code: "E011",
+            title: "Not a directory",
+            hint: "check `in:` (or `out:`, `ref_roots`), relative to the directory of `pig.yaml`",
+            details: "A path expected to be a directory is not, e.g. `in: \"templates\"` when \
+                      `templates` does not exist next to `pig.yaml`. Relative paths resolve \
+                      against the directory of `pig.yaml`, not the current directory.",
+        },
+        Explanation {
+            code: "E012",
+            title: "Not empty",
+            hint: "pick an empty directory, or pass `--force`",
+            details: "A command refused to write into a non-empty directory, e.g. `pig split`.",
+        },
+        Explanation {
+            code: "E013",
+            title: "Invalid entry name",
+            hint: "use entry names that are plain file names, without `/` or `..`",
+            details: "An entry `name` is used as a file name (e.g. for archived contexts), so it \
+                      cannot be empty, `.`, `..` or contain path separators.",
+        },
+        Explanation {
+            code: "E014",
+            title: "Unknown entry",
+            hint: "check the entry names of `pig.yaml` (entries without `name` are numbered)",
+            details: "A command named an entry that `pig.yaml` does not have, e.g. \
+                      `pig bundle petstore out.yaml` without an entry `name: petstore`.",
+        },
+        Explanation {
+            code: "E015",
+            title: "Duplicate entry name",
+            hint: "give each entry of `pig.yaml` a distinct `name`",
+            details: "Two entries of `pig.yaml` have the same `name`.",
+        },
+        Explanation {
+            code: "E016",
+            title: "Reference outside of ref_roots",
+            hint: "add the file's directory to `ref_roots`, or move the file",
+            details: "A `$ref` points to a file outside of the entry's `ref_roots`, e.g. \
+                      `$ref: ../../secrets.yaml` with `ref_roots: [\".\"]`.",
+        },
+        Explanation {
+            code: "E017",
+            title: "Incompatible template pack",
+            hint: "upgrade pig, or use a version of the template pack supporting this context",
+            details:
+                "The template pack's `.pig.pack.yaml` declares `context_version`s that do not \
+                      include the one pig provides (`pig.context_version`).",
+        },
+        Explanation {
+            code: "E018",
+            title: "Invalid $ref",
+            hint: "check the `$ref` named in the error",
+            details: "A `$ref` is not a string, or is malformed, or (with `pig bundle`) is \
+                      recursive and can only be kept with `--components`.",
+        },
+        Explanation {
+            code: "E019",
+            title: "Invalid glob",
+            hint: "check the globs of `copy` (and `--preview`)",
+            details: "A glob could not be parsed, e.g. `copy: [\"**/*.{png\"]`.",
+        },
+        Explanation {
+            code: "E020",
+            title: "HTTP error",
+            hint: "check the URL, `headers` (or `PIG_HTTP_AUTH`), `proxy` and `tls` settings",
+            details: "A remote `$ref` could not be downloaded, e.g. a 401 without credentials or \
+                      a certificate signed by a private CA missing from `tls.<host>.ca`.",
+        },
+        Explanation {
+            code: "E021",
+            title: "Offline",
+            hint: "run once without `--offline` (or `offline: true`) to download remote files",
+            details: "Remote `$ref`s are needed but were never downloaded, and network access is \
+                      forbidden.",
+        },
+        Explanation {
+            code: "E022",
+            title: "Invalid overlay",
+            hint: "check the overlay's `actions` (`target`, `update` or `remove`)",
+            details: "An `OpenAPI` Overlay of `overlays` is malformed, e.g. an action without \
+                      `target`, or a JSONPath that does not parse.",
+        },
+        Explanation {
+            code: "E023",
+            title: "Conflicting partials",
+            hint: "define each key in a single partial of the `api` directory",
+            details:
+                "Two partials of a directory `api` define the same key with different values, \
+                      e.g. `info.title` in `info.yaml` and `main.yaml`.",
+        },
+        Explanation {
+            code: "E024",
+            title: "Rename conflict",
+            hint: "pick a new name that does not already exist in `renames`",
+            details: "A rename targets an existing schema, operation or tag, e.g. \
+                      `renames: { schemas: { PetDTO: Pet } }` when `Pet` exists.",
+        },
+        Explanation {
+            code: "E025",
+            title: "Invalid patch",
+            hint: "check the patch's operations and paths",
+            details: "A JSON Patch or JSON Merge Patch of `patches` is malformed, or does not \
+                      apply, e.g. `remove` of a missing path.",
+        },
+        Explanation {
+            code: "E026",
+            title: "Unknown method",
+            hint: "check the editor extension's version against pig's",
+            details: "`pig ide` received a JSON-RPC method it does not support.",
+        },
+        Explanation {
+            code: "E027",
+            title: "Checksum mismatch",
+            hint: "review the file's changes, then update its pin (e.g. with `sha256sum`)",
+            details: "A file pinned by `api_sha256` or `dependencies_sha256` changed.",
+        },
+        Explanation {
+            code: "E028",
+            title: "Unpinned file",
+            hint: "add the file to `dependencies_sha256`",
+            details: "With `dependencies_sha256`, every file of the document must be pinned, and \
+                      this one is not, e.g. a newly referenced `schemas/owner.yaml`.",
+        },
+        Explanation {
+            code: "E029",
+            title: "Unknown lint rule",
+            hint: "check the names of `lint.rules`",
+            details: "`lint.rules` names a rule pig does not have, e.g. `operation-ids` instead \
+                      of `operation-id`.",
+        },
+        Explanation {
+            code: "E030",
+            title: "Lint errors",
+            hint: "fix the lint issues above, or lower their severity in `lint.rules`",
+            details: "Lint rules with severity `error` reported issues, e.g. \
+                      `rules: { operation-tags: \"error\" }` and an untagged operation.",
+        },
+        Explanation {
+            code: "E031",
+            title: "Breaking changes",
+            hint: "review the breaking changes above, or version the API",
+            details: "`pig diff-spec` found changes breaking clients, e.g. a removed operation.",
+        },
+        Explanation {
+            code: "E032",
+            title: "Git error",
+            hint: "check the revision and that pig runs inside a git repository",
+            details: "A git command failed, e.g. `pig diff-spec main:openapi.yaml openapi.yaml` \
+                      outside of a repository or with a misspelled revision.",
+        },
+        Explanation {
+            code: "E033",
+            title: "Unknown api_kind",
+            hint: "use one of the registered kinds (see `api_kind` in the README)",
+            details: "`api_kind` names a kind of document no loader supports.",
+        },
+        Explanation {
+            code: "E034",
+            title: "Invalid document",
+            hint: "fix the document, or set `validate: lenient` to generate anyway",
+            details: "The document does not validate against its kind, e.g. an `OpenAPI` \
+                      operation without `responses`.",
+        },
+        Explanation {
+            code: "E035",
+            title: "$ref not found",
+            hint: "check the file and the pointer of the `$ref`",
+            details: "A `$ref` points to a missing key, e.g. `#/components/schemas/Pett`.",
+        },
+        Explanation {
+            code: "E036",
+            title: "Foreign files",
+            hint: "move those files out of `out`, pass `--force` to trash them, or set `shared_out: true` to leave them",
+            details: "The `out` directory contains files pig did not generate, which cleaning \
+                      would trash.",
+        },
+        Explanation {
+            code: "E037",
+            title: "Conflicting output",
+            hint: "make the templates write different files",
+            details: "Several templates (or `emit` calls) write the same file.",
+        },
+        Explanation {
+            code: "E038",
+            title: "Panic",
+            hint: "this is a bug in pig, please report it with the command and `pig.yaml`",
+            details: "pig crashed.",
+        },
+        Explanation {
+            code: "E039",
+            title: "Output outside of out",
+            hint: "check the template's output path (e.g. `..` in emitted paths)",
+            details: "A template tried to write outside of its entry's `out` directory.",
+        },
+        Explanation {
+            code: "E040",
+            title: "Unknown error code",
+            hint: "run `pig explain` to list error codes",
+            details: "`pig explain` was given a code that does not exist.",
+        },
+        Explanation {
+            code: "E041",
+            title: "Validation problems",
+            hint: "fix the problems above, then run `pig validate` again",
+            details: "`pig validate` found problems in entries' documents, e.g. a `$ref` to a \
+                      missing file or an `OpenAPI` file that does not validate.",
+        },
+        Explanation {
+            code: "E042",
+            title: "Not in the document",
+            hint: "check the names given to `pig extract` against the resolved document",
+            details: "`pig extract` was asked for a path, operation or schema the document does \
+                      not have, e.g. `--path /users/{id}` when the path is `/users/{userId}`.",
+        },
+        Explanation {
+            code: "E043",
+            title: "Interrupted clean",
+            hint: "run `pig recover` to finish the clean, or `pig recover --undo` to restore files",
+            details: "A previous run was killed while trashing files, leaving `.pig/journal` next \
+                      to `pig.yaml` with the planned moves. Generating again would lose track of \
+                      them.",
+        },
+        Explanation {
+            code: "E044",
+            title: "Not in a build script",
+            hint: "call `pig::build_script()` from `build.rs`, where cargo sets `OUT_DIR`",
+            details: "`pig::build_script()` writes to cargo's `OUT_DIR` and reads `pig.yaml` \
+                      from `CARGO_MANIFEST_DIR`, both set by cargo for build scripts only.",
+        },
+        Explanation {
+            code: "E045",
+            title: "Entries after each other",
+            hint: "remove one of the entries from the other's `after` in `pig.yaml`",
+            details: "Entries are generated after the entries of their `after`, so these can \
+                      not be ordered, e.g. `docs` after `client` and `client` after `docs`.",
+        },
+        Explanation {
+            code: "E046",
+            title: "Edited generated files",
+            hint: "move the edits to templates (or the `OpenAPI` document), then generate again",
+            details: "`pig verify` found generated files whose content no longer matches the \
+                      hash `checksums: true` embedded in them, i.e. files edited by hand, whose \
+                      edits the next generation would lose.",
+        },
+        Explanation {
+            code: "E047",
+            title: "Headers over plain HTTP",
+            hint: "use an `https://` URL, or remove the host from `headers` in `pig.yaml`",
+            details: "`headers` of a host (and `PIG_HTTP_AUTH`) are only sent over TLS, as they \
+                      usually hold credentials, so `$ref`s to `http://` URLs of that host can \
+                      not be downloaded.",
+        },
+        Explanation {
+            code: "E048",
+            title: "Invalid path",
+            hint: "check the paths of `pig.yaml` and of `$ref`s, e.g. for a filesystem root",
+            details: "A path that should be a file in a directory is not, e.g. a `$ref` or \
+                      `api` resolving to `/`, or a file to trash that is not in its `out` \
+                      directory.",
+        },
+    ];
+
+    /// The explanation of `err`.
+    pub fn of(err: &PigError) -> &'static Explanation {
+        let i = match err {
+            PigError::Io(_) => 0,
+            PigError::Yaml(_) => 1,
+            PigError::Json(_) => 2,
+            PigError::Tera(_) => 3,
+            PigError::Walkk(_) => 4,
+            PigError::Watch(_) => 5,
+            PigError::Write(_) => 6,
+            PigError::Warnings(_, _) => 7,
+            PigError::ConfigNotFound(_) => 8,
+            PigError::NotAFile(_) => 9,
+            PigError::NotADirectory(_) => 10,
+            PigError::NotEmpty(_) => 11,
+            PigError::InvalidEntryName(_) => 12,
+            PigError::UnknownEntry(_) => 13,
+            PigError::DuplicateEntryName(_) => 14,
+            PigError::RefOutsideRoots(_) => 15,
+            PigError::ContextVersion(_, _, _) => 16,
+            PigError::InvalidRef(_) => 17,
+            PigError::Glob(_) => 18,
+            PigError::Http(_) => 19,
+            PigError::Offline(_) => 20,
+            PigError::InvalidOverlay(_, _) => 21,
+            PigError::PartialConflict(_, _) => 22,
+            PigError::RenameConflict(_, _) => 23,
+            PigError::InvalidPatch(_, _) => 24,
+            PigError::UnknownMethod(_) => 25,
+            PigError::ChecksumMismatch(_, _, _) => 26,
+            PigError::Unpinned(_) => 27,
+            PigError::UnknownLintRule(_) => 28,
+            PigError::LintErrors(_, _) => 29,
+            PigError::BreakingChanges(_) => 30,
+            PigError::Git(_) => 31,
+            PigError::UnknownApiKind(_) => 32,
+            PigError::InvalidSpec(_, _) => 33,
+            PigError::RefNotFound(_) => 34,
+            PigError::ForeignFiles(_) => 35,
+            PigError::ConflictingOutput(_) => 36,
+            PigError::Panic(_) => 37,
+            PigError::OutputOutside(_, _) => 38,
+            PigError::UnknownCode(_) => 39,
+            PigError::Invalid(_) => 40,
+            PigError::NotInDocument(_, _) => 41,
+            PigError::Interrupted(_) => 42,
+            PigError::NotInBuildScript(_) => 43,
+            PigError::EntryCycle(_) => 44,
+            PigError::Edited(_) => 45,
+            PigError::InsecureHeaders(_) => 46,
+            PigError::InvalidPath(_) => 47,
+        };
+
+        &Self::ERRORS[i]
+    }
+
+    /// What to try after `err`, more specific than the code's hint when possible.
+    pub fn hint(err: &PigError) -> String {
+        let explanation = Self::of(err);
+
+        if let PigError::Tera(err) = err {
+            // The cause is somewhere in the chain
+            let mut source: Option<&dyn Error> = Some(err);
+
+            while let Some(err) = source {
+                if let Some(ErrorKind::FilterNotFound(name)) =
+                    err.downcast_ref::<tera::Error>().map(|err| &err.kind)
+                {
+                    return format!(
+                        "there is no filter `{name}`, registered filters are {}",
+                        Self::filters().join(", ")
+                    );
+                }
+
+                source = err.source();
+            }
+        }
+
+        explanation.hint.into()
+    }
+
+    /// The filters available to templates.
+    fn filters() -> Vec<&'static str> {
+        const FILTERS: [&str; 47] = [
+            "abs",
+            "addslashes",
+            "as_str",
+            "capitalize",
+            "concat",
+            "date",
+            "escape",
+            "escape_xml",
+            "filesizeformat",
+            "filter",
+            "first",
+            "float",
+            "get",
+            "group_by",
+            "indent",
+            "int",
+            "join",
+            "json_encode",
+            "last",
+            "length",
+            "linebreaksbr",
+            "lower",
+            "map",
+            "nth",
+            "pluralize",
+            "replace",
+            "reverse",
+            "round",
+            "slice",
+            "slugify",
+            "sort",
+            "spaceless",
+            "split",
+            "striptags",
+            "title",
+            "trim",
+            "trim_end",
+            "trim_end_matches",
+            "trim_start",
+            "trim_start_matches",
+            "truncate",
+            "unique",
+            "upper",
+            "urlencode",
+            "urlencode_strict",
+            "version_cmp",
+            "wordcount",
+        ];
+        let mut tera = Tera::default();
+        tera.register_filter(VersionCmp::NAME, VersionCmp);
+
+        FILTERS
+            .into_iter()
+            .filter(|filter| tera.get_filter(filter).is_ok())
+            .collect()
+    }
+
+    /// Prints the explanation of `code`, or lists codes.
+    pub fn print(code: Option<&str>) -> PigResult<()> {
+        let Some(code) = code else {
+            for explanation in &Self::ERRORS {
+                Output::line(format!("{} {}", explanation.code, explanation.title));
+            }
+
+            return Ok(());
+        };
+        let explanation = Self::ERRORS
+            .iter()
+            .find(|explanation| explanation.code.eq_ignore_ascii_case(code.trim()))
+            .ok_or_else(|| PigError::UnknownCode(code.into()))?;
+
+        Output::line(format!("{}: {}", explanation.code, explanation.title));
+        Output::line("");
+        Output::line(explanation.details);
+        Output::line("");
+        Output::line(format!("Try: {}", explanation.hint));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,220 @@
+use crate::{derive::Derive, PigError, PigResult};
+use serde_json::{json, Map, Value as Json};
+use std::collections::BTreeSet;
+
+/// Extracts the smallest document with some paths, operations and schemas, from a bundled one
+/// (with `$ref`s to `components` only).
+#[derive(Debug)]
+pub enum Extract {}
+
+impl Extract {
+    /// Top-level keys kept as is.
+    const KEPT: [&'static str; 6] = [
+        "openapi",
+        "info",
+        "jsonSchemaDialect",
+        "servers",
+        "security",
+        "externalDocs",
+    ];
+
+    /// Keeps the `paths` (whole path items), operations (by `operationId`) and schemas of
+    /// `components` requested, with the components they reference, transitively.
+    pub fn subset(
+        openapi: &Json,
+        paths: &[String],
+        operations: &[String],
+        schemas: &[String],
+    ) -> PigResult<Json> {
+        let mut subset = Map::new();
+
+        for key in Self::KEPT {
+            if let Some(value) = openapi.get(key) {
+                subset.insert(key.into(), value.clone());
+            }
+        }
+
+        let mut items = Map::new();
+
+        for path in paths {
+            let item = openapi["paths"]
+                .get(path)
+                .ok_or_else(|| PigError::NotInDocument("path".into(), path.clone()))?;
+
+            items.insert(path.clone(), item.clone());
+        }
+
+        'operations: for id in operations {
+            for (path, item) in openapi["paths"].as_object().into_iter().flatten() {
+                for method in Derive::METHODS {
+                    if item[method]["operationId"] != *id.as_str() {
+                        continue;
+                    }
+
+                    // With the path item's other fields, but not its other operations
+                    let kept = items.entry(path.clone()).or_insert_with(|| {
+                        let mut item = item.clone();
+                        if let Some(item) = item.as_object_mut() {
+                            item.retain(|key, _| !Derive::METHODS.contains(&key.as_str()));
+                        }
+                        item
+                    });
+                    kept[method] = item[method].clone();
+
+                    continue 'operations;
+                }
+            }
+
+            return Err(PigError::NotInDocument("operation".into(), id.clone()));
+        }
+
+        subset.insert("paths".into(), items.into());
+
+        let mut components = BTreeSet::new();
+
+        for name in schemas {
+            if openapi["components"]["schemas"].get(name).is_none() {
+                return Err(PigError::NotInDocument("schema".into(), name.clone()));
+            }
+
+            components.insert(("schemas".to_string(), name.clone()));
+        }
+
+        // Components referenced by what is kept, transitively
+        let mut queue = components.iter().cloned().collect::<Vec<_>>();
+        Self::references(&Json::Object(subset.clone()), &mut queue);
+
+        while let Some((section, name)) = queue.pop() {
+            let Some(component) = openapi["components"][&section].get(&name) else {
+                continue;
+            };
+
+            let mut references = Vec::new();
+            Self::references(component, &mut references);
+            components.insert((section, name));
+
+            queue.extend(
+                references
+                    .into_iter()
+                    .filter(|reference| !components.contains(reference)),
+            );
+        }
+
+        // Security schemes are referenced by name
+        let mut security = BTreeSet::new();
+        Self::security(&Json::Object(subset.clone()), &mut security);
+        components.extend(
+            security
+                .into_iter()
+                .map(|name| ("securitySchemes".to_string(), name)),
+        );
+
+        let mut kept = json!({});
+        for (section, name) in components {
+            if let Some(component) = openapi["components"][&section].get(&name) {
+                kept[section][name] = component.clone();
+            }
+        }
+
+        if kept.as_object().is_some_and(|kept| !kept.is_empty()) {
+            subset.insert("components".into(), kept);
+        }
+
+        // Tags of the kept operations
+        let mut tags = BTreeSet::new();
+        for item in subset["paths"]
+            .as_object()
+            .into_iter()
+            .flatten()
+            .map(|(_, item)| item)
+        {
+            for method in Derive::METHODS {
+                for tag in item[method]["tags"].as_array().into_iter().flatten() {
+                    tags.extend(tag.as_str().map(String::from));
+                }
+            }
+        }
+
+        let tags = openapi["tags"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|tag| tag["name"].as_str().is_some_and(|name| tags.contains(name)))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        if !tags.is_empty() {
+            subset.insert("tags".into(), tags.into());
+        }
+
+        Ok(subset.into())
+    }
+
+    /// The components referenced in `value` (by `$ref`s and discriminator mappings).
+    fn references(value: &Json, references: &mut Vec<(String, String)>) {
+        let mut push = |reference: &str| {
+            let Some(pointer) = reference.strip_prefix("#/components/") else {
+                return;
+            };
+            let Some((section, name)) = pointer.split_once('/') else {
+                return;
+            };
+            let name = name.replace("~1", "/").replace("~0", "~");
+
+            references.push((section.into(), name));
+        };
+
+        match value {
+            Json::Array(values) => {
+                for value in values {
+                    Self::references(value, references);
+                }
+            }
+            Json::Object(object) => {
+                if let Some(reference) = object.get("$ref").and_then(Json::as_str) {
+                    push(reference);
+                }
+
+                for reference in object
+                    .get("discriminator")
+                    .and_then(|discriminator| discriminator.get("mapping"))
+                    .and_then(Json::as_object)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|(_, reference)| reference.as_str())
+                {
+                    push(reference);
+                }
+
+                for value in object.values() {
+                    Self::references(value, references);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// The security schemes of the security requirements of `value`.
+    fn security(value: &Json, names: &mut BTreeSet<String>) {
+        match value {
+            Json::Array(values) => {
+                for value in values {
+                    Self::security(value, names);
+                }
+            }
+            Json::Object(object) => {
+                for (key, value) in object {
+                    match value {
+                        Json::Array(requirements) if key == "security" => {
+                            for requirement in requirements.iter().filter_map(Json::as_object) {
+                                names.extend(requirement.keys().cloned());
+                            }
+                        }
+                        value => Self::security(value, names),
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
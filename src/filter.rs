@@ -0,0 +1,85 @@
+use crate::{
+    config::{DropOperations, Selection},
+    derive::Derive,
+    PigResult,
+};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde_json::{Map, Value as Json};
+
+/// Removes operations from the document, before templates see it.
+#[derive(Debug)]
+pub enum Filter {}
+
+impl Filter {
+    /// Removes the operations that `drop` matches.
+    pub fn drop(openapi: &mut Json, drop: &DropOperations) {
+        Self::retain(openapi, |_, operation| {
+            let is_dropped = (drop.deprecated && operation["deprecated"] == true)
+                || drop
+                    .extensions
+                    .iter()
+                    .any(|extension| operation[extension] == true);
+
+            !is_dropped
+        });
+    }
+
+    /// Removes the operations that `include` (unless empty) does not match and those that
+    /// `exclude` matches.
+    pub fn select(openapi: &mut Json, include: &Selection, exclude: &Selection) -> PigResult<()> {
+        let globs = |selection: &Selection| {
+            let mut globs = GlobSetBuilder::new();
+            for glob in &selection.paths {
+                globs.add(Glob::new(glob)?);
+            }
+
+            PigResult::Ok(globs.build()?)
+        };
+        let matches = |selection: &Selection, globs: &GlobSet, path: &str, operation: &Json| {
+            globs.is_match(path)
+                || operation["tags"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(Json::as_str)
+                    .any(|tag| selection.tags.iter().any(|other| other == tag))
+        };
+        let (included, excluded) = (globs(include)?, globs(exclude)?);
+
+        Self::retain(openapi, |path, operation| {
+            (include.is_empty() || matches(include, &included, path, operation))
+                && !matches(exclude, &excluded, path, operation)
+        });
+
+        Ok(())
+    }
+
+    /// Keeps the operations (of `paths` and `webhooks`) for which `keep` (given their path)
+    /// is `true`, then path items not left without operations.
+    fn retain(openapi: &mut Json, keep: impl Fn(&str, &Json) -> bool) {
+        for key in ["paths", "webhooks"] {
+            let Some(items) = openapi.get_mut(key).and_then(Json::as_object_mut) else {
+                continue;
+            };
+
+            items.retain(|path, item| {
+                let Some(item) = item.as_object_mut() else {
+                    return true;
+                };
+                let had_operations = Self::has_operations(item);
+
+                item.retain(|method, operation| {
+                    !Derive::METHODS.contains(&method.as_str()) || keep(path, operation)
+                });
+
+                !had_operations || Self::has_operations(item)
+            });
+        }
+    }
+
+    fn has_operations(item: &Map<String, Json>) -> bool {
+        Derive::METHODS
+            .iter()
+            .any(|method| item.contains_key(*method))
+    }
+}
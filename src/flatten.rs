@@ -0,0 +1,180 @@
+use serde_json::{Map, Value as Json};
+
+/// Merges `allOf` compositions into single effective schemas, so that templates need not, or
+/// exposes their lineage, for templates generating inheritance.
+#[derive(Debug)]
+pub struct Flatten {
+    /// The prefix of metadata keys.
+    prefix: String,
+}
+
+impl Flatten {
+    const METADATA: [&'static str; 11] = [
+        "ref",
+        "file",
+        "keys",
+        "name",
+        "circular",
+        "shared",
+        "synthetic",
+        "flattened",
+        "parents",
+        "own_properties",
+        "inherited_properties",
+    ];
+
+    pub fn new(prefix: Option<&str>) -> Self {
+        Self {
+            prefix: prefix.unwrap_or("$").into(),
+        }
+    }
+
+    fn key(&self, name: &str) -> String {
+        format!("{}{name}", self.prefix)
+    }
+
+    /// Adds the merge of every schema with `allOf` as its `$flattened`, innermost first.
+    pub fn apply(&self, value: &mut Json) {
+        match value {
+            Json::Array(values) => {
+                for value in values {
+                    self.apply(value);
+                }
+            }
+            Json::Object(object) => {
+                for value in object.values_mut() {
+                    self.apply(value);
+                }
+
+                if let Some(Json::Array(members)) = object.get("allOf") {
+                    let flattened = self.flatten(object, members);
+                    object.insert(self.key("flattened"), flattened.into());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Adds the lineage of every schema with `allOf`, innermost first: `$parents`, its named
+    /// ancestors (nearest first, i.e. named members then their own ancestors), `$own_properties`,
+    /// its properties and those of inline members, and `$inherited_properties`, those of
+    /// ancestors.
+    pub fn lineage(&self, value: &mut Json) {
+        match value {
+            Json::Array(values) => {
+                for value in values {
+                    self.lineage(value);
+                }
+            }
+            Json::Object(object) => {
+                for value in object.values_mut() {
+                    self.lineage(value);
+                }
+
+                let Some(Json::Array(members)) = object.get("allOf") else {
+                    return;
+                };
+                let mut parents = Vec::<Json>::new();
+                let mut ancestors = Vec::<Json>::new();
+                let mut own = Map::new();
+                let mut inherited = Map::new();
+                let properties = |member: &Map<String, Json>, key: &str| {
+                    member
+                        .get(key)
+                        .and_then(Json::as_object)
+                        .cloned()
+                        .unwrap_or_default()
+                };
+
+                for member in members.iter().filter_map(Json::as_object) {
+                    let own_properties = match member.get(&self.key("own_properties")) {
+                        Some(_) => properties(member, &self.key("own_properties")),
+                        None => properties(member, "properties"),
+                    };
+                    inherited.extend(properties(member, &self.key("inherited_properties")));
+
+                    let member_parents = member
+                        .get(&self.key("parents"))
+                        .and_then(Json::as_array)
+                        .cloned()
+                        .unwrap_or_default();
+
+                    // Parents of inline members are parents too
+                    match member.get(&self.key("name")) {
+                        Some(name) => {
+                            parents.push(name.clone());
+                            ancestors.extend(member_parents);
+                            inherited.extend(own_properties);
+                        }
+                        None => {
+                            parents.extend(member_parents);
+                            own.extend(own_properties);
+                        }
+                    }
+                }
+
+                // Nearest first, then first come
+                let mut seen = Vec::new();
+                parents.extend(ancestors);
+                parents.retain(|parent| {
+                    let is_new = !seen.contains(parent);
+                    seen.push(parent.clone());
+                    is_new
+                });
+                own.extend(properties(object, "properties"));
+
+                object.insert(self.key("parents"), parents.into());
+                object.insert(self.key("own_properties"), own.into());
+                object.insert(self.key("inherited_properties"), inherited.into());
+            }
+            _ => {}
+        }
+    }
+
+    /// Merges the members of `allOf` (flattened already), then the schema itself: properties and
+    /// other keys of later ones win, `required` are joined.
+    fn flatten(&self, schema: &Map<String, Json>, members: &[Json]) -> Map<String, Json> {
+        let metadata = Self::METADATA.map(|name| self.key(name));
+        let mut flattened = Map::new();
+        let mut properties = Map::new();
+        let mut required = Vec::<Json>::new();
+
+        let members = members
+            .iter()
+            .map(|member| member.get(self.key("flattened")).unwrap_or(member))
+            .filter_map(Json::as_object)
+            .chain([schema])
+            .collect::<Vec<_>>();
+
+        for member in members {
+            for (key, value) in member {
+                match (key.as_str(), value) {
+                    ("allOf", _) => {}
+                    (key, _) if metadata.iter().any(|metadata| metadata == key) => {}
+                    ("properties", Json::Object(value)) => {
+                        properties.extend(value.clone());
+                    }
+                    ("required", Json::Array(value)) => {
+                        for name in value {
+                            if !required.contains(name) {
+                                required.push(name.clone());
+                            }
+                        }
+                    }
+                    (key, value) => {
+                        flattened.insert(key.into(), value.clone());
+                    }
+                }
+            }
+        }
+
+        if !properties.is_empty() {
+            flattened.insert("properties".into(), properties.into());
+        }
+        if !required.is_empty() {
+            flattened.insert("required".into(), required.into());
+        }
+
+        flattened
+    }
+}
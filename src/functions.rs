@@ -0,0 +1,478 @@
+use crate::{PigError, PigResult};
+use serde_json::{Map, Value as Json};
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    sync::{Arc, Mutex, MutexGuard},
+};
+use tera::{Context, Tera};
+
+/// Locks the state of a function, poisoned by a panic during an earlier call.
+fn lock<T>(state: &Mutex<T>) -> PigResult<MutexGuard<'_, T>> {
+    state
+        .lock()
+        .map_err(|_| PigError::Panic("the state of a template function is poisoned".into()))
+}
+
+/// Same as [`lock`], from a function call.
+fn lock_call<T>(state: &Mutex<T>) -> tera::Result<MutexGuard<'_, T>> {
+    lock(state).map_err(|err| tera::Error::msg(err.to_string()))
+}
+
+/// The `register(kind=..., ...)` function, collecting facts from templates.
+///
+/// Facts are grouped by `kind` and get the registering `template` unless given.
+#[derive(Clone, Default, Debug)]
+pub struct Registry {
+    template: Arc<Mutex<String>>,
+    facts: Arc<Mutex<BTreeMap<String, Vec<Json>>>>,
+}
+
+impl Registry {
+    pub const NAME: &'static str = "register";
+
+    /// Sets the template currently rendering.
+    pub fn template(&self, template: &str) -> PigResult<()> {
+        *lock(&self.template)? = template.into();
+
+        Ok(())
+    }
+
+    pub fn facts(&self) -> PigResult<Json> {
+        Ok(serde_json::to_value(&*lock(&self.facts)?)?)
+    }
+}
+
+/// The `emit(path=..., content=...)` function, queuing extra output files.
+///
+/// Paths are relative to the output directory.
+#[derive(Clone, Default, Debug)]
+pub struct Emitter {
+    files: Arc<Mutex<Vec<(String, String)>>>,
+}
+
+impl Emitter {
+    pub const NAME: &'static str = "emit";
+
+    /// Takes the files emitted so far.
+    pub fn files(&self) -> PigResult<Vec<(String, String)>> {
+        Ok(std::mem::take(&mut *lock(&self.files)?))
+    }
+}
+
+/// The `dispatch(item=..., default=...)` function, rendering the template an item chooses with
+/// its `x-pig-template` extension (or `default`), with the context plus `item`.
+///
+/// Templates dispatched to can not `dispatch` themselves.
+#[derive(Clone, Debug)]
+pub struct Dispatch {
+    tera: Arc<Tera>,
+    context: Arc<Mutex<Context>>,
+}
+
+impl Dispatch {
+    pub const NAME: &'static str = "dispatch";
+    pub const EXTENSION: &'static str = "x-pig-template";
+
+    pub fn new(tera: Tera, context: &Context) -> Self {
+        Self {
+            tera: Arc::new(tera),
+            context: Arc::new(Mutex::new(context.clone())),
+        }
+    }
+
+    /// Sets the context of the templates rendering.
+    pub fn context(&self, context: &Context) -> PigResult<()> {
+        *lock(&self.context)? = context.clone();
+
+        Ok(())
+    }
+
+    /// The templates chosen by items of `value`, i.e. variants.
+    pub fn variants(value: &Json) -> BTreeSet<String> {
+        fn variants(value: &Json, found: &mut BTreeSet<String>) {
+            match value {
+                Json::Array(values) => {
+                    for value in values {
+                        variants(value, found);
+                    }
+                }
+                Json::Object(object) => {
+                    if let Some(Json::String(template)) = object.get(Dispatch::EXTENSION) {
+                        found.insert(template.clone());
+                    }
+
+                    for value in object.values() {
+                        variants(value, found);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let mut found = BTreeSet::new();
+        variants(value, &mut found);
+
+        found
+    }
+}
+
+impl tera::Function for Dispatch {
+    fn call(&self, args: &HashMap<String, Json>) -> tera::Result<Json> {
+        let item = args
+            .get("item")
+            .ok_or_else(|| tera::Error::msg("`dispatch` requires an `item`"))?;
+        let template = item
+            .get(Self::EXTENSION)
+            .or_else(|| args.get("default"))
+            .and_then(Json::as_str)
+            .ok_or_else(|| {
+                tera::Error::msg(format!(
+                    "`dispatch` requires an item with `{}` or a `default` template",
+                    Self::EXTENSION
+                ))
+            })?;
+        let mut context = lock_call(&self.context)?.clone();
+        context.insert("item", item);
+
+        Ok(Json::String(self.tera.render(template, &context)?))
+    }
+}
+
+impl tera::Function for Emitter {
+    fn call(&self, args: &HashMap<String, Json>) -> tera::Result<Json> {
+        let arg = |name: &str| {
+            args.get(name)
+                .and_then(Json::as_str)
+                .map(String::from)
+                .ok_or_else(|| tera::Error::msg(format!("`emit` requires a `{name}` string")))
+        };
+
+        lock_call(&self.files)?.push((arg("path")?, arg("content")?));
+
+        Ok(Json::String(String::new()))
+    }
+}
+
+impl tera::Function for Registry {
+    fn call(&self, args: &HashMap<String, Json>) -> tera::Result<Json> {
+        let kind = args
+            .get("kind")
+            .and_then(Json::as_str)
+            .ok_or_else(|| tera::Error::msg("`register` requires a `kind` string"))?
+            .to_string();
+        let mut fact = args.clone().into_iter().collect::<Map<_, _>>();
+
+        if !fact.contains_key("template") {
+            fact.insert("template".into(), lock_call(&self.template)?.clone().into());
+        }
+        lock_call(&self.facts)?
+            .entry(kind)
+            .or_default()
+            .push(fact.into());
+
+        Ok(Json::String(String::new()))
+    }
+}
+
+/// The `ident(value=..., lang="rust", used=...)` function, sanitizing a string into an identifier
+/// of `lang`: runs of invalid characters become `_`, leading digits get a `_` prefix and keywords
+/// a `_` suffix.
+///
+/// Values given the same `used` register never share an identifier: values colliding with an
+/// earlier one get a numeric suffix (`_2`, `_3`, ...), and a value always gets the same identifier.
+#[derive(Clone, Default, Debug)]
+pub struct Ident {
+    /// Identifiers by value, by register.
+    used: Arc<Mutex<HashMap<String, HashMap<String, String>>>>,
+}
+
+impl Ident {
+    pub const NAME: &'static str = "ident";
+    pub const LANGS: [&'static str; 9] = [
+        "csharp",
+        "go",
+        "java",
+        "javascript",
+        "kotlin",
+        "python",
+        "rust",
+        "swift",
+        "typescript",
+    ];
+
+    /// Whether a call had a `used` register (conservatively, when unknown), whose identifiers
+    /// depend on every call before.
+    pub fn has_registers(&self) -> bool {
+        self.used.lock().map_or(true, |used| !used.is_empty())
+    }
+
+    /// The reserved words of `lang`.
+    #[rustfmt::skip]
+    fn keywords(lang: &str) -> Option<&'static [&'static str]> {
+        Some(match lang {
+            "csharp" => &[
+                "_", "abstract", "as", "base", "bool", "break", "byte", "case", "catch", "char",
+                "checked", "class", "const", "continue", "decimal", "default", "delegate", "do",
+                "double", "else", "enum", "event", "explicit", "extern", "false", "finally",
+                "fixed", "float", "for", "foreach", "goto", "if", "implicit", "in", "int",
+                "interface", "internal", "is", "lock", "long", "namespace", "new", "null",
+                "object", "operator", "out", "override", "params", "private", "protected",
+                "public", "readonly", "ref", "return", "sbyte", "sealed", "short", "sizeof",
+                "stackalloc", "static", "string", "struct", "switch", "this", "throw", "true",
+                "try", "typeof", "uint", "ulong", "unchecked", "unsafe", "ushort", "using",
+                "virtual", "void", "volatile", "while",
+            ],
+            "go" => &[
+                "_", "break", "case", "chan", "const", "continue", "default", "defer", "else",
+                "fallthrough", "for", "func", "go", "goto", "if", "import", "interface", "map",
+                "package", "range", "return", "select", "struct", "switch", "type", "var",
+            ],
+            "java" => &[
+                "_", "abstract", "assert", "boolean", "break", "byte", "case", "catch", "char",
+                "class", "const", "continue", "default", "do", "double", "else", "enum",
+                "extends", "false", "final", "finally", "float", "for", "goto", "if",
+                "implements", "import", "instanceof", "int", "interface", "long", "native", "new",
+                "null", "package", "permits", "private", "protected", "public", "record",
+                "return", "sealed", "short", "static", "strictfp", "super", "switch",
+                "synchronized", "this", "throw", "throws", "transient", "true", "try", "var",
+                "void", "volatile", "while", "yield",
+            ],
+            "javascript" | "typescript" => &[
+                "any", "as", "async", "await", "boolean", "break", "case", "catch", "class",
+                "const", "continue", "debugger", "declare", "default", "delete", "do", "else",
+                "enum", "export", "extends", "false", "finally", "for", "from", "function", "if",
+                "implements", "import", "in", "instanceof", "interface", "keyof", "let", "module",
+                "namespace", "never", "new", "null", "number", "of", "package", "private",
+                "protected", "public", "readonly", "return", "static", "string", "super",
+                "switch", "symbol", "this", "throw", "true", "try", "type", "typeof", "undefined",
+                "unknown", "var", "void", "while", "with", "yield",
+            ],
+            "kotlin" => &[
+                "_", "as", "break", "class", "continue", "do", "else", "false", "for", "fun",
+                "if", "in", "interface", "is", "null", "object", "package", "return", "super",
+                "this", "throw", "true", "try", "typealias", "typeof", "val", "var", "when",
+                "while",
+            ],
+            "python" => &[
+                "False", "None", "True", "and", "as", "assert", "async", "await", "break",
+                "class", "continue", "def", "del", "elif", "else", "except", "finally", "for",
+                "from", "global", "if", "import", "in", "is", "lambda", "nonlocal", "not", "or",
+                "pass", "raise", "return", "try", "while", "with", "yield",
+            ],
+            "rust" => &[
+                "Self", "_", "abstract", "as", "async", "await", "become", "box", "break",
+                "const", "continue", "crate", "do", "dyn", "else", "enum", "extern", "false",
+                "final", "fn", "for", "gen", "if", "impl", "in", "let", "loop", "macro", "match",
+                "mod", "move", "mut", "override", "priv", "pub", "ref", "return", "self",
+                "static", "struct", "super", "trait", "true", "try", "type", "typeof", "unsafe",
+                "unsized", "use", "virtual", "where", "while", "yield",
+            ],
+            "swift" => &[
+                "Any", "Self", "_", "as", "associatedtype", "await", "break", "case", "catch",
+                "class", "continue", "default", "defer", "deinit", "do", "else", "enum",
+                "extension", "fallthrough", "false", "fileprivate", "for", "func", "guard", "if",
+                "import", "in", "init", "inout", "internal", "is", "let", "nil", "open",
+                "operator", "precedencegroup", "private", "protocol", "public", "repeat",
+                "rethrows", "return", "self", "static", "struct", "subscript", "super", "switch",
+                "throw", "throws", "true", "try", "typealias", "var", "where", "while",
+            ],
+            _ => return None,
+        })
+    }
+
+    /// `value` as an identifier, given the `keywords` of a language.
+    pub fn sanitize(value: &str, keywords: &[&str]) -> String {
+        let mut ident = value
+            .split(|char: char| !(char.is_alphanumeric() || char == '_'))
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>()
+            .join("_");
+
+        if !ident.starts_with(|char: char| char.is_alphabetic() || char == '_') {
+            ident.insert(0, '_');
+        }
+
+        if keywords.contains(&ident.as_str()) {
+            ident.push('_');
+        }
+
+        ident
+    }
+}
+
+impl tera::Function for Ident {
+    fn call(&self, args: &HashMap<String, Json>) -> tera::Result<Json> {
+        let value = match args.get("value") {
+            Some(Json::String(value)) => value.clone(),
+            Some(Json::Number(value)) => value.to_string(),
+            _ => return Err(tera::Error::msg("`ident` requires a `value` string")),
+        };
+        let lang = args.get("lang").and_then(Json::as_str).unwrap_or("rust");
+        let keywords = Self::keywords(lang).ok_or_else(|| {
+            tera::Error::msg(format!(
+                "`ident` does not know `{lang}`, known languages are {}",
+                Self::LANGS.join(", ")
+            ))
+        })?;
+        let ident = Self::sanitize(&value, keywords);
+
+        let Some(register) = args.get("used") else {
+            return Ok(Json::String(ident));
+        };
+        let register = register
+            .as_str()
+            .ok_or_else(|| tera::Error::msg("`ident` requires `used` to be a string"))?;
+        let mut used = lock_call(&self.used)?;
+        let idents = used.entry(register.into()).or_default();
+
+        if let Some(ident) = idents.get(&value) {
+            return Ok(Json::String(ident.clone()));
+        }
+
+        let ident = (1..)
+            .map(|i| match i {
+                1 => ident.clone(),
+                i => format!("{ident}_{i}"),
+            })
+            .find(|ident| !idents.values().any(|used| used == ident))
+            .unwrap();
+        idents.insert(value, ident.clone());
+
+        Ok(Json::String(ident))
+    }
+}
+
+/// The `version_cmp(other=...)` filter, comparing versions (e.g. `lifecycle.deprecated_in`):
+/// `-1`, `0` or `1`.
+///
+/// Versions compare by numeric components (`v1.10` > `1.9`, `1.2` = `1.2.0`), then pre-releases
+/// (`1.0.0-beta` < `1.0.0`).
+#[derive(Clone, Copy, Default, Debug)]
+pub struct VersionCmp;
+
+impl VersionCmp {
+    pub const NAME: &'static str = "version_cmp";
+
+    pub fn compare(a: &str, b: &str) -> Ordering {
+        let split = |version: &str| {
+            let version = version.trim().trim_start_matches(['v', 'V']);
+            let (release, pre) = match version.split_once('-') {
+                Some((release, pre)) => (release.to_string(), Some(pre.to_string())),
+                None => (version.to_string(), None),
+            };
+
+            (release, pre)
+        };
+        let components = |a: &str, b: &str| {
+            let a = a.split('.').collect::<Vec<_>>();
+            let b = b.split('.').collect::<Vec<_>>();
+
+            (0..a.len().max(b.len()))
+                .map(|i| {
+                    let a = a.get(i).copied().unwrap_or("0");
+                    let b = b.get(i).copied().unwrap_or("0");
+
+                    match (a.parse::<u64>(), b.parse::<u64>()) {
+                        (Ok(a), Ok(b)) => a.cmp(&b),
+                        _ => a.cmp(b),
+                    }
+                })
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or(Ordering::Equal)
+        };
+        let (a, a_pre) = split(a);
+        let (b, b_pre) = split(b);
+
+        components(&a, &b).then_with(|| match (a_pre, b_pre) {
+            (None, None) => Ordering::Equal,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(_), None) => Ordering::Less,
+            (Some(a), Some(b)) => components(&a, &b),
+        })
+    }
+}
+
+impl tera::Filter for VersionCmp {
+    fn filter(&self, value: &Json, args: &HashMap<String, Json>) -> tera::Result<Json> {
+        let version = |value: &Json| match value {
+            Json::String(version) => Some(version.clone()),
+            Json::Number(version) => Some(version.to_string()),
+            _ => None,
+        };
+        let value = version(value)
+            .ok_or_else(|| tera::Error::msg("`version_cmp` filters a version string"))?;
+        let other = args
+            .get("other")
+            .and_then(version)
+            .ok_or_else(|| tera::Error::msg("`version_cmp` requires an `other` version"))?;
+
+        Ok(match Self::compare(&value, &other) {
+            Ordering::Less => -1,
+            Ordering::Equal => 0,
+            Ordering::Greater => 1,
+        }
+        .into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tera::{Filter, Function};
+
+    fn call(ident: &Ident, value: &str, used: Option<&str>) -> Json {
+        let mut args = HashMap::from([("value".to_string(), Json::from(value))]);
+        args.extend(used.map(|used| ("used".to_string(), Json::from(used))));
+
+        ident.call(&args).unwrap()
+    }
+
+    #[test]
+    fn registers() {
+        let ident = Ident::default();
+
+        assert_eq!(call(&ident, "foo-bar", None), "foo_bar");
+        assert!(!ident.has_registers());
+
+        assert_eq!(call(&ident, "foo-bar", Some("types")), "foo_bar");
+        assert_eq!(call(&ident, "foo bar", Some("types")), "foo_bar_2");
+        assert_eq!(call(&ident, "foo-bar", Some("types")), "foo_bar");
+        assert_eq!(call(&ident, "foo bar", Some("fields")), "foo_bar");
+        assert!(ident.has_registers());
+    }
+
+    #[test]
+    fn version_cmp() {
+        let cmp = |a: &str, b: &str| {
+            VersionCmp
+                .filter(&a.into(), &HashMap::from([("other".into(), b.into())]))
+                .unwrap()
+        };
+
+        assert_eq!(cmp("v1.10", "1.9"), 1);
+        assert_eq!(cmp("1.2", "1.2.0"), 0);
+        assert_eq!(cmp("1.0.0-beta", "1.0.0"), -1);
+        assert_eq!(cmp("1.0.0-alpha", "1.0.0-beta"), -1);
+        assert_eq!(cmp("2", "10"), -1);
+        assert!(VersionCmp.filter(&Json::Null, &HashMap::new()).is_err());
+    }
+
+    #[test]
+    fn poisoned_state() {
+        let registry = Registry::default();
+        let template = registry.template.clone();
+        let _ = std::thread::spawn(move || {
+            let _template = template.lock();
+            panic!("while registering");
+        })
+        .join();
+
+        assert!(matches!(
+            registry.template("a.jinja"),
+            Err(PigError::Panic(_))
+        ));
+        let args = HashMap::from([("kind".to_string(), Json::from("route"))]);
+        assert!(registry.call(&args).is_err());
+    }
+}
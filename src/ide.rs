@@ -0,0 +1,377 @@
+use crate::{
+    config::{Api, Config, ConfigEntry},
+    output::Output,
+    pig::Pig,
+    PigError, PigResult,
+};
+use serde_json::{json, Value as Json};
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+/// A minimal language server for template authors, over stdio (JSON-RPC with `Content-Length`
+/// headers, as in LSP).
+///
+/// Completes context keys (e.g. `components.schemas.` in templates) and goes to the definition
+/// of context values in the `OpenAPI` files (using `$file` and `$keys`). Also answers
+/// `pig/templates` (the templates of every entry) and `pig/context` (an entry's context).
+#[derive(Debug)]
+pub struct Ide {
+    config: Config,
+    /// Contexts, by entry name.
+    contexts: HashMap<String, Json>,
+    /// Open documents, by URI.
+    documents: HashMap<String, String>,
+}
+
+impl Ide {
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            contexts: HashMap::new(),
+            documents: HashMap::new(),
+        }
+    }
+
+    pub fn run(mut self) -> PigResult<()> {
+        // Stdout is the protocol stream
+        Output::stderr();
+
+        let mut stdin = BufReader::new(std::io::stdin().lock());
+        let mut stdout = std::io::stdout().lock();
+
+        while let Some(message) = Self::read(&mut stdin)? {
+            let method = message["method"].as_str().unwrap_or_default();
+            let params = &message["params"];
+
+            if method == "exit" {
+                break;
+            }
+
+            let result = self.handle(method, params);
+
+            // Notifications get no response
+            let Some(id) = message.get("id") else {
+                if let Err(err) = result {
+                    Output::error(err);
+                }
+                continue;
+            };
+            let response = match result {
+                Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                Err(err) => {
+                    let code = match err {
+                        PigError::UnknownMethod(_) => -32601,
+                        _ => -32603,
+                    };
+
+                    json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "error": { "code": code, "message": err.to_string() },
+                    })
+                }
+            };
+            let response = serde_json::to_string(&response)?;
+
+            write!(
+                stdout,
+                "Content-Length: {}\r\n\r\n{response}",
+                response.len()
+            )?;
+            stdout.flush()?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads a message, if any.
+    fn read(stdin: &mut impl BufRead) -> PigResult<Option<Json>> {
+        let mut length = None;
+
+        loop {
+            let mut header = String::new();
+
+            if stdin.read_line(&mut header)? == 0 {
+                return Ok(None);
+            }
+
+            let header = header.trim();
+            if header.is_empty() {
+                break;
+            }
+
+            if let Some((name, value)) = header.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    length = value.trim().parse::<usize>().ok();
+                }
+            }
+        }
+
+        let mut content = vec![0; length.unwrap_or_default()];
+        stdin.read_exact(&mut content)?;
+
+        Ok(Some(serde_json::from_slice(&content)?))
+    }
+
+    fn handle(&mut self, method: &str, params: &Json) -> PigResult<Json> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+
+        match method {
+            "initialize" => Ok(json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "completionProvider": { "triggerCharacters": ["."] },
+                    "definitionProvider": true,
+                },
+                "serverInfo": { "name": "pig", "version": env!("CARGO_PKG_VERSION") },
+            })),
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.documents.insert(uri.into(), text.into());
+                Ok(Json::Null)
+            }
+            "textDocument/didChange" => {
+                if let Some(text) = params["contentChanges"]
+                    .as_array()
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str())
+                {
+                    self.documents.insert(uri.into(), text.into());
+                }
+                Ok(Json::Null)
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+                Ok(Json::Null)
+            }
+            // Specs may have changed
+            "textDocument/didSave" => {
+                self.contexts.clear();
+                Ok(Json::Null)
+            }
+            "textDocument/completion" => self.completion(uri, &params["position"]),
+            "textDocument/definition" => self.definition(uri, &params["position"]),
+            "pig/templates" => self.templates(),
+            "pig/context" => {
+                let name = params["entry"].as_str().unwrap_or_default();
+                let entry = self
+                    .config
+                    .entries
+                    .iter()
+                    .find(|entry| entry.name == name)
+                    .cloned()
+                    .ok_or_else(|| PigError::UnknownEntry(name.into()))?;
+
+                self.context(&entry).cloned()
+            }
+            "initialized" | "shutdown" | "$/cancelRequest" | "$/setTrace" => Ok(Json::Null),
+            _ => Err(PigError::UnknownMethod(method.into())),
+        }
+    }
+
+    fn templates(&self) -> PigResult<Json> {
+        let mut templates = Vec::new();
+
+        for entry in &self.config.entries {
+            let tera = Pig::tera(entry)?;
+            let mut names = tera.get_template_names().collect::<Vec<_>>();
+            names.sort();
+
+            for name in names {
+                templates.push(json!({
+                    "entry": entry.name,
+                    "template": name,
+                    "path": entry.input.join(name),
+                }));
+            }
+        }
+
+        Ok(templates.into())
+    }
+
+    /// The context of `entry`, computed once.
+    fn context(&mut self, entry: &ConfigEntry) -> PigResult<&Json> {
+        if !self.contexts.contains_key(&entry.name) {
+            let mut tera = Pig::tera(entry)?;
+            let (_, context) = Pig::openapi(entry, &mut tera)?;
+            self.contexts.insert(entry.name.clone(), context);
+        }
+
+        Ok(&self.contexts[&entry.name])
+    }
+
+    /// The entry of the template at `uri`, and the text before and after the cursor on its line.
+    fn cursor(
+        &self,
+        uri: &str,
+        position: &Json,
+    ) -> PigResult<Option<(ConfigEntry, String, String)>> {
+        let path = PathBuf::from(
+            uri.strip_prefix("file://")
+                .unwrap_or(uri)
+                .replace("%20", " "),
+        );
+        let Some(entry) = self
+            .config
+            .entries
+            .iter()
+            .find(|entry| path.starts_with(&entry.input))
+        else {
+            return Ok(None);
+        };
+        let text = match self.documents.get(uri) {
+            Some(text) => text.clone(),
+            None => std::fs::read_to_string(&path)?,
+        };
+        let line = position["line"].as_u64().unwrap_or_default() as usize;
+        let character = position["character"].as_u64().unwrap_or_default() as usize;
+        let line = text.lines().nth(line).unwrap_or_default();
+        let before = line.chars().take(character).collect();
+        let after = line.chars().skip(character).collect();
+
+        Ok(Some((entry.clone(), before, after)))
+    }
+
+    fn is_key(char: char) -> bool {
+        char.is_alphanumeric() || char == '_' || char == '.'
+    }
+
+    fn completion(&mut self, uri: &str, position: &Json) -> PigResult<Json> {
+        let Some((entry, before, _)) = self.cursor(uri, position)? else {
+            return Ok(Json::Array(Vec::new()));
+        };
+        let start = before
+            .char_indices()
+            .rev()
+            .take_while(|(_, char)| Self::is_key(*char))
+            .last()
+            .map_or(before.len(), |(i, _)| i);
+        let expression = &before[start..];
+        let (path, prefix) = expression.rsplit_once('.').unwrap_or(("", expression));
+        let context = self.context(&entry)?;
+        let value = path
+            .split('.')
+            .filter(|key| !key.is_empty())
+            .try_fold(context, |value, key| value.get(key));
+
+        let items = value
+            .and_then(Json::as_object)
+            .into_iter()
+            .flatten()
+            .filter(|(key, _)| key.starts_with(prefix))
+            .map(|(key, value)| {
+                let detail = match value {
+                    Json::Null => "null",
+                    Json::Bool(_) => "boolean",
+                    Json::Number(_) => "number",
+                    Json::String(_) => "string",
+                    Json::Array(_) => "array",
+                    Json::Object(_) => "object",
+                };
+
+                json!({ "label": key, "kind": 10, "detail": detail })
+            })
+            .collect();
+
+        Ok(Json::Array(items))
+    }
+
+    fn definition(&mut self, uri: &str, position: &Json) -> PigResult<Json> {
+        let Some((entry, before, after)) = self.cursor(uri, position)? else {
+            return Ok(Json::Null);
+        };
+        let start = before
+            .char_indices()
+            .rev()
+            .take_while(|(_, char)| Self::is_key(*char))
+            .last()
+            .map_or(before.len(), |(i, _)| i);
+        let end = after
+            .find(|char| !Self::is_key(char))
+            .unwrap_or(after.len());
+        let expression = format!("{}{}", &before[start..], &after[..end]);
+        let prefix = entry.ref_prefix.clone().unwrap_or_else(|| "$".into());
+        let base = entry.file.parent().unwrap_or(Path::new("")).to_path_buf();
+        let (file, mut keys) = match &entry.openapi {
+            Api::File(file) => (file.clone(), Vec::new()),
+            Api::Inline(_) => (entry.file.clone(), vec!["api".to_string()]),
+        };
+        let (mut file, mut found) = (file, true);
+        let mut value = self.context(&entry)?;
+
+        // The provenance of the deepest referenced object along the way
+        for key in expression.split('.').filter(|key| !key.is_empty()) {
+            let Some(next) = value.get(key) else {
+                found = false;
+                break;
+            };
+
+            value = next;
+            keys.push(key.into());
+
+            if let (Some(ref_file), Some(ref_keys)) = (
+                value.get(format!("{prefix}file")).and_then(Json::as_str),
+                value.get(format!("{prefix}keys")).and_then(Json::as_array),
+            ) {
+                file = base.join(ref_file);
+                keys = ref_keys
+                    .iter()
+                    .filter_map(Json::as_str)
+                    .map(String::from)
+                    .collect();
+            }
+        }
+
+        if !found && keys.is_empty() {
+            return Ok(Json::Null);
+        }
+
+        let line = std::fs::read_to_string(&file)
+            .map(|content| Self::line(&content, &keys))
+            .unwrap_or_default();
+
+        Ok(json!({
+            "uri": format!("file://{}", file.display()),
+            "range": {
+                "start": { "line": line, "character": 0 },
+                "end": { "line": line, "character": 0 },
+            },
+        }))
+    }
+
+    /// The line of `keys` in a YAML (or JSON) document, as far as they are found.
+    fn line(content: &str, keys: &[String]) -> usize {
+        let lines = content.lines().collect::<Vec<_>>();
+        let (mut line, mut indent) = (0, None);
+
+        for key in keys {
+            let found = lines.iter().enumerate().skip(line).find(|(i, text)| {
+                let trimmed = text.trim_start();
+                let depth = text.len() - trimmed.len();
+
+                (*i > line || indent.is_none())
+                    && indent.is_none_or(|indent| depth > indent)
+                    && [
+                        format!("{key}:"),
+                        format!("\"{key}\":"),
+                        format!("'{key}':"),
+                    ]
+                    .iter()
+                    .any(|key| trimmed.starts_with(key.as_str()))
+            });
+
+            match found {
+                Some((i, text)) => {
+                    line = i;
+                    indent = Some(text.len() - text.trim_start().len());
+                }
+                None => break,
+            }
+        }
+
+        line
+    }
+}
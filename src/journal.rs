@@ -0,0 +1,229 @@
+use crate::{output::Output, PigError, PigResult};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{create_dir_all, read, remove_file, rename, write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// The moves of a clean, written before making them so that an interrupted clean can be
+/// completed or undone (`pig recover`).
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Journal {
+    #[serde(skip)]
+    file: PathBuf,
+    /// The trash snapshot.
+    pub trash: PathBuf,
+    /// Files to trash, and where.
+    pub moves: Vec<(PathBuf, PathBuf)>,
+}
+
+impl Journal {
+    const FILE: &'static str = ".pig/journal";
+
+    /// The journal of the config in `root`, if a clean was interrupted.
+    pub fn read(root: &Path) -> PigResult<Option<Self>> {
+        let file = root.join(Self::FILE);
+
+        match read(&file) {
+            Ok(journal) => Ok(Some(Self {
+                file,
+                ..serde_json::from_slice(&journal)?
+            })),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Fails if a clean was interrupted, as cleaning again would lose track of its files.
+    pub fn check(root: &Path) -> PigResult<()> {
+        match root.join(Self::FILE) {
+            file if file.exists() => Err(PigError::Interrupted(file)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Journals `moves` to `trash`, before making them.
+    pub fn write(root: &Path, trash: &Path, moves: Vec<(PathBuf, PathBuf)>) -> PigResult<Self> {
+        let journal = Self {
+            file: root.join(Self::FILE),
+            trash: trash.into(),
+            moves,
+        };
+
+        create_dir_all(Self::parent(&journal.file)?)?;
+        write(&journal.file, serde_json::to_vec_pretty(&journal)?)?;
+
+        Ok(journal)
+    }
+
+    /// Forgets the journal, once its moves are made (or undone).
+    pub fn remove(self) -> PigResult<()> {
+        remove_file(&self.file)?;
+
+        Ok(())
+    }
+
+    /// Makes the moves not made yet, returning how many.
+    pub fn complete(self) -> PigResult<usize> {
+        let mut count = 0;
+
+        for (from, to) in &self.moves {
+            if from.exists() && !to.exists() {
+                create_dir_all(Self::parent(to)?)?;
+                rename(from, to)?;
+                count += 1;
+            }
+        }
+
+        self.remove()?;
+
+        Ok(count)
+    }
+
+    /// Moves trashed files back, returning how many.
+    pub fn undo(self) -> PigResult<usize> {
+        let mut count = 0;
+
+        for (from, to) in &self.moves {
+            if to.exists() && !from.exists() {
+                create_dir_all(Self::parent(from)?)?;
+                rename(to, from)?;
+                count += 1;
+            }
+        }
+
+        self.remove()?;
+
+        Ok(count)
+    }
+
+    /// The directory of `file`.
+    fn parent(file: &Path) -> PigResult<&Path> {
+        file.parent()
+            .ok_or_else(|| PigError::InvalidPath(file.to_path_buf()))
+    }
+}
+
+/// Defers Ctrl-C until dropped, so that moves are not interrupted halfway.
+#[derive(Debug)]
+pub struct Deferred {
+    /// The handler to restore.
+    #[cfg(unix)]
+    previous: libc::sighandler_t,
+}
+
+impl Deferred {
+    pub fn interrupts() -> Self {
+        #[cfg(unix)]
+        {
+            extern "C" fn interrupt(_: libc::c_int) {
+                INTERRUPTED.store(true, Ordering::Relaxed);
+            }
+
+            // SAFETY: the handler only stores an atomic
+            let previous = unsafe {
+                libc::signal(
+                    libc::SIGINT,
+                    interrupt as extern "C" fn(libc::c_int) as libc::sighandler_t,
+                )
+            };
+
+            Self { previous }
+        }
+
+        #[cfg(not(unix))]
+        Self {}
+    }
+}
+
+impl Drop for Deferred {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        {
+            // SAFETY: restores the previous handler
+            unsafe {
+                libc::signal(libc::SIGINT, self.previous);
+            }
+
+            // Handled by the previous handler instead, e.g. when watching
+            if self.previous != libc::SIG_DFL && INTERRUPTED.swap(false, Ordering::Relaxed) {
+                // SAFETY: signals this process only
+                unsafe {
+                    libc::raise(libc::SIGINT);
+                }
+            }
+        }
+
+        if INTERRUPTED.load(Ordering::Relaxed) {
+            Output::error("Interrupted");
+            std::process::exit(130);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{read_to_string, remove_dir_all};
+
+    /// A fresh directory with `a` and `dir/b` files.
+    fn root(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("pig-journal-{name}-{}", std::process::id()));
+        let _ = remove_dir_all(&root);
+        create_dir_all(root.join("out/dir")).unwrap();
+        write(root.join("out/a"), "a").unwrap();
+        write(root.join("out/dir/b"), "b").unwrap();
+
+        root
+    }
+
+    fn moves(root: &Path) -> Vec<(PathBuf, PathBuf)> {
+        ["a", "dir/b"]
+            .into_iter()
+            .map(|file| (root.join("out").join(file), root.join("trash").join(file)))
+            .collect()
+    }
+
+    #[test]
+    fn complete() {
+        let root = root("complete");
+        Journal::write(&root, &root.join("trash"), moves(&root)).unwrap();
+
+        // Interrupted after the first move
+        create_dir_all(root.join("trash")).unwrap();
+        rename(root.join("out/a"), root.join("trash/a")).unwrap();
+        assert!(matches!(
+            Journal::check(&root),
+            Err(PigError::Interrupted(_))
+        ));
+
+        let journal = Journal::read(&root).unwrap().unwrap();
+        assert_eq!(journal.moves, moves(&root));
+        assert_eq!(journal.complete().unwrap(), 1);
+        assert_eq!(read_to_string(root.join("trash/dir/b")).unwrap(), "b");
+        assert!(!root.join("out/dir/b").exists());
+        assert!(Journal::check(&root).is_ok());
+        assert!(Journal::read(&root).unwrap().is_none());
+
+        remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn undo() {
+        let root = root("undo");
+        let journal = Journal::write(&root, &root.join("trash"), moves(&root)).unwrap();
+        assert_eq!(journal.complete().unwrap(), 2);
+
+        Journal::write(&root, &root.join("trash"), moves(&root)).unwrap();
+        let journal = Journal::read(&root).unwrap().unwrap();
+        assert_eq!(journal.undo().unwrap(), 2);
+        assert_eq!(read_to_string(root.join("out/a")).unwrap(), "a");
+        assert_eq!(read_to_string(root.join("out/dir/b")).unwrap(), "b");
+        assert!(Journal::check(&root).is_ok());
+
+        remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,499 @@
+//! 🦀 OpenAPI code generation 🐷
+//!
+//! # TODO
+//! - [x] README
+//! - [x] Parse CLI args
+//! - [x] Resolve $refs
+//! - [x] Render templates
+//! - [x] Watch mode
+//! - [x] Watch `openapi.yaml` dependencies
+//! - [x] Clean output directory
+//! - [ ] Run post generation command
+//! - [ ] Template functions (cases, dbg, ...)
+//! - [ ] Error handling
+//! - [ ] Error reporting
+
+mod bundle;
+mod check;
+mod checksum;
+mod command;
+pub mod config;
+mod derive;
+mod diff;
+mod examples;
+mod explain;
+mod extract;
+mod filter;
+mod flatten;
+mod functions;
+mod ide;
+mod journal;
+pub mod loader;
+mod manifest;
+pub mod output;
+mod overlay;
+mod pack;
+mod patch;
+pub mod pig;
+pub mod progress;
+mod promote;
+mod remote;
+mod rename;
+mod resolver;
+mod shutdown;
+mod spec_diff;
+mod split;
+mod usage;
+
+use crate::{
+    command::Command,
+    config::{Config, GlobalConfig},
+    explain::Explain,
+    manifest::Manifest,
+    output::{Color, Output},
+    pig::Pig,
+};
+use clap::Parser;
+use std::path::PathBuf;
+
+pub type PigResult<T> = Result<T, PigError>;
+
+#[derive(thiserror::Error, Debug)]
+pub enum PigError {
+    #[error("Io: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Yaml: {0}")]
+    Yaml(#[from] serde_yaml::Error),
+
+    #[error("Json: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Tera: {0:#?}")]
+    Tera(#[from] tera::Error),
+
+    #[error("Walk: {0:#?}")]
+    Walkk(#[from] walkdir::Error),
+
+    #[error("Watch: {0:#?}")]
+    Watch(#[from] notify::Error),
+
+    #[error(
+        "Write: {}",
+        .0.iter()
+            .map(|(path, err)| format!("\n  {}: {err}", path.display()))
+            .collect::<String>()
+    )]
+    Write(Vec<(PathBuf, std::io::Error)>),
+
+    #[error("Denied warnings in entry {0}: {1}")]
+    Warnings(String, usize),
+
+    #[error("Config not found: {0}")]
+    ConfigNotFound(PathBuf),
+
+    #[error("Not a file: {0}")]
+    NotAFile(PathBuf),
+
+    #[error("Not a directory: {0}")]
+    NotADirectory(PathBuf),
+
+    #[error("Not empty (use --force): {0}")]
+    NotEmpty(PathBuf),
+
+    #[error("Invalid entry name: {0}")]
+    InvalidEntryName(String),
+
+    #[error("Unknown entry: {0}")]
+    UnknownEntry(String),
+
+    #[error("Duplicate entry name: {0}")]
+    DuplicateEntryName(String),
+
+    #[error("Reference outside of `ref_roots`: {0}")]
+    RefOutsideRoots(PathBuf),
+
+    #[error("Template pack {0} expects context {1}, pig provides v{2}")]
+    ContextVersion(String, pack::Versions, u64),
+
+    #[error("Invalid $ref: {0}")]
+    InvalidRef(String),
+
+    #[error("Glob: {0}")]
+    Glob(#[from] globset::Error),
+
+    #[error("Http: {0}")]
+    Http(String),
+
+    #[error("Offline, missing remote files: {}", .0.join(", "))]
+    Offline(Vec<String>),
+
+    #[error("Invalid overlay {0}: {1}")]
+    InvalidOverlay(PathBuf, String),
+
+    #[error("Conflicting {1} in partial {0}")]
+    PartialConflict(PathBuf, String),
+
+    #[error("Cannot rename to {0} {1}: already exists")]
+    RenameConflict(String, String),
+
+    #[error("Invalid patch {0}: {1}")]
+    InvalidPatch(PathBuf, String),
+
+    #[error("Unknown method: {0}")]
+    UnknownMethod(String),
+
+    #[error("Checksum mismatch for {0}: expected {1}, got {2}")]
+    ChecksumMismatch(PathBuf, String, String),
+
+    #[error("Unpinned file (not in `dependencies_sha256`): {0}")]
+    Unpinned(String),
+
+    #[error("Unknown lint rule: {0}")]
+    UnknownLintRule(String),
+
+    #[error("{1} lint errors in {0}")]
+    LintErrors(String, usize),
+
+    #[error("{0} breaking changes")]
+    BreakingChanges(usize),
+
+    #[error("Git: {0}")]
+    Git(String),
+
+    #[error("Unknown api_kind: {0}")]
+    UnknownApiKind(String),
+
+    #[error("Invalid {0} document: {1}")]
+    InvalidSpec(String, String),
+
+    #[error("$ref not found: {0}")]
+    RefNotFound(String),
+
+    #[error(
+        "Refusing to trash files not generated by pig (use --force):{}",
+        .0.iter()
+            .map(|path| format!("\n  {}", path.display()))
+            .collect::<String>()
+    )]
+    ForeignFiles(Vec<PathBuf>),
+
+    #[error("Conflicting output file: {0}")]
+    ConflictingOutput(PathBuf),
+
+    #[error("Panic: {0}")]
+    Panic(String),
+
+    #[error("Output outside of the `out` directory: {1} (template: {0})")]
+    OutputOutside(String, PathBuf),
+
+    #[error("Unknown error code: {0}")]
+    UnknownCode(String),
+
+    #[error("{0} validation problems")]
+    Invalid(usize),
+
+    #[error("No {0} {1} in the document")]
+    NotInDocument(String, String),
+
+    #[error("A clean was interrupted (see {0}), run `pig recover`")]
+    Interrupted(PathBuf),
+
+    #[error("Not in a build script: {0} is not set")]
+    NotInBuildScript(String),
+
+    #[error("Entries after each other: {}", .0.join(", "))]
+    EntryCycle(Vec<String>),
+
+    #[error("{0} generated files were edited")]
+    Edited(usize),
+
+    #[error("Refusing to send headers to {0} over plain HTTP")]
+    InsecureHeaders(String),
+
+    #[error("Invalid path: {0}")]
+    InvalidPath(PathBuf),
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum PigWarning {
+    #[error("Duplicate operationId {0}: {}", .1.join(", "))]
+    DuplicateOperationId(String, Vec<String>),
+
+    #[error("Colliding paths: {}", .0.join(", "))]
+    CollidingPaths(Vec<String>),
+
+    #[error("Colliding components/{0}: {}", .1.join(", "))]
+    CollidingComponents(String, Vec<String>),
+
+    #[error("Lint {1}: {2} ({0})")]
+    Lint(String, String, String),
+
+    #[error("Invalid OpenAPI file {0}: {1}")]
+    InvalidOpenapi(PathBuf, String),
+
+    #[error("TLS certificates of {0} are not verified (`insecure: true`)")]
+    InsecureTls(String),
+
+    #[error("Rename of {0} {1} matches nothing")]
+    RenameNoMatch(String, String),
+
+    #[error("Overlay {0}: {1} selects nothing")]
+    OverlayNoMatch(PathBuf, String),
+
+    #[error("{0} OpenAPI files, over the {1} files limit")]
+    TooManyDependencies(usize, usize),
+
+    #[error("File {0} (from template {1}) is {2} bytes, over the {3} bytes budget")]
+    FileTooLarge(PathBuf, String, u64, u64),
+
+    #[error("Generated files are {0} bytes, over the {1} bytes budget")]
+    TotalTooLarge(u64, u64),
+
+    #[error("Missing example file {0} (`externalValue`)")]
+    MissingExample(PathBuf),
+
+    #[error("Example file {0} is {1} bytes, over the {2} bytes limit (`max_example_size`)")]
+    ExampleTooLarge(PathBuf, u64, u64),
+
+    #[error("Link {0} targets no operation of the document: {1}")]
+    UnresolvedLink(String, String),
+
+    #[error(
+        "No templates (`*.jinja` files) in {0}: skipped, leaving `out` as is \
+        (see the README to get started, or use --allow-empty)"
+    )]
+    NoTemplates(PathBuf),
+
+    #[error("Operation {0} appears in no generated file")]
+    UnusedOperation(String),
+
+    #[error("Schema {0} appears in no generated file")]
+    UnusedSchema(String),
+
+    #[error("Template {0} loops over `{1}`, which is empty")]
+    EmptyLoop(String, String),
+
+    #[error("Schemas get no `$deps` nor `$dependents` with `ref_metadata: none`")]
+    NoSchemaDeps,
+}
+
+#[derive(Parser, Debug)]
+#[command(author, version, about)]
+pub struct Args {
+    /// Watch mode
+    #[arg(short, long)]
+    watch: bool,
+
+    /// Generate (honoring `--if-changed`) and signal readiness, then watch (implies `--watch`)
+    #[arg(long)]
+    once_then_watch: bool,
+
+    /// Print this message once the first generation is done, when watching
+    #[arg(long, value_name = "MESSAGE")]
+    ready_message: Option<String>,
+
+    /// Write `ready` to this file descriptor once the first generation is done, when watching
+    #[arg(long, value_name = "FD")]
+    ready_fd: Option<u32>,
+
+    /// Skip generation when nothing changed since the last one
+    #[arg(long)]
+    if_changed: bool,
+
+    /// Fail on warnings
+    #[arg(long)]
+    deny_warnings: bool,
+
+    /// Override the `out` directory of an entry (repeatable)
+    #[arg(long, value_name = "ENTRY=PATH", value_parser = Args::entry_path)]
+    out_override: Vec<(String, PathBuf)>,
+
+    /// Override a context value, by dot-separated path, with a YAML value (repeatable)
+    #[arg(long = "set", value_name = "KEY=VALUE", value_parser = Args::key_value)]
+    set: Vec<(String, String)>,
+
+    /// Forbid network access, using previously downloaded remote `$ref`s
+    #[arg(long)]
+    offline: bool,
+
+    /// Download remote `$ref`s, ignoring `remote_ttl`
+    #[arg(long)]
+    refresh: bool,
+
+    /// Trash files in output directories even when not generated by pig
+    #[arg(long)]
+    force: bool,
+
+    /// Generate (and clean) entries without templates, instead of skipping them
+    #[arg(long)]
+    allow_empty: bool,
+
+    /// Print templates matching this glob instead of generating
+    #[arg(long, value_name = "GLOB")]
+    preview: Option<String>,
+
+    /// Archive the contexts of each run in this directory
+    #[arg(long, value_name = "DIR")]
+    archive_contexts: Option<PathBuf>,
+
+    /// Threads to use (defaults to the available parallelism, up to 8)
+    #[arg(short, long, value_name = "N", env = "PIG_JOBS", value_parser = clap::value_parser!(u16).range(1..))]
+    jobs: Option<u16>,
+
+    /// More output (repeatable, e.g. `-vv` lists every trashed file)
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Colored output (defaults to the global config's `color`, else `auto`)
+    #[arg(long, value_enum)]
+    color: Option<Color>,
+
+    /// No emojis
+    #[arg(long)]
+    plain: bool,
+
+    /// Append everything, including debug messages, to this file
+    #[arg(long, value_name = "PATH")]
+    log_file: Option<PathBuf>,
+
+    /// Rotate the log file when over this size (in bytes)
+    #[arg(long, value_name = "BYTES", default_value_t = 10_000_000)]
+    log_max_size: u64,
+
+    /// Report panics as errors (for testing)
+    #[arg(long, hide = true)]
+    strict_no_panic: bool,
+
+    /// Path of the `pig.yaml` file (leave empty to search upwards from the current directory)
+    config: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+impl Args {
+    fn run(mut self) -> PigResult<()> {
+        match self.command.take() {
+            Some(command) => command.run(self),
+            None => Config::new(self).and_then(Pig::oink),
+        }
+    }
+
+    fn entry_path(arg: &str) -> Result<(String, PathBuf), String> {
+        match arg.split_once('=') {
+            Some((entry, path)) if !entry.is_empty() && !path.is_empty() => {
+                Ok((entry.into(), path.into()))
+            }
+            _ => Err(format!("expected ENTRY=PATH, got `{arg}`")),
+        }
+    }
+
+    fn key_value(arg: &str) -> Result<(String, String), String> {
+        match arg.split_once('=') {
+            Some((key, value)) if !key.is_empty() && key.split('.').all(|key| !key.is_empty()) => {
+                Ok((key.into(), value.into()))
+            }
+            _ => Err(format!("expected KEY=VALUE, got `{arg}`")),
+        }
+    }
+}
+
+pub fn cli() {
+    let args = Args::parse();
+    // Errors of the global config are reported with the project's
+    let color = args
+        .color
+        .or_else(|| GlobalConfig::new().ok()?.color)
+        .unwrap_or_default();
+    Output::init(color, args.plain);
+
+    if let Some(log) = &args.log_file {
+        if let Err(err) = Output::log(log, args.log_max_size) {
+            Output::error(&err);
+            std::process::exit(1);
+        }
+    }
+
+    let result = if args.strict_no_panic {
+        std::panic::set_hook(Box::new(|_| {}));
+        std::panic::catch_unwind(|| args.run()).unwrap_or_else(|panic| {
+            Err(PigError::Panic(
+                panic
+                    .downcast_ref::<&str>()
+                    .map(|panic| panic.to_string())
+                    .or_else(|| panic.downcast_ref::<String>().cloned())
+                    .unwrap_or_default(),
+            ))
+        })
+    } else {
+        args.run()
+    };
+
+    if let Err(err) = result {
+        let explanation = Explain::of(&err);
+        Output::error(&err);
+        Output::info(format!(
+            "Try: {} (see `pig explain {}`)",
+            Explain::hint(&err),
+            explanation.code
+        ));
+
+        std::process::exit(if let PigError::Panic(_) = err { 101 } else { 1 });
+    }
+}
+
+/// Generates from a `build.rs`: the entries of the crate's `pig.yaml` are written to
+/// `$OUT_DIR/<entry>`, and cargo runs the build script again when `pig.yaml`, templates or the
+/// `OpenAPI` files change.
+///
+/// ```ignore
+/// // build.rs
+/// fn main() {
+///     pig::build_script().unwrap();
+/// }
+///
+/// // src/lib.rs
+/// include!(concat!(env!("OUT_DIR"), "/api/client.rs"));
+/// ```
+pub fn build_script() -> PigResult<()> {
+    let var = |name: &str| {
+        std::env::var_os(name)
+            .map(PathBuf::from)
+            .ok_or_else(|| PigError::NotInBuildScript(name.into()))
+    };
+    let out = var("OUT_DIR")?;
+    let file = var("CARGO_MANIFEST_DIR")?.join("pig.yaml");
+
+    // Stdout is for cargo
+    Output::init(Color::Never, true);
+    Output::stderr();
+
+    let mut config = Config::new(Args::parse_from(["pig".as_ref(), file.as_os_str()]))?;
+
+    for entry in &mut config.entries {
+        entry.output = out.join(&entry.name);
+        std::fs::create_dir_all(&entry.output)?;
+    }
+
+    let entries = config.entries.clone();
+    Output::data(format!("cargo:rerun-if-changed={}", config.file.display()));
+    Pig::oink(config)?;
+
+    for entry in &entries {
+        let dependencies = Manifest::read(entry)
+            .map(|manifest| manifest.dependencies)
+            .unwrap_or_default();
+
+        for path in [&entry.input]
+            .into_iter()
+            .chain(&entry.overlays)
+            .chain(&entry.patches)
+            .chain(&dependencies)
+        {
+            Output::data(format!("cargo:rerun-if-changed={}", path.display()));
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,970 @@
+//! Loaders of input documents, by `api_kind`.
+//!
+//! Register your own with [`register`], before running `pig`.
+
+use crate::{derive::Derive, PigError, PigResult};
+use openapiv3::OpenAPI;
+use serde_json::{json, Map, Value as Json};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+static LOADERS: Mutex<Vec<Arc<dyn SpecLoader>>> = Mutex::new(Vec::new());
+
+/// Validates the files of a kind of document.
+pub trait SpecLoader: Send + Sync + std::fmt::Debug {
+    /// The `api_kind` of this loader.
+    fn kind(&self) -> &str;
+
+    /// Whether `document` (the main file) is of this kind, for auto-detection.
+    fn detect(&self, document: &Json) -> bool;
+
+    /// Normalizes `value`, the main file or another file of (normalized) `main`, e.g. upgrading
+    /// it, before validation and whatever the validation mode.
+    fn normalize(&self, value: Json, _main: Option<&Json>) -> Json {
+        value
+    }
+
+    /// Validates `value` (normalized), the main file or another file of `main`, returning a
+    /// normalized value (if any).
+    fn validate(&self, value: &Json, main: Option<&Json>) -> PigResult<Option<Json>>;
+}
+
+/// Registers a loader, taking precedence over built-in loaders (and previously registered ones).
+pub fn register(loader: impl SpecLoader + 'static) -> PigResult<()> {
+    lock()?.insert(0, Arc::new(loader));
+
+    Ok(())
+}
+
+/// Locks the registered loaders, poisoned by a panic while registering.
+fn lock() -> PigResult<MutexGuard<'static, Vec<Arc<dyn SpecLoader>>>> {
+    LOADERS
+        .lock()
+        .map_err(|_| PigError::Panic("the registered loaders are poisoned".into()))
+}
+
+/// The registered loaders then built-in loaders, in detection order (the first one, `openapi30`,
+/// being the default).
+pub(crate) fn loaders() -> PigResult<Vec<Arc<dyn SpecLoader>>> {
+    let mut loaders = lock()?.clone();
+    loaders.extend([
+        Arc::new(OpenApi30) as Arc<dyn SpecLoader>,
+        Arc::new(OpenApi31),
+        Arc::new(Swagger2),
+        Arc::new(AsyncApi),
+        Arc::new(JsonSchema),
+        Arc::new(RawYaml),
+    ]);
+
+    Ok(loaders)
+}
+
+/// The version of an `openapi` or `asyncapi` document.
+fn version<'a>(document: &'a Json, key: &str) -> Option<&'a str> {
+    document.get(key).and_then(Json::as_str)
+}
+
+/// Checks `value` is an object with `keys`.
+fn require(kind: &str, value: &Json, keys: &[&str]) -> PigResult<()> {
+    let object = value
+        .as_object()
+        .ok_or_else(|| PigError::InvalidSpec(kind.into(), "not an object".into()))?;
+
+    match keys.iter().find(|key| !object.contains_key(**key)) {
+        Some(key) => Err(PigError::InvalidSpec(
+            kind.into(),
+            format!("missing `{key}`"),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// `OpenAPI` `v3.0.x`, deserialized with `openapiv3`.
+#[derive(Debug)]
+pub struct OpenApi30;
+
+impl OpenApi30 {
+    /// Restores the `callbacks` of operations, that `openapiv3` drops.
+    fn callbacks(value: &Json, openapi: &mut Json) {
+        let Some(paths) = value.get("paths").and_then(Json::as_object) else {
+            return;
+        };
+
+        for (path, item) in paths {
+            for method in Derive::METHODS {
+                let callbacks = item
+                    .get(method)
+                    .and_then(|operation| operation.get("callbacks"));
+                let operation = openapi
+                    .get_mut("paths")
+                    .and_then(|paths| paths.get_mut(path))
+                    .and_then(|item| item.get_mut(method))
+                    .and_then(Json::as_object_mut);
+
+                if let (Some(callbacks), Some(operation)) = (callbacks, operation) {
+                    operation.insert("callbacks".into(), callbacks.clone());
+                }
+            }
+        }
+    }
+}
+
+impl SpecLoader for OpenApi30 {
+    fn kind(&self) -> &str {
+        "openapi30"
+    }
+
+    fn detect(&self, document: &Json) -> bool {
+        version(document, "openapi").is_some_and(|version| version.starts_with("3.0"))
+    }
+
+    fn validate(&self, value: &Json, main: Option<&Json>) -> PigResult<Option<Json>> {
+        match main {
+            Some(main) => {
+                // We allow omitting the mandatory fields in other files
+                let mut value = value.clone();
+
+                if let Some(object) = value.as_object_mut() {
+                    object.extend(
+                        json!({
+                            "openapi": version(main, "openapi"),
+                            "info": { "title": "", "version": "" },
+                            "paths": {},
+                        })
+                        .as_object()
+                        .unwrap()
+                        .clone(),
+                    );
+                }
+
+                // Make sure the file deserializes correctly into OpenAPI
+                serde_json::from_value::<OpenAPI>(value)?;
+                Ok(None)
+            }
+            // Make sure the file deserializes correctly into OpenAPI
+            None => {
+                let openapi = serde_json::from_value::<OpenAPI>(value.clone())?;
+                let mut openapi = serde_json::to_value(openapi)?;
+                Self::callbacks(value, &mut openapi);
+
+                Ok(Some(openapi))
+            }
+        }
+    }
+}
+
+/// `OpenAPI` `v3.1.x`, checking the main file's mandatory fields.
+///
+/// Schemas with `type` arrays (JSON Schema style) are normalized for templates written for
+/// `v3.0.x`: `type: ["string", "null"]` gives `type: "string"` and `nullable: true`, the original
+/// array being kept as `types`.
+#[derive(Debug)]
+pub struct OpenApi31;
+
+impl OpenApi31 {
+    fn types(value: &mut Json) {
+        match value {
+            Json::Null | Json::Bool(_) | Json::Number(_) | Json::String(_) => {}
+            Json::Array(values) => values.iter_mut().for_each(Self::types),
+            Json::Object(object) => {
+                if let Some(Json::Array(types)) = object.get("type") {
+                    let types = types.clone();
+                    let mut others = types.iter().filter(|ty| ty.as_str() != Some("null"));
+
+                    if types.len() > 1 && others.clone().count() < types.len() {
+                        object.insert("nullable".into(), true.into());
+                    }
+                    if let (Some(ty), None) = (others.next(), others.next()) {
+                        object.insert("type".into(), ty.clone());
+                    }
+
+                    object.insert("types".into(), types.into());
+                }
+
+                object.values_mut().for_each(Self::types);
+            }
+        }
+    }
+}
+
+impl SpecLoader for OpenApi31 {
+    fn kind(&self) -> &str {
+        "openapi31"
+    }
+
+    fn detect(&self, document: &Json) -> bool {
+        version(document, "openapi").is_some_and(|version| version.starts_with("3.1"))
+    }
+
+    fn normalize(&self, mut value: Json, _: Option<&Json>) -> Json {
+        Self::types(&mut value);
+        value
+    }
+
+    fn validate(&self, value: &Json, main: Option<&Json>) -> PigResult<Option<Json>> {
+        if main.is_none() {
+            require(self.kind(), value, &["openapi", "info"])?;
+            require(self.kind(), &value["info"], &["title", "version"])?;
+
+            if !["paths", "components", "webhooks"]
+                .iter()
+                .any(|key| value.get(key).is_some())
+            {
+                return Err(PigError::InvalidSpec(
+                    self.kind().into(),
+                    "missing `paths`, `components` or `webhooks`".into(),
+                ));
+            }
+            if value
+                .get("jsonSchemaDialect")
+                .is_some_and(|dialect| !dialect.is_string())
+            {
+                return Err(PigError::InvalidSpec(
+                    self.kind().into(),
+                    "`jsonSchemaDialect` is not a string".into(),
+                ));
+            }
+            if value
+                .get("webhooks")
+                .is_some_and(|webhooks| !webhooks.is_object())
+            {
+                return Err(PigError::InvalidSpec(
+                    self.kind().into(),
+                    "`webhooks` is not an object".into(),
+                ));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+/// Swagger `v2.0`, upgraded to `OpenAPI` `v3.0.3` (then validated as such).
+///
+/// Other files only get their `$ref`s and schemas upgraded, e.g. `#/definitions/Pet` becoming
+/// `#/components/schemas/Pet`, their `definitions`, `parameters` and `responses` moving to
+/// `components` accordingly.
+#[derive(Debug)]
+pub struct Swagger2;
+
+impl Swagger2 {
+    const REFS: [(&'static str, &'static str); 3] = [
+        ("#/definitions/", "#/components/schemas/"),
+        ("#/parameters/", "#/components/parameters/"),
+        ("#/responses/", "#/components/responses/"),
+    ];
+
+    /// Upgrades `$ref`s and schema keywords, everywhere.
+    fn refs(value: &mut Json) {
+        match value {
+            Json::Null | Json::Bool(_) | Json::Number(_) | Json::String(_) => {}
+            Json::Array(values) => values.iter_mut().for_each(Self::refs),
+            Json::Object(object) => {
+                if let Some(Json::String(reference)) = object.get_mut("$ref") {
+                    let (file, pointer) = reference.split_at(reference.find('#').unwrap_or(0));
+
+                    if let Some((old, new)) =
+                        Self::REFS.iter().find(|(old, _)| pointer.starts_with(old))
+                    {
+                        *reference = format!("{file}{new}{}", &pointer[old.len()..]);
+                    }
+                }
+                if let Some(nullable) = object.remove("x-nullable") {
+                    object.insert("nullable".into(), nullable);
+                }
+                if object.get("type").and_then(Json::as_str) == Some("file") {
+                    object.insert("type".into(), "string".into());
+                    object.insert("format".into(), "binary".into());
+                }
+
+                object.values_mut().for_each(Self::refs);
+            }
+        }
+    }
+
+    /// The schema of a non-body parameter or header, from its inline keywords.
+    fn schema(parameter: &mut Map<String, Json>) -> Json {
+        const KEYWORDS: [&str; 16] = [
+            "type",
+            "format",
+            "items",
+            "collectionFormat",
+            "default",
+            "maximum",
+            "exclusiveMaximum",
+            "minimum",
+            "exclusiveMinimum",
+            "maxLength",
+            "minLength",
+            "pattern",
+            "maxItems",
+            "minItems",
+            "uniqueItems",
+            "enum",
+        ];
+
+        let mut schema = Map::new();
+
+        for keyword in KEYWORDS {
+            if let Some(value) = parameter.remove(keyword) {
+                if keyword != "collectionFormat" {
+                    schema.insert(keyword.into(), value);
+                }
+            }
+        }
+
+        schema.into()
+    }
+
+    /// Upgrades a query, path, header or cookie parameter.
+    fn parameter(mut parameter: Map<String, Json>) -> Json {
+        if parameter.contains_key("$ref") {
+            return parameter.into();
+        }
+
+        let style = match parameter.get("collectionFormat").and_then(Json::as_str) {
+            Some("ssv") => Some(("spaceDelimited", false)),
+            Some("pipes") => Some(("pipeDelimited", false)),
+            Some("multi") => Some(("form", true)),
+            Some("csv") if parameter.get("in").and_then(Json::as_str) == Some("query") => {
+                Some(("form", false))
+            }
+            _ => None,
+        };
+        let schema = Self::schema(&mut parameter);
+
+        parameter.insert("schema".into(), schema);
+        if let Some((style, explode)) = style {
+            parameter.insert("style".into(), style.into());
+            parameter.insert("explode".into(), explode.into());
+        }
+
+        parameter.into()
+    }
+
+    /// Upgrades a body parameter into a request body.
+    fn body(mut parameter: Map<String, Json>, consumes: &[Json]) -> Json {
+        let schema = parameter.remove("schema").unwrap_or_else(|| json!({}));
+        let content = consumes
+            .iter()
+            .map(|media| {
+                (
+                    media.as_str().unwrap_or_default().to_string(),
+                    json!({ "schema": schema }),
+                )
+            })
+            .collect::<Map<_, _>>();
+        let mut body = json!({ "content": content });
+
+        for key in ["description", "required"] {
+            if let Some(value) = parameter.remove(key) {
+                body[key] = value;
+            }
+        }
+
+        body
+    }
+
+    /// Upgrades form parameters into a request body.
+    fn form(parameters: Vec<Map<String, Json>>, consumes: &[Json]) -> Json {
+        let mut properties = Map::new();
+        let mut required = Vec::new();
+        let mut multipart = consumes.iter().any(|media| {
+            media
+                .as_str()
+                .is_some_and(|media| media.starts_with("multipart/"))
+        });
+
+        for mut parameter in parameters {
+            let name = parameter.remove("name").unwrap_or_default();
+            let name = name.as_str().unwrap_or_default();
+            let mut schema = Self::schema(&mut parameter);
+
+            multipart |= schema["type"] == "file";
+            Self::refs(&mut schema);
+            if let Some(description) = parameter.remove("description") {
+                schema["description"] = description;
+            }
+            if parameter.get("required").and_then(Json::as_bool) == Some(true) {
+                required.push(Json::from(name));
+            }
+
+            properties.insert(name.into(), schema);
+        }
+
+        let media = if multipart {
+            "multipart/form-data"
+        } else {
+            "application/x-www-form-urlencoded"
+        };
+        let mut schema = json!({ "type": "object", "properties": properties });
+        if !required.is_empty() {
+            schema["required"] = required.into();
+        }
+
+        json!({ "content": { media: { "schema": schema } } })
+    }
+
+    /// Upgrades a response.
+    fn response(mut response: Map<String, Json>, produces: &[Json]) -> Json {
+        if response.contains_key("$ref") {
+            return response.into();
+        }
+
+        if let Some(schema) = response.remove("schema") {
+            let examples = response.remove("examples");
+            let content = produces
+                .iter()
+                .filter_map(Json::as_str)
+                .map(|media| {
+                    let mut object = json!({ "schema": schema });
+                    if let Some(example) =
+                        examples.as_ref().and_then(|examples| examples.get(media))
+                    {
+                        object["example"] = example.clone();
+                    }
+
+                    (media.to_string(), object)
+                })
+                .collect::<Map<_, _>>();
+
+            response.insert("content".into(), content.into());
+        }
+
+        if let Some(Json::Object(headers)) = response.get_mut("headers") {
+            for header in headers.values_mut() {
+                if let Some(object) = header.as_object_mut() {
+                    let schema = Self::schema(object);
+                    object.insert("schema".into(), schema);
+                }
+            }
+        }
+
+        response.into()
+    }
+
+    /// Upgrades an operation, with the document's default media types.
+    fn operation(
+        operation: &mut Map<String, Json>,
+        parameters: &[Json],
+        consumes: &[Json],
+        produces: &[Json],
+    ) {
+        let mut media = |key, default: &[Json]| {
+            operation
+                .remove(key)
+                .and_then(|media| media.as_array().cloned())
+                .unwrap_or_else(|| default.to_vec())
+        };
+        let consumes = media("consumes", consumes);
+        let produces = media("produces", produces);
+        let (mut form, mut others) = (Vec::new(), Vec::new());
+
+        // Path item parameters are merged in, for body and form parameters to become bodies
+        let own = operation
+            .get("parameters")
+            .and_then(Json::as_array)
+            .cloned()
+            .unwrap_or_default();
+
+        for parameter in parameters.iter().chain(&own) {
+            let Some(parameter) = parameter.as_object().cloned() else {
+                continue;
+            };
+
+            match parameter.get("in").and_then(Json::as_str) {
+                Some("body") => {
+                    operation.insert("requestBody".into(), Self::body(parameter, &consumes));
+                }
+                Some("formData") => form.push(parameter),
+                _ => others.push(Self::parameter(parameter)),
+            }
+        }
+
+        if !form.is_empty() {
+            operation.insert("requestBody".into(), Self::form(form, &consumes));
+        }
+        if others.is_empty() {
+            operation.remove("parameters");
+        } else {
+            operation.insert("parameters".into(), others.into());
+        }
+
+        if let Some(Json::Object(responses)) = operation.get_mut("responses") {
+            for response in responses.values_mut() {
+                if let Some(object) = response.as_object().cloned() {
+                    *response = Self::response(object, &produces);
+                }
+            }
+        }
+    }
+
+    fn upgrade(swagger: &Json) -> Json {
+        let mut swagger = swagger.as_object().cloned().unwrap_or_default();
+        let list = |swagger: &Map<String, Json>, key| {
+            swagger
+                .get(key)
+                .and_then(Json::as_array)
+                .cloned()
+                .unwrap_or_default()
+        };
+        let consumes = list(&swagger, "consumes");
+        let produces = list(&swagger, "produces");
+        let consumes = if consumes.is_empty() {
+            vec!["application/json".into()]
+        } else {
+            consumes
+        };
+        let produces = if produces.is_empty() {
+            vec!["application/json".into()]
+        } else {
+            produces
+        };
+
+        let host = swagger.remove("host");
+        let base = swagger.remove("basePath");
+        let schemes = list(&swagger, "schemes");
+        let servers = match (
+            host.as_ref().and_then(Json::as_str),
+            base.as_ref().and_then(Json::as_str),
+        ) {
+            (None, None) => Vec::new(),
+            (None, Some(base)) => vec![json!({ "url": base })],
+            (Some(host), base) => {
+                let schemes = if schemes.is_empty() {
+                    vec!["https".into()]
+                } else {
+                    schemes
+                };
+
+                schemes
+                    .iter()
+                    .filter_map(Json::as_str)
+                    .map(|scheme| json!({ "url": format!("{scheme}://{host}{}", base.unwrap_or_default()) }))
+                    .collect()
+            }
+        };
+
+        let mut openapi = Map::new();
+        openapi.insert("openapi".into(), "3.0.3".into());
+
+        for key in ["swagger", "consumes", "produces", "schemes"] {
+            swagger.remove(key);
+        }
+        if !servers.is_empty() {
+            openapi.insert("servers".into(), servers.into());
+        }
+
+        let mut components = Map::new();
+        let mut bodies = Map::new();
+
+        if let Some(definitions) = swagger.remove("definitions") {
+            components.insert("schemas".into(), definitions);
+        }
+        if let Some(Json::Object(parameters)) = swagger.remove("parameters") {
+            let mut others = Map::new();
+
+            for (name, parameter) in parameters {
+                let Some(parameter) = parameter.as_object().cloned() else {
+                    continue;
+                };
+
+                // Body parameters are request bodies in OpenAPI 3
+                match parameter.get("in").and_then(Json::as_str) {
+                    Some("body") => {
+                        bodies.insert(name, Self::body(parameter, &consumes));
+                    }
+                    _ => {
+                        others.insert(name, Self::parameter(parameter));
+                    }
+                }
+            }
+
+            components.insert("parameters".into(), others.into());
+        }
+        if !bodies.is_empty() {
+            components.insert("requestBodies".into(), bodies.clone().into());
+        }
+        if let Some(Json::Object(responses)) = swagger.remove("responses") {
+            let responses = responses
+                .into_iter()
+                .map(|(name, response)| {
+                    let response = Self::response(
+                        response.as_object().cloned().unwrap_or_default(),
+                        &produces,
+                    );
+                    (name, response)
+                })
+                .collect::<Map<_, _>>();
+
+            components.insert("responses".into(), responses.into());
+        }
+        if let Some(Json::Object(schemes)) = swagger.remove("securityDefinitions") {
+            let schemes = schemes
+                .into_iter()
+                .map(|(name, scheme)| (name, Self::security(scheme)))
+                .collect::<Map<_, _>>();
+
+            components.insert("securitySchemes".into(), schemes.into());
+        }
+
+        if let Some(Json::Object(paths)) = swagger.get_mut("paths") {
+            for item in paths.values_mut() {
+                let Some(item) = item.as_object_mut() else {
+                    continue;
+                };
+                let parameters = item
+                    .remove("parameters")
+                    .and_then(|parameters| parameters.as_array().cloned())
+                    .unwrap_or_default();
+
+                for (method, operation) in item.iter_mut() {
+                    if method.starts_with("x-") {
+                        continue;
+                    }
+                    if let Some(operation) = operation.as_object_mut() {
+                        Self::operation(operation, &parameters, &consumes, &produces);
+                        // Body parameters referenced from operations are request bodies now
+                        Self::bodies(operation, &bodies);
+                    }
+                }
+            }
+        }
+
+        openapi.extend(swagger);
+        if !components.is_empty() {
+            openapi.insert("components".into(), components.into());
+        }
+
+        let mut openapi = Json::from(openapi);
+        Self::refs(&mut openapi);
+
+        openapi
+    }
+
+    /// Moves references to body parameters to `requestBody`.
+    fn bodies(operation: &mut Map<String, Json>, bodies: &Map<String, Json>) {
+        let Some(Json::Array(parameters)) = operation.get_mut("parameters") else {
+            return;
+        };
+        let mut body = None;
+
+        parameters.retain(|parameter| {
+            let name = parameter
+                .get("$ref")
+                .and_then(Json::as_str)
+                .and_then(|reference| reference.strip_prefix("#/parameters/"));
+
+            match name.filter(|name| bodies.contains_key(*name)) {
+                Some(name) => {
+                    body = Some(json!({ "$ref": format!("#/components/requestBodies/{name}") }));
+                    false
+                }
+                None => true,
+            }
+        });
+
+        if parameters.is_empty() {
+            operation.remove("parameters");
+        }
+        if let Some(body) = body {
+            operation.insert("requestBody".into(), body);
+        }
+    }
+
+    /// Upgrades a security scheme.
+    fn security(scheme: Json) -> Json {
+        let mut scheme = scheme.as_object().cloned().unwrap_or_default();
+
+        match scheme.get("type").and_then(Json::as_str) {
+            Some("basic") => {
+                scheme.insert("type".into(), "http".into());
+                scheme.insert("scheme".into(), "basic".into());
+            }
+            Some("oauth2") => {
+                let flow = scheme.remove("flow");
+                let flow = match flow.as_ref().and_then(Json::as_str) {
+                    Some("application") => "clientCredentials",
+                    Some("accessCode") => "authorizationCode",
+                    Some(flow) => flow,
+                    None => "implicit",
+                };
+                let mut object = Map::new();
+
+                for key in ["authorizationUrl", "tokenUrl", "scopes"] {
+                    if let Some(value) = scheme.remove(key) {
+                        object.insert(key.into(), value);
+                    }
+                }
+                scheme.insert("flows".into(), json!({ flow: object }));
+            }
+            _ => {}
+        }
+
+        scheme.into()
+    }
+}
+
+impl SpecLoader for Swagger2 {
+    fn kind(&self) -> &str {
+        "swagger2"
+    }
+
+    fn detect(&self, document: &Json) -> bool {
+        version(document, "swagger").is_some_and(|version| version.starts_with("2."))
+    }
+
+    fn normalize(&self, mut value: Json, main: Option<&Json>) -> Json {
+        match main {
+            Some(_) => {
+                if let Some(object) = value.as_object_mut() {
+                    let mut components = Map::new();
+
+                    for (old, new) in Self::REFS {
+                        let old = old.trim_start_matches("#/").trim_end_matches('/');
+                        let new = new
+                            .trim_start_matches("#/components/")
+                            .trim_end_matches('/');
+
+                        if let Some(value) = object.remove(old) {
+                            components.insert(new.into(), value);
+                        }
+                    }
+
+                    if !components.is_empty() {
+                        object.insert("components".into(), components.into());
+                    }
+                }
+
+                Self::refs(&mut value);
+
+                value
+            }
+            None => Self::upgrade(&value),
+        }
+    }
+
+    fn validate(&self, value: &Json, main: Option<&Json>) -> PigResult<Option<Json>> {
+        match main {
+            Some(_) => Ok(None),
+            // Upgraded already
+            None => OpenApi30.validate(value, None),
+        }
+    }
+}
+
+/// `AsyncAPI`, only checking the main file's mandatory fields.
+#[derive(Debug)]
+pub struct AsyncApi;
+
+impl SpecLoader for AsyncApi {
+    fn kind(&self) -> &str {
+        "asyncapi"
+    }
+
+    fn detect(&self, document: &Json) -> bool {
+        version(document, "asyncapi").is_some()
+    }
+
+    fn validate(&self, value: &Json, main: Option<&Json>) -> PigResult<Option<Json>> {
+        match main {
+            Some(_) => Ok(None),
+            None => require(self.kind(), value, &["asyncapi", "info"]).map(|_| None),
+        }
+    }
+}
+
+/// JSON Schema documents, which are objects or booleans.
+#[derive(Debug)]
+pub struct JsonSchema;
+
+impl SpecLoader for JsonSchema {
+    fn kind(&self) -> &str {
+        "jsonschema"
+    }
+
+    fn detect(&self, document: &Json) -> bool {
+        document.get("$schema").is_some()
+    }
+
+    fn validate(&self, value: &Json, _: Option<&Json>) -> PigResult<Option<Json>> {
+        if value.is_object() || value.is_boolean() {
+            Ok(None)
+        } else {
+            Err(PigError::InvalidSpec(
+                self.kind().into(),
+                "not an object or a boolean".into(),
+            ))
+        }
+    }
+}
+
+/// Any YAML, as is. Never detected, only used with `api_kind: raw-yaml`.
+#[derive(Debug)]
+pub struct RawYaml;
+
+impl SpecLoader for RawYaml {
+    fn kind(&self) -> &str {
+        "raw-yaml"
+    }
+
+    fn detect(&self, _: &Json) -> bool {
+        false
+    }
+
+    fn validate(&self, _: &Json, _: Option<&Json>) -> PigResult<Option<Json>> {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect(document: Json) -> Option<String> {
+        loaders()
+            .unwrap()
+            .into_iter()
+            .find(|loader| loader.detect(&document))
+            .map(|loader| loader.kind().into())
+    }
+
+    #[test]
+    fn detection() {
+        assert_eq!(
+            detect(json!({ "openapi": "3.0.3" })).as_deref(),
+            Some("openapi30")
+        );
+        assert_eq!(
+            detect(json!({ "openapi": "3.1.0" })).as_deref(),
+            Some("openapi31")
+        );
+        assert_eq!(
+            detect(json!({ "swagger": "2.0" })).as_deref(),
+            Some("swagger2")
+        );
+        assert_eq!(
+            detect(json!({ "asyncapi": "2.6.0" })).as_deref(),
+            Some("asyncapi")
+        );
+        assert_eq!(
+            detect(json!({ "$schema": "x" })).as_deref(),
+            Some("jsonschema")
+        );
+        assert_eq!(detect(json!({ "openapi": 3 })), None);
+        assert_eq!(detect(json!([])), None);
+    }
+
+    #[test]
+    fn openapi30_partials() {
+        let main = json!({ "openapi": "3.0.3" });
+        let partial = json!({ "components": { "schemas": { "A": { "type": "string" } } } });
+
+        assert!(matches!(
+            OpenApi30.validate(&partial, Some(&main)),
+            Ok(None)
+        ));
+        assert!(OpenApi30.validate(&partial, None).is_err());
+    }
+
+    #[test]
+    fn openapi31_types() {
+        let value = OpenApi31.normalize(
+            json!({ "components": { "schemas": {
+                "A": { "type": ["string", "null"] },
+                "B": { "type": ["string", "integer"] },
+                "C": { "items": { "type": ["integer", "null"] } },
+            } } }),
+            None,
+        );
+        let schemas = &value["components"]["schemas"];
+
+        assert_eq!(
+            schemas["A"],
+            json!({ "type": "string", "nullable": true, "types": ["string", "null"] })
+        );
+        assert_eq!(
+            schemas["B"],
+            json!({ "type": ["string", "integer"], "types": ["string", "integer"] })
+        );
+        assert_eq!(
+            schemas["C"]["items"],
+            json!({ "type": "integer", "nullable": true, "types": ["integer", "null"] })
+        );
+    }
+
+    #[test]
+    fn openapi31_validation() {
+        let document = json!({ "openapi": "3.1.0", "info": { "title": "T", "version": "1" } });
+
+        assert!(matches!(
+            OpenApi31.validate(&document, None),
+            Err(PigError::InvalidSpec(_, _))
+        ));
+        assert!(OpenApi31
+            .validate(&json!({ "webhooks": {} }), Some(&document))
+            .is_ok());
+    }
+
+    #[test]
+    fn swagger2_upgrade() {
+        let swagger = json!({
+            "swagger": "2.0",
+            "info": { "title": "T", "version": "1" },
+            "host": "example.com",
+            "basePath": "/v1",
+            "schemes": ["https"],
+            "paths": { "/pets": { "post": {
+                "parameters": [{
+                    "name": "pet",
+                    "in": "body",
+                    "required": true,
+                    "schema": { "$ref": "#/definitions/Pet" },
+                }],
+                "responses": { "200": {
+                    "description": "ok",
+                    "schema": { "$ref": "#/definitions/Pet" },
+                } },
+            } } },
+            "definitions": { "Pet": { "type": "object", "x-nullable": true } },
+        });
+        let openapi = Swagger2.normalize(swagger, None);
+        let post = &openapi["paths"]["/pets"]["post"];
+        let pet = json!({ "$ref": "#/components/schemas/Pet" });
+
+        assert_eq!(openapi["openapi"], "3.0.3");
+        assert_eq!(
+            openapi["servers"],
+            json!([{ "url": "https://example.com/v1" }])
+        );
+        assert_eq!(
+            openapi["components"]["schemas"]["Pet"],
+            json!({ "type": "object", "nullable": true })
+        );
+        assert_eq!(post["requestBody"]["required"], true);
+        assert_eq!(
+            post["requestBody"]["content"]["application/json"]["schema"],
+            pet
+        );
+        assert_eq!(
+            post["responses"]["200"]["content"]["application/json"]["schema"],
+            pet
+        );
+        assert!(matches!(Swagger2.validate(&openapi, None), Ok(Some(_))));
+    }
+
+    #[test]
+    fn swagger2_partials() {
+        let main = json!({ "swagger": "2.0" });
+        let partial = Swagger2.normalize(
+            json!({ "definitions": { "Pet": { "items": { "$ref": "#/definitions/Tag" } } } }),
+            Some(&main),
+        );
+
+        assert_eq!(
+            partial,
+            json!({ "components": { "schemas": {
+                "Pet": { "items": { "$ref": "#/components/schemas/Tag" } },
+            } } })
+        );
+    }
+}
@@ -0,0 +1,136 @@
+use crate::{
+    config::{Api, ConfigEntry},
+    pig::Pig,
+    PigResult,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeSet, HashSet},
+    fs::{create_dir_all, read, write},
+    path::{Path, PathBuf},
+};
+use walkdir::WalkDir;
+
+/// What `pig` generated for an entry, in `out/.pig/<entry>/manifest.json`.
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct Manifest {
+    /// Hash of the config, the `OpenAPI` dependencies and the templates.
+    pub hash: String,
+    pub dependencies: BTreeSet<PathBuf>,
+    /// Generated files, relative to `out`.
+    pub files: BTreeSet<PathBuf>,
+}
+
+impl Manifest {
+    const FILE: &'static str = "manifest.json";
+
+    pub fn new(
+        file: &Path,
+        config: &ConfigEntry,
+        dependencies: &HashSet<PathBuf>,
+        files: &[PathBuf],
+    ) -> PigResult<Self> {
+        let dependencies = dependencies.iter().cloned().collect();
+
+        Ok(Self {
+            hash: Self::hash(file, config, &dependencies)?,
+            dependencies,
+            files: files
+                .iter()
+                .filter_map(|file| file.strip_prefix(&config.output).ok())
+                .map(Path::to_path_buf)
+                .collect(),
+        })
+    }
+
+    pub fn read(config: &ConfigEntry) -> Option<Self> {
+        serde_json::from_slice(&read(Pig::dir(config).join(Self::FILE)).ok()?).ok()
+    }
+
+    pub fn write(&self, config: &ConfigEntry) -> PigResult<()> {
+        let dir = Pig::dir(config);
+
+        create_dir_all(&dir)?;
+        write(dir.join(Self::FILE), serde_json::to_string_pretty(self)?)?;
+
+        Ok(())
+    }
+
+    /// Whether nothing changed since this manifest was written.
+    pub fn is_fresh(&self, file: &Path, config: &ConfigEntry) -> bool {
+        self.files
+            .iter()
+            .all(|file| config.output.join(file).is_file())
+            && Self::hash(file, config, &self.dependencies).is_ok_and(|hash| hash == self.hash)
+    }
+
+    fn hash(
+        file: &Path,
+        config: &ConfigEntry,
+        dependencies: &BTreeSet<PathBuf>,
+    ) -> PigResult<String> {
+        let mut hasher = Hasher::new();
+
+        hasher.write(env!("CARGO_PKG_VERSION").as_bytes());
+        hasher.write(&serde_json::to_vec(config)?);
+        hasher.file(file)?;
+
+        for dependency in dependencies {
+            hasher.file(dependency)?;
+        }
+
+        // Partials may have been added
+        if let Api::File(dir) = &config.openapi {
+            if dir.is_dir() {
+                for entry in WalkDir::new(dir).follow_links(true).sort_by_file_name() {
+                    let entry = entry?;
+
+                    if entry.file_type().is_file() {
+                        hasher.file(entry.path())?;
+                    }
+                }
+            }
+        }
+
+        for entry in WalkDir::new(&config.input)
+            .follow_links(true)
+            .sort_by_file_name()
+        {
+            let entry = entry?;
+
+            if entry.file_type().is_file() {
+                hasher.file(entry.path())?;
+            }
+        }
+
+        Ok(hasher.finish())
+    }
+}
+
+/// FNV-1a, stable across runs and builds.
+#[derive(Debug)]
+pub struct Hasher(u64);
+
+impl Hasher {
+    pub fn new() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+
+    pub fn finish(&self) -> String {
+        format!("{:016x}", self.0)
+    }
+
+    pub fn write(&mut self, bytes: &[u8]) {
+        for byte in (bytes.len() as u64).to_le_bytes().iter().chain(bytes) {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn file(&mut self, file: &Path) -> PigResult<()> {
+        self.write(file.as_os_str().as_encoded_bytes());
+        self.write(&read(file)?);
+
+        Ok(())
+    }
+}
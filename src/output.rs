@@ -0,0 +1,171 @@
+use crate::PigResult;
+use colored::{ColoredString, Colorize};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::Display,
+    fs::File,
+    io::{IsTerminal, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, PoisonError,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const INFO: &str = "🐷";
+const WARN: &str = "🚧";
+const ERROR: &str = "🚨";
+
+static PLAIN: AtomicBool = AtomicBool::new(false);
+static STDERR: AtomicBool = AtomicBool::new(false);
+static LOG: Mutex<Option<Log>> = Mutex::new(None);
+
+#[derive(clap::ValueEnum, Serialize, Deserialize, Copy, Clone, Default, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum Color {
+    // When printing to a terminal, unless `NO_COLOR` is set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// Everything `pig` prints goes through here.
+#[derive(Debug)]
+pub enum Output {}
+
+impl Output {
+    pub fn init(color: Color, plain: bool) {
+        colored::control::set_override(match color {
+            Color::Auto => {
+                std::env::var_os("NO_COLOR").is_none_or(|no_color| no_color.is_empty())
+                    && std::io::stdout().is_terminal()
+            }
+            Color::Always => true,
+            Color::Never => false,
+        });
+        PLAIN.store(plain, Ordering::Relaxed);
+    }
+
+    /// Prints to stderr instead of stdout, e.g. when stdout is a protocol stream.
+    pub fn stderr() {
+        STDERR.store(true, Ordering::Relaxed);
+    }
+
+    /// Also writes everything, including debug messages, to `path`.
+    ///
+    /// The file is appended to, and rotated to `<path>.1` when over `max_size` bytes.
+    pub fn log(path: &Path, max_size: u64) -> PigResult<()> {
+        *LOG.lock().unwrap_or_else(PoisonError::into_inner) = Some(Log::open(path, max_size)?);
+
+        Ok(())
+    }
+
+    pub fn line(message: impl Display) {
+        Log::write("line", &message);
+        Self::println(message);
+    }
+
+    /// Data for other programs (e.g. JSON), always on stdout.
+    pub fn data(data: impl Display) {
+        Log::write("data", &data);
+        println!("{data}");
+    }
+
+    /// Only written to the log file.
+    pub fn debug(message: impl Display) {
+        Log::write("debug", &message);
+    }
+
+    pub fn info(message: impl Display) {
+        Self::print(INFO, "info", message.to_string().normal());
+    }
+
+    pub fn warn(message: impl Display) {
+        Self::print(WARN, "warning", message.to_string().yellow());
+    }
+
+    pub fn error(message: impl Display) {
+        Self::print(ERROR, "error", message.to_string().red());
+    }
+
+    /// Flushes stdout, stderr and the log file, e.g. before exiting.
+    pub fn flush() {
+        let _ = std::io::stdout().flush();
+        let _ = std::io::stderr().flush();
+
+        if let Some(log) = LOG.lock().unwrap_or_else(PoisonError::into_inner).as_mut() {
+            let _ = log.file.sync_data();
+        }
+    }
+
+    fn print(emoji: &str, level: &str, message: ColoredString) {
+        Log::write(level, &&*message);
+
+        if PLAIN.load(Ordering::Relaxed) {
+            Self::println(format!("{level}: {message}"));
+        } else {
+            Self::println(format!("{emoji} {message}"));
+        }
+    }
+
+    fn println(message: impl Display) {
+        if STDERR.load(Ordering::Relaxed) {
+            eprintln!("{message}");
+        } else {
+            println!("{message}");
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Log {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_size: u64,
+}
+
+impl Log {
+    fn open(path: &Path, max_size: u64) -> PigResult<Self> {
+        let file = File::options().create(true).append(true).open(path)?;
+
+        Ok(Self {
+            path: path.into(),
+            size: file.metadata()?.len(),
+            file,
+            max_size,
+        })
+    }
+
+    /// Writes to the log file, if any, ignoring errors.
+    fn write(level: &str, message: &dyn Display) {
+        if let Some(log) = LOG.lock().unwrap_or_else(PoisonError::into_inner).as_mut() {
+            let _ = log.append(level, message);
+        }
+    }
+
+    fn append(&mut self, level: &str, message: &dyn Display) -> PigResult<()> {
+        if self.size > self.max_size {
+            let mut rotated = self.path.clone().into_os_string();
+            rotated.push(".1");
+            std::fs::rename(&self.path, rotated)?;
+            *self = Self::open(&self.path, self.max_size)?;
+        }
+
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let line = format!(
+            "[{}.{:03}] {level}: {message}\n",
+            time.as_secs(),
+            time.subsec_millis()
+        );
+
+        self.file.write_all(line.as_bytes())?;
+        self.size += line.len() as u64;
+
+        Ok(())
+    }
+}
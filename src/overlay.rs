@@ -0,0 +1,111 @@
+use crate::{PigError, PigResult, PigWarning};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as Json;
+use serde_json_path::JsonPath;
+use std::path::{Path, PathBuf};
+
+/// An `OpenAPI` Overlay document, applied to the resolved document.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Overlay {
+    #[serde(skip)]
+    file: PathBuf,
+    actions: Vec<Action>,
+}
+
+/// Updates or removes the nodes selected by `target`, a JSONPath.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct Action {
+    target: String,
+    update: Option<Json>,
+    #[serde(default)]
+    remove: bool,
+}
+
+impl Overlay {
+    pub fn read(file: &Path) -> PigResult<Self> {
+        Ok(Self {
+            file: file.to_path_buf(),
+            ..serde_yaml::from_slice(&std::fs::read(file)?)?
+        })
+    }
+
+    /// Applies the actions in order, warning about targets selecting nothing.
+    pub fn apply(&self, openapi: &mut Json) -> PigResult<Vec<PigWarning>> {
+        let mut warnings = Vec::new();
+
+        for action in &self.actions {
+            let path = JsonPath::parse(&action.target).map_err(|err| {
+                PigError::InvalidOverlay(self.file.clone(), format!("{}: {err}", action.target))
+            })?;
+            let mut pointers = path
+                .query_located(openapi)
+                .locations()
+                .map(|location| location.to_json_pointer())
+                .collect::<Vec<_>>();
+
+            if pointers.is_empty() {
+                warnings.push(PigWarning::OverlayNoMatch(
+                    self.file.clone(),
+                    action.target.clone(),
+                ));
+                continue;
+            }
+
+            if action.remove {
+                // Last array items first, not to shift the others
+                pointers.sort_by_key(|pointer| {
+                    let (parent, key) = pointer.rsplit_once('/').unwrap_or_default();
+                    (
+                        parent.to_string(),
+                        key.parse::<usize>().ok(),
+                        key.to_string(),
+                    )
+                });
+
+                for pointer in pointers.iter().rev() {
+                    let (parent, key) = pointer.rsplit_once('/').unwrap_or_default();
+                    let key = key.replace("~1", "/").replace("~0", "~");
+
+                    match openapi.pointer_mut(parent) {
+                        Some(Json::Object(object)) => {
+                            object.remove(&key);
+                        }
+                        Some(Json::Array(values)) => {
+                            if let Ok(i) = key.parse::<usize>() {
+                                values.remove(i);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            } else if let Some(update) = &action.update {
+                for pointer in &pointers {
+                    if let Some(target) = openapi.pointer_mut(pointer) {
+                        Self::merge(target, update.clone());
+                    }
+                }
+            }
+        }
+
+        Ok(warnings)
+    }
+
+    /// Merges objects recursively, appends to arrays and replaces anything else.
+    fn merge(target: &mut Json, update: Json) {
+        match (target, update) {
+            (Json::Object(target), Json::Object(update)) => {
+                for (key, update) in update {
+                    match target.get_mut(&key) {
+                        Some(target) => Self::merge(target, update),
+                        None => {
+                            target.insert(key, update);
+                        }
+                    }
+                }
+            }
+            (Json::Array(target), Json::Array(update)) => target.extend(update),
+            (Json::Array(target), update) => target.push(update),
+            (target, update) => *target = update,
+        }
+    }
+}
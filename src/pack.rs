@@ -0,0 +1,72 @@
+use crate::{config::ConfigEntry, derive::Derive, PigError, PigResult};
+use serde::{Deserialize, Serialize};
+use std::{fs::read_to_string, io::ErrorKind};
+
+/// Compatible context version(s), e.g. `2` or `[2, 3]`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum Versions {
+    One(u64),
+    Many(Vec<u64>),
+}
+
+impl Versions {
+    fn contains(&self, version: u64) -> bool {
+        match self {
+            Self::One(one) => *one == version,
+            Self::Many(many) => many.contains(&version),
+        }
+    }
+}
+
+impl std::fmt::Display for Versions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::One(one) => write!(f, "v{one}"),
+            Self::Many(many) => write!(
+                f,
+                "{}",
+                many.iter()
+                    .map(|version| format!("v{version}"))
+                    .collect::<Vec<_>>()
+                    .join(" or "),
+            ),
+        }
+    }
+}
+
+/// A template pack's description, in `in/.pig.pack.yaml`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Pack {
+    #[serde(default)]
+    pub name: String,
+    pub context_version: Versions,
+}
+
+impl Pack {
+    const FILE: &'static str = ".pig.pack.yaml";
+
+    /// Fails if the entry's template pack is incompatible with this context version.
+    pub fn check(config: &ConfigEntry) -> PigResult<()> {
+        let file = config.input.join(Self::FILE);
+        let pack = match read_to_string(&file) {
+            Ok(pack) => serde_yaml::from_str::<Self>(&pack)?,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
+
+        if pack.context_version.contains(Derive::CONTEXT_VERSION) {
+            Ok(())
+        } else {
+            Err(PigError::ContextVersion(
+                if pack.name.is_empty() {
+                    file.display().to_string()
+                } else {
+                    pack.name
+                },
+                pack.context_version,
+                Derive::CONTEXT_VERSION,
+            ))
+        }
+    }
+}
@@ -0,0 +1,211 @@
+use crate::{PigError, PigResult};
+use serde::Deserialize;
+use serde_json::Value as Json;
+use std::path::Path;
+
+/// A JSON Patch (RFC 6902, an array of operations) or a JSON Merge Patch (RFC 7386, anything
+/// else), applied to the resolved document.
+#[derive(Clone, Debug)]
+pub enum Patch {
+    Json(Vec<Operation>),
+    Merge(Json),
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum Operation {
+    Add { path: String, value: Json },
+    Remove { path: String },
+    Replace { path: String, value: Json },
+    Move { from: String, path: String },
+    Copy { from: String, path: String },
+    Test { path: String, value: Json },
+}
+
+impl Patch {
+    /// Applies the patch in `file` to `openapi`.
+    pub fn apply(file: &Path, openapi: &mut Json) -> PigResult<()> {
+        let invalid = |message: String| PigError::InvalidPatch(file.to_path_buf(), message);
+        // Arrays are JSON Patches, even with invalid operations
+        let patch = match serde_yaml::from_slice::<Json>(&std::fs::read(file)?)? {
+            patch @ Json::Array(_) => {
+                Self::Json(serde_json::from_value(patch).map_err(|err| invalid(err.to_string()))?)
+            }
+            patch => Self::Merge(patch),
+        };
+
+        match patch {
+            Self::Json(operations) => {
+                for operation in operations {
+                    operation.apply(openapi).map_err(invalid)?;
+                }
+            }
+            Self::Merge(patch) => Self::merge(openapi, patch),
+        }
+
+        if !openapi.is_object() {
+            return Err(invalid("the document is not an object anymore".into()));
+        }
+
+        Ok(())
+    }
+
+    fn merge(target: &mut Json, patch: Json) {
+        let Json::Object(patch) = patch else {
+            *target = patch;
+            return;
+        };
+
+        if !target.is_object() {
+            *target = Json::Object(Default::default());
+        }
+
+        let Json::Object(target) = target else {
+            return;
+        };
+
+        for (key, patch) in patch {
+            if patch.is_null() {
+                target.remove(&key);
+            } else {
+                Self::merge(target.entry(key).or_insert(Json::Null), patch);
+            }
+        }
+    }
+}
+
+impl Operation {
+    fn apply(self, openapi: &mut Json) -> Result<(), String> {
+        match self {
+            Self::Add { path, value } => Self::add(openapi, &path, value),
+            Self::Remove { path } => Self::remove(openapi, &path).map(|_| ()),
+            Self::Replace { path, value } => {
+                Self::remove(openapi, &path)?;
+                Self::add(openapi, &path, value)
+            }
+            Self::Move { from, path } => {
+                let value = Self::remove(openapi, &from)?;
+                Self::add(openapi, &path, value)
+            }
+            Self::Copy { from, path } => {
+                let value = openapi
+                    .pointer(&from)
+                    .cloned()
+                    .ok_or_else(|| format!("{from} not found"))?;
+                Self::add(openapi, &path, value)
+            }
+            Self::Test { path, value } => match openapi.pointer(&path) {
+                Some(actual) if *actual == value => Ok(()),
+                _ => Err(format!("test failed at {path}")),
+            },
+        }
+    }
+
+    /// The parent of `path` and the (unescaped) last key.
+    fn split(path: &str) -> Result<(&str, String), String> {
+        let (parent, key) = path
+            .rsplit_once('/')
+            .ok_or_else(|| format!("invalid path {path:?}"))?;
+
+        Ok((parent, key.replace("~1", "/").replace("~0", "~")))
+    }
+
+    fn add(openapi: &mut Json, path: &str, value: Json) -> Result<(), String> {
+        if path.is_empty() {
+            *openapi = value;
+            return Ok(());
+        }
+
+        let (parent, key) = Self::split(path)?;
+
+        match openapi.pointer_mut(parent) {
+            Some(Json::Object(object)) => {
+                object.insert(key, value);
+            }
+            Some(Json::Array(values)) if key == "-" => values.push(value),
+            Some(Json::Array(values)) => match key.parse::<usize>() {
+                Ok(i) if i <= values.len() => values.insert(i, value),
+                _ => return Err(format!("invalid index at {path}")),
+            },
+            _ => return Err(format!("{parent} not found")),
+        }
+
+        Ok(())
+    }
+
+    fn remove(openapi: &mut Json, path: &str) -> Result<Json, String> {
+        let (parent, key) = Self::split(path)?;
+        let not_found = || format!("{path} not found");
+
+        match openapi.pointer_mut(parent) {
+            Some(Json::Object(object)) => object.remove(&key).ok_or_else(not_found),
+            Some(Json::Array(values)) => match key.parse::<usize>() {
+                Ok(i) if i < values.len() => Ok(values.remove(i)),
+                _ => Err(not_found()),
+            },
+            _ => Err(not_found()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::fs::{create_dir_all, remove_dir_all, write};
+
+    /// Applies `patch` to a small document.
+    fn apply(name: &str, patch: &str) -> (PigResult<()>, Json) {
+        let dir = std::env::temp_dir().join(format!("pig-patch-{name}-{}", std::process::id()));
+        create_dir_all(&dir).unwrap();
+        write(dir.join("patch.yaml"), patch).unwrap();
+
+        let mut openapi = json!({ "paths": {}, "info": { "title": "T" } });
+        let result = Patch::apply(&dir.join("patch.yaml"), &mut openapi);
+        remove_dir_all(dir).unwrap();
+
+        (result, openapi)
+    }
+
+    #[test]
+    fn json_patch() {
+        let (result, openapi) = apply(
+            "json",
+            "- { op: replace, path: /info/title, value: U }\n\
+             - { op: add, path: /info/x-a~1b, value: 1 }\n\
+             - { op: remove, path: /paths }\n",
+        );
+
+        assert!(result.is_ok());
+        assert_eq!(openapi, json!({ "info": { "title": "U", "x-a/b": 1 } }));
+    }
+
+    #[test]
+    fn merge_patch() {
+        let (result, openapi) = apply("merge", "info: { title: ~, version: '1' }\n");
+
+        assert!(result.is_ok());
+        assert_eq!(openapi, json!({ "paths": {}, "info": { "version": "1" } }));
+    }
+
+    #[test]
+    fn malformed_operations() {
+        let (result, openapi) = apply("malformed", "- { op: replase, path: /info, value: 1 }\n");
+
+        // Not taken for a merge patch replacing the document
+        assert!(matches!(result, Err(PigError::InvalidPatch(_, _))));
+        assert!(openapi.is_object());
+    }
+
+    #[test]
+    fn non_object_documents() {
+        for (name, patch) in [
+            ("json-root", "- { op: add, path: '', value: [] }\n"),
+            ("merge-root", "1\n"),
+        ] {
+            let (result, _) = apply(name, patch);
+
+            assert!(matches!(result, Err(PigError::InvalidPatch(_, _))));
+        }
+    }
+}
@@ -1,16 +1,39 @@
 use crate::{
-    config::{Config, ConfigEntry},
-    resolver::Resolver,
-    Args, PigResult,
+    check::Check,
+    checksum::Checksum,
+    config::{Api, Config, ConfigEntry, RefMetadata, Severity},
+    derive::Derive,
+    diff::{diff, Change},
+    examples::Examples,
+    filter::Filter,
+    flatten::Flatten,
+    functions::{Dispatch, Emitter, Ident, Registry, VersionCmp},
+    journal::{Deferred, Journal},
+    manifest::{Hasher, Manifest},
+    output::Output,
+    overlay::Overlay,
+    pack::Pack,
+    patch::Patch,
+    progress::Progress,
+    promote::Promote,
+    remote::Remote,
+    rename::Rename,
+    resolver::{Resolved, Resolver},
+    shutdown::Shutdown,
+    usage::Usage,
+    Args, PigError, PigResult, PigWarning,
 };
 use clap::Parser;
-use notify::{event::DataChange, RecommendedWatcher, RecursiveMode, Watcher as _};
+use globset::{Glob, GlobSetBuilder};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher as _};
+use serde_json::{json, Value as Json};
 use std::{
-    collections::HashSet,
-    fs::{create_dir_all, write, File},
-    path::{Path, PathBuf},
-    sync::mpsc::{Receiver, Sender},
-    time::{Duration, SystemTime, UNIX_EPOCH},
+    collections::{BTreeMap, BTreeSet, HashSet},
+    fs::{create_dir_all, read, write},
+    io::Write as _,
+    path::{Component, Path, PathBuf},
+    sync::mpsc::{Receiver, RecvTimeoutError, Sender},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 use tera::{Context, Tera};
 use walkdir::WalkDir;
@@ -20,162 +43,1208 @@ pub enum Pig {}
 
 impl Pig {
     const JINJA: &'static str = ".jinja";
-    const JSON_CONTEXT: &'static str = ".pig.context.json";
-    const YAML_CONTEXT: &'static str = ".pig.context.yaml";
+    const DIR: &'static str = ".pig";
+    const JSON_CONTEXT: &'static str = "context.json";
+    const YAML_CONTEXT: &'static str = "context.yaml";
     const TRASH: &'static str = ".pig.trash";
+    /// The project cache, next to `pig.yaml`.
+    const CACHE: &'static str = ".pig/cache";
+    const SPECS: &'static str = "specs";
+    const REMOTE: &'static str = "remote";
+    const HTTP_AUTH: &'static str = "PIG_HTTP_AUTH";
 
     pub fn oink(config: Config) -> PigResult<()> {
-        if config.watch {
+        if let Some(preview) = &config.preview {
+            Self::preview(&config, preview)
+        } else if config.once_then_watch {
+            Self::once_then_watch(config)
+        } else if config.watch {
             Self::watch(config)
+        } else if config.if_changed && Self::is_fresh(&config) {
+            Ok(())
         } else {
             Self::run(config)
         }
     }
 
+    /// Whether no entry changed since its last generation.
+    fn is_fresh(config: &Config) -> bool {
+        config.entries.iter().all(|entry| {
+            Manifest::read(entry).is_some_and(|manifest| manifest.is_fresh(&config.file, entry))
+        })
+    }
+
     fn run(config: Config) -> PigResult<()> {
-        let data = config
-            .entries
+        // Entries see the files of the entries of their `after`
+        for stage in config.stages() {
+            Self::generate(
+                &config,
+                stage
+                    .into_iter()
+                    .map(|i| config.entries[i].clone())
+                    .collect(),
+            )?;
+        }
+
+        if let Some(archive) = &config.archive {
+            Self::archive(archive, &config.entries)?;
+        }
+
+        Progress::Finished.emit();
+
+        Ok(())
+    }
+
+    /// Generates `entries` (of `config`) together, cleaning once.
+    fn generate(config: &Config, entries: Vec<ConfigEntry>) -> PigResult<()> {
+        let mut nonempty = Vec::new();
+        for entry in entries {
+            if !Self::is_empty(config, &entry)? {
+                nonempty.push(entry);
+            }
+        }
+        let entries = nonempty;
+
+        let data = entries
             .iter()
             .map(|entry| {
-                let (_, context) = Pig::context(entry)?;
-                let tera = Pig::tera(entry)?;
+                Progress::EntryStarted {
+                    entry: entry.name.clone(),
+                }
+                .emit();
+
+                let mut tera = Pig::tera(entry)?;
+                let (dependencies, context) = Pig::context(entry, &mut tera)?;
 
-                Ok((entry, tera, context))
+                Ok((entry, tera, dependencies, context))
+            })
+            .collect::<PigResult<Vec<_>>>()?;
+
+        let rendered = data
+            .iter()
+            .map(|(entry, tera, _, context)| {
+                let files = Self::render(entry, tera, context, &Ident::default(), |_| true)?;
+
+                // Only when rendering every template
+                if entry.warn_unused {
+                    Self::warn(entry, Usage::check(tera, context, &files))?;
+                }
+
+                let files = files
+                    .into_iter()
+                    .map(|(_, file, content)| (file, content))
+                    .collect::<Vec<_>>();
+
+                Ok((files, Self::copies(entry)?))
             })
             .collect::<PigResult<Vec<_>>>()?;
 
         Self::clean(
-            &config,
-            data.iter().map(|(config, tera, _)| (*config, tera)),
+            config,
+            &entries,
+            &Self::outputs(rendered.iter().flat_map(|(files, copies)| {
+                files
+                    .iter()
+                    .map(|(file, _)| file)
+                    .chain(copies.iter().map(|(_, file)| file))
+            }))?,
         )?;
 
-        for (config, tera, context) in data {
-            Self::render(config, &tera, &context)?;
+        for ((entry, _, dependencies, _), (files, copies)) in data.iter().zip(rendered) {
+            Self::write(&files, config.jobs)?;
+            Self::copy(&copies)?;
+            let files = files
+                .into_iter()
+                .map(|(file, _)| file)
+                .chain(copies.into_iter().map(|(_, file)| file))
+                .collect::<Vec<_>>();
+            Manifest::new(&config.file, entry, dependencies, &files)?.write(entry)?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether `entry` has no templates nor copied files (warned about), e.g. on first run with
+    /// a wrong `in`, not to clean its output for nothing, unless `allow_empty`.
+    fn is_empty(config: &Config, entry: &ConfigEntry) -> PigResult<bool> {
+        if config.allow_empty || !Self::inputs(entry)?.is_empty() {
+            return Ok(false);
+        }
+
+        Self::warn(entry, vec![PigWarning::NoTemplates(entry.input.clone())])?;
+        Ok(true)
+    }
+
+    /// Prints templates matching `glob`, without cleaning nor writing anything.
+    fn preview(config: &Config, glob: &str) -> PigResult<()> {
+        let glob = Glob::new(glob)?.compile_matcher();
+
+        for entry in &config.entries {
+            let mut tera = Pig::tera(entry)?;
+            let (_, openapi) = Pig::openapi(entry, &mut tera)?;
+            let context = Context::from_value(openapi)?;
+            let is_late = |template: &str| entry.late.iter().any(|late| late == template);
+            // Late templates need the facts registered by the others
+            let late = tera
+                .get_template_names()
+                .any(|template| glob.is_match(template) && is_late(template));
+            let filter = |template: &str| glob.is_match(template) || (late && !is_late(template));
+
+            for (_, path, content) in
+                Self::contents(entry, &tera, &context, &Ident::default(), filter)?
+                    .into_iter()
+                    .filter(|(template, _, _)| glob.is_match(template))
+            {
+                Output::line(format!("==> {} <==", path.display()));
+                Output::line(content);
+            }
         }
 
         Ok(())
     }
 
     fn watch(config: Config) -> PigResult<()> {
+        Shutdown::handle();
         Watcher::new(config)?.watch()
     }
 
-    fn context(config: &ConfigEntry) -> PigResult<(HashSet<PathBuf>, Context)> {
-        let (dependencies, openapi) = Resolver::new(&config.openapi)?.resolve()?;
+    /// Generates (honoring `--if-changed`) and signals readiness, then keeps watching.
+    fn once_then_watch(mut config: Config) -> PigResult<()> {
+        if !(config.if_changed && Self::is_fresh(&config)) {
+            Self::run(config.clone())?;
+        }
+
+        Self::ready(&config)?;
+
+        // Readiness was already signaled
+        config.ready_message = None;
+        config.ready_fd = None;
+
+        Self::watch(config)
+    }
+
+    /// Signals that the first generation is done, with `--ready-message` and `--ready-fd`.
+    fn ready(config: &Config) -> PigResult<()> {
+        if let Some(message) = &config.ready_message {
+            Output::line(message);
+        }
+
+        if let Some(fd) = config.ready_fd {
+            #[cfg(unix)]
+            {
+                use std::os::fd::FromRawFd;
+
+                let fd = libc::c_int::try_from(fd)
+                    .map_err(|_| std::io::Error::from_raw_os_error(libc::EBADF))?;
+
+                // SAFETY: only queries the descriptor
+                if unsafe { libc::fcntl(fd, libc::F_GETFD) } == -1 {
+                    return Err(std::io::Error::last_os_error().into());
+                }
+
+                // SAFETY: the descriptor is open, and left so for whoever passed it
+                let mut file =
+                    std::mem::ManuallyDrop::new(unsafe { std::fs::File::from_raw_fd(fd) });
+                file.write_all(b"ready\n")?;
+            }
+
+            #[cfg(not(unix))]
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                format!("--ready-fd {fd} needs file descriptors"),
+            )
+            .into());
+        }
+
+        Ok(())
+    }
+
+    fn context(config: &ConfigEntry, tera: &mut Tera) -> PigResult<(HashSet<PathBuf>, Context)> {
+        let (dependencies, openapi) = Self::openapi(config, tera)?;
 
+        let dir = Self::dir(config);
+        create_dir_all(&dir)?;
         write(
-            config.output.as_path().join(Self::JSON_CONTEXT),
+            dir.join(Self::JSON_CONTEXT),
             serde_json::to_string_pretty(&openapi)?,
         )?;
         write(
-            config.output.as_path().join(Self::YAML_CONTEXT),
+            dir.join(Self::YAML_CONTEXT),
             serde_yaml::to_string(&openapi)?,
         )?;
 
         Ok((dependencies, Context::from_value(openapi)?))
     }
 
-    fn tera(config: &ConfigEntry) -> PigResult<Tera> {
-        Ok(Tera::new(
-            &config
-                .input
-                .as_path()
-                .join(format!("**/*{}", Self::JINJA))
-                .display()
-                .to_string(),
-        )?)
+    /// Resolves the `OpenAPI` document of an entry with `resolver`, then applies its overlays,
+    /// patches and renames.
+    pub(crate) fn document(config: &ConfigEntry, resolver: Resolver) -> PigResult<Resolved> {
+        if let Some(expected) = &config.api_sha256 {
+            match &config.openapi {
+                Api::File(file) if file.is_file() => {
+                    Self::verify(file, &std::fs::read(file)?, expected)?
+                }
+                Api::File(file) => return Err(PigError::NotAFile(file.clone())),
+                // Keys are sorted, so that the same document always hashes the same
+                Api::Inline(openapi) => {
+                    Self::verify(&config.file, &serde_json::to_vec(openapi)?, expected)?
+                }
+            }
+        }
+
+        let mut resolved = resolver.resolve()?;
+
+        if !config.dependencies_sha256.is_empty() {
+            for (name, file) in &resolved.files {
+                let is_main = matches!(&config.openapi, Api::File(main) if main == file);
+
+                if is_main || *file == config.file {
+                    continue;
+                }
+
+                match config.dependencies_sha256.get(name) {
+                    Some(expected) => Self::verify(file, &std::fs::read(file)?, expected)?,
+                    None => return Err(PigError::Unpinned(name.clone())),
+                }
+            }
+        }
+
+        for file in &config.overlays {
+            let warnings = Overlay::read(file)?.apply(&mut resolved.openapi)?;
+            resolved.warnings.extend(warnings);
+            resolved.dependencies.insert(file.clone());
+        }
+
+        for file in &config.patches {
+            Patch::apply(file, &mut resolved.openapi)?;
+            resolved.dependencies.insert(file.clone());
+        }
+
+        if let Some(case) = config.operation_ids {
+            Derive::operation_ids(&mut resolved.openapi, case);
+        }
+
+        Rename::apply(
+            &mut resolved,
+            &config.renames,
+            config.ref_prefix.as_deref().unwrap_or("$"),
+        )?;
+        Filter::drop(&mut resolved.openapi, &config.drop_operations);
+        Filter::select(&mut resolved.openapi, &config.include, &config.exclude)?;
+
+        Ok(resolved)
     }
 
-    fn output(config: &ConfigEntry, template: &str) -> PathBuf {
-        let len = template.len() - Pig::JINJA.len();
+    /// Fails unless the SHA-256 of `content` (of `file`) is `expected` (hex).
+    fn verify(file: &Path, content: &[u8], expected: &str) -> PigResult<()> {
+        let actual = Checksum::sha256(content);
 
-        config.output.join(&template[..len])
+        if actual.eq_ignore_ascii_case(expected.trim()) {
+            Ok(())
+        } else {
+            Err(PigError::ChecksumMismatch(
+                file.to_path_buf(),
+                expected.into(),
+                actual,
+            ))
+        }
     }
 
-    fn clean<'a, T: IntoIterator<Item = (&'a ConfigEntry, &'a Tera)>>(
-        config: &Config,
-        it: T,
-    ) -> PigResult<()> {
-        let outputs = {
-            let mut outputs = HashSet::new();
+    /// Resolves, enriches and checks the `OpenAPI` document of an entry.
+    pub(crate) fn openapi(
+        config: &ConfigEntry,
+        tera: &mut Tera,
+    ) -> PigResult<(HashSet<PathBuf>, Json)> {
+        let resolved = Self::document(config, Self::resolver(config, tera)?)?;
+        let components = resolved.components();
+        let Resolved {
+            mut dependencies,
+            mut openapi,
+            mut warnings,
+            shared,
+            raw,
+            root,
+            files,
+            ..
+        } = resolved;
+
+        if let Some(file) = files.get(&root) {
+            let mut examples = Examples::new(
+                &files,
+                config.ref_prefix.as_deref(),
+                config.max_example_size.unwrap_or(Examples::MAX),
+            );
+            examples.inline(&mut openapi, file);
+            dependencies.extend(examples.dependencies);
+            warnings.extend(examples.warnings);
+        }
 
-            for (config, tera) in it {
-                for template in tera.get_template_names() {
-                    let output = Self::output(config, template);
+        // Derived before `pig` is completed, so that shared targets are derived too
+        openapi["pig"] = json!({ "refs": shared, "view": config.view.to_string() });
+        Derive::view(&mut openapi, config.view);
+        Derive::lifecycle(&mut openapi, &config.lifecycle);
+        Derive::server_urls(&mut openapi, config.ref_prefix.as_deref().unwrap_or("$"));
+        openapi["pig"]["schema_order"] =
+            Derive::schema_deps(&mut openapi, config.ref_prefix.as_deref().unwrap_or("$"));
 
-                    if !outputs.contains(&output) {
-                        outputs.insert(output);
-                    } else {
-                        panic!("Conflicting output file: {}", output.display());
-                    }
+        let flatten = Flatten::new(config.ref_prefix.as_deref());
+
+        // Flattened before derivation, so that merged properties are derived too
+        if config.flatten_all_of {
+            flatten.apply(&mut openapi);
+        }
+
+        Derive::derive(&mut openapi, config.ref_prefix.as_deref().unwrap_or("$"));
+        // After derivation, so that copied properties keep their derived data
+        flatten.lineage(&mut openapi);
+
+        // After derivation, so that targets carry their derived data
+        for (link, target) in Derive::links(
+            &mut openapi,
+            &root,
+            config.ref_prefix.as_deref().unwrap_or("$"),
+        ) {
+            warnings.push(PigWarning::UnresolvedLink(link, target));
+        }
+
+        if config.promote_schemas {
+            Promote::new(config.ref_prefix.as_deref()).apply(&mut openapi);
+        }
+
+        // Dependencies are known by the `$name`s of references
+        if config.ref_metadata == RefMetadata::None
+            && openapi
+                .pointer("/components/schemas")
+                .and_then(Json::as_object)
+                .is_some_and(|schemas| !schemas.is_empty())
+        {
+            warnings.push(PigWarning::NoSchemaDeps);
+        }
+
+        for (host, _) in config.tls.iter().filter(|(_, tls)| tls.insecure) {
+            warnings.push(PigWarning::InsecureTls(host.clone()));
+        }
+
+        if let Some(max) = config
+            .max_dependencies
+            .filter(|max| dependencies.len() > *max)
+        {
+            warnings.push(PigWarning::TooManyDependencies(dependencies.len(), max));
+        }
+
+        let mut errors = 0;
+        if let Some(lint) = &config.lint {
+            for diagnostic in Check::lint(&openapi, lint, &components) {
+                match diagnostic.severity {
+                    Severity::Error => errors += 1,
+                    Severity::Warn => {}
+                    Severity::Info | Severity::Off => continue,
                 }
+
+                warnings.push(diagnostic.into());
             }
+        }
+
+        Self::warn(
+            config,
+            warnings.into_iter().chain(Check::check(&openapi)).collect(),
+        )?;
+
+        if errors > 0 {
+            return Err(PigError::LintErrors(config.name.clone(), errors));
+        }
+
+        let operations = Derive::operation_list(&mut openapi);
+        openapi["pig"]["tags"] = Derive::tag_groups(&openapi, &operations);
+        openapi["pig"]["error_model"] =
+            Derive::error_model(&operations, config.ref_prefix.as_deref().unwrap_or("$"));
+        openapi["pig"]["operations"] = operations;
+        openapi["pig"]["variants"] = json!(Dispatch::variants(&openapi));
+        // Not derived
+        if config.raw {
+            openapi["pig"]["raw"] = json!({ "document": raw.get(&root), "files": raw });
+        }
+
+        openapi["pig"]["context_version"] = Derive::CONTEXT_VERSION.into();
+        // Overrides are seen by vars, and override them
+        Self::set(config, &mut openapi);
+        openapi["pig"]["vars"] = Self::vars(config, tera, &openapi)?;
+        Self::set(config, &mut openapi);
+
+        Ok((dependencies, openapi))
+    }
 
-            outputs
+    /// The configured resolver of an entry.
+    pub(crate) fn resolver(config: &ConfigEntry, tera: &mut Tera) -> PigResult<Resolver> {
+        let resolver = match &config.openapi {
+            Api::File(dir) if dir.is_dir() => Resolver::directory(&config.file, dir)?,
+            Api::File(file) => Resolver::new(file)?,
+            Api::Inline(openapi) => Resolver::inline(&config.file, openapi.clone())?,
         };
 
-        let mut trash = {
-            let trash = config.file.parent().unwrap().join(Self::TRASH).join(
-                SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap()
-                    .as_millis()
-                    .to_string(),
-            );
-            let mut created = false;
+        let root = config
+            .file
+            .parent()
+            .ok_or_else(|| PigError::InvalidPath(config.file.clone()))?;
+
+        Ok(resolver
+            .roots(&config.ref_roots)
+            .metadata(config.ref_metadata, Some(root))
+            .cache(&root.join(Self::CACHE).join(Self::SPECS))
+            .validate(config.validate)
+            .kind(config.api_kind.as_deref())
+            .prefix(config.ref_prefix.as_deref())
+            .depth(config.ref_depth)
+            .raw(config.raw)
+            .remote(
+                Remote::new(
+                    config.cache.join(Self::REMOTE),
+                    Self::headers(config, tera)?,
+                )
+                .ttl(
+                    config
+                        .remote_ttl
+                        .filter(|_| !config.refresh)
+                        .map(Duration::from_secs),
+                )
+                .offline(config.offline)
+                .proxy(config.proxy.as_deref())
+                .tls(&config.tls),
+            ))
+    }
+
+    /// Prints warnings, failing if the entry denies them.
+    fn warn(config: &ConfigEntry, warnings: Vec<PigWarning>) -> PigResult<()> {
+        for warning in &warnings {
+            Output::warn(warning);
+            Progress::Warning {
+                entry: config.name.clone(),
+                message: warning.to_string(),
+            }
+            .emit();
+        }
+
+        if config.deny_warnings && !warnings.is_empty() {
+            Err(PigError::Warnings(config.name.clone(), warnings.len()))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Applies `--set` overrides, creating (or replacing with) objects along their paths.
+    fn set(config: &ConfigEntry, openapi: &mut Json) {
+        for (path, value) in &config.set {
+            let mut target = &mut *openapi;
+
+            for key in path.split('.') {
+                if !target.is_object() {
+                    *target = json!({});
+                }
+
+                target = &mut target[key];
+            }
+
+            *target = value.clone();
+        }
+    }
+
+    /// Renders the entry's `vars` (their strings, at any depth) against the resolved context and
+    /// environment variables.
+    fn vars(config: &ConfigEntry, tera: &mut Tera, openapi: &Json) -> PigResult<Json> {
+        fn render(tera: &mut Tera, context: &Context, var: &Json) -> PigResult<Json> {
+            Ok(match var {
+                Json::String(var) => tera.render_str(var, context)?.into(),
+                Json::Array(vars) => vars
+                    .iter()
+                    .map(|var| render(tera, context, var))
+                    .collect::<PigResult<_>>()?,
+                Json::Object(vars) => vars
+                    .iter()
+                    .map(|(name, var)| Ok((name.clone(), render(tera, context, var)?)))
+                    .collect::<PigResult<_>>()?,
+                var => var.clone(),
+            })
+        }
+
+        let mut context = Context::from_value(openapi.clone())?;
+        context.insert("env", &std::env::vars().collect::<BTreeMap<_, _>>());
+
+        render(tera, &context, &serde_json::to_value(&config.vars)?)
+    }
+
+    /// Renders the entry's `headers` (by host) against environment variables, adding an
+    /// `Authorization` header from `PIG_HTTP_AUTH` (a bearer token) to each host unless given.
+    fn headers(
+        config: &ConfigEntry,
+        tera: &mut Tera,
+    ) -> PigResult<BTreeMap<String, BTreeMap<String, String>>> {
+        let mut context = Context::new();
+        context.insert("env", &std::env::vars().collect::<BTreeMap<_, _>>());
+        let token = std::env::var(Self::HTTP_AUTH).ok();
+
+        config
+            .headers
+            .iter()
+            .map(|(host, headers)| {
+                let mut headers = headers
+                    .iter()
+                    .map(|(name, header)| Ok((name.clone(), tera.render_str(header, &context)?)))
+                    .collect::<PigResult<BTreeMap<_, _>>>()?;
+
+                if let Some(token) = &token {
+                    if !headers
+                        .keys()
+                        .any(|name| name.eq_ignore_ascii_case("authorization"))
+                    {
+                        headers.insert("Authorization".into(), format!("Bearer {token}"));
+                    }
+                }
+
+                Ok((host.clone(), headers))
+            })
+            .collect()
+    }
+
+    /// Copies the contexts to `archive/<timestamp>-<hash>/<entry>.json`.
+    fn archive<'a>(
+        archive: &Path,
+        entries: impl IntoIterator<Item = &'a ConfigEntry>,
+    ) -> PigResult<()> {
+        let mut hasher = Hasher::new();
+        let contexts = entries
+            .into_iter()
+            .map(|entry| {
+                let context = read(Self::dir(entry).join(Self::JSON_CONTEXT))?;
+                hasher.write(&context);
+
+                Ok((&entry.name, context))
+            })
+            .collect::<PigResult<Vec<_>>>()?;
+        let run = archive.join(format!(
+            "{}-{}",
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis(),
+            hasher.finish(),
+        ));
+
+        create_dir_all(&run)?;
+
+        for (name, context) in contexts {
+            write(run.join(format!("{name}.json")), context)?;
+        }
+
+        Ok(())
+    }
+
+    /// The directory of `pig`'s own files for this entry, e.g. `out/.pig/entry`.
+    pub fn dir(config: &ConfigEntry) -> PathBuf {
+        config.output.join(Self::DIR).join(&config.name)
+    }
+
+    pub(crate) fn tera(config: &ConfigEntry) -> PigResult<Tera> {
+        Pack::check(config)?;
+
+        let mut tera = Tera::default();
+        tera.register_filter(VersionCmp::NAME, VersionCmp);
+        tera.add_template_files(
+            Self::inputs(config)?
+                .into_iter()
+                .filter(|(_, _, binary)| !binary)
+                .map(|(file, name, _)| (file, Some(name))),
+        )?;
+
+        Ok(tera)
+    }
+
+    /// The files of the `in` directory to render or copy, with their name (relative to `in`) and
+    /// whether they are copied as is: `.jinja` files are templates, unless binary or matching
+    /// `copy`.
+    fn inputs(config: &ConfigEntry) -> PigResult<Vec<(PathBuf, String, bool)>> {
+        let mut copy = GlobSetBuilder::new();
+        for glob in &config.copy {
+            copy.add(Glob::new(glob)?);
+        }
+        let copy = copy.build()?;
+        let mut inputs = Vec::new();
+
+        for entry in WalkDir::new(&config.input)
+            .follow_links(true)
+            .sort_by_file_name()
+        {
+            let entry = entry?;
+            let file = entry.path();
+
+            if !entry.file_type().is_file() {
+                continue;
+            }
+
+            let name = file
+                .strip_prefix(&config.input)
+                .unwrap_or(file)
+                .to_string_lossy()
+                .replace('\\', "/");
+
+            if copy.is_match(&name) {
+                inputs.push((file.to_path_buf(), name, true));
+            } else if name.ends_with(Self::JINJA) {
+                let binary = Self::is_binary(&read(file)?);
+                inputs.push((file.to_path_buf(), name, binary));
+            }
+        }
+
+        Ok(inputs)
+    }
+
+    /// Whether `content` is binary, by magic bytes (images, fonts, archives, PDFs) or by not being
+    /// UTF-8 text.
+    fn is_binary(content: &[u8]) -> bool {
+        const MAGIC: [&[u8]; 9] = [
+            b"\x89PNG",
+            b"\xFF\xD8\xFF",
+            b"GIF8",
+            b"%PDF",
+            b"PK\x03\x04",
+            b"wOFF",
+            b"wOF2",
+            b"\0\0\x01\0",
+            b"RIFF",
+        ];
 
-            move |config: &ConfigEntry, path: &Path| {
-                if !created {
-                    create_dir_all(&trash)?;
-                    created = true;
+        MAGIC.iter().any(|magic| content.starts_with(magic))
+            || content.contains(&0)
+            || std::str::from_utf8(content).is_err()
+    }
+
+    /// Files copied as is, with their output path (without `.jinja`, if any).
+    fn copies(config: &ConfigEntry) -> PigResult<Vec<(PathBuf, PathBuf)>> {
+        Self::inputs(config)?
+            .into_iter()
+            .filter(|(_, _, binary)| *binary)
+            .map(|(file, name, _)| {
+                let path = name.strip_suffix(Self::JINJA).unwrap_or(&name);
+                Ok((file, Self::path(config, &name, Path::new(path))?))
+            })
+            .collect()
+    }
+
+    /// Copies files byte-exact, only when their content changed.
+    fn copy(copies: &[(PathBuf, PathBuf)]) -> PigResult<()> {
+        for (file, output) in copies {
+            let content = read(file)?;
+
+            if read(output).ok().as_ref() != Some(&content) {
+                if let Some(dir) = output.parent() {
+                    create_dir_all(dir)?;
                 }
+                write(output, content)?;
+            }
+        }
+
+        Ok(())
+    }
 
-                std::fs::rename(path, trash.join(path.strip_prefix(&config.output).unwrap()))?;
+    fn output(config: &ConfigEntry, template: &str) -> PigResult<PathBuf> {
+        let len = template.len() - Pig::JINJA.len();
 
-                PigResult::Ok(())
+        Self::path(config, template, Path::new(&template[..len]))
+    }
+
+    /// `path` in the `out` directory, from `template`.
+    fn path(config: &ConfigEntry, template: &str, path: &Path) -> PigResult<PathBuf> {
+        let mut output = PathBuf::new();
+
+        // Normalize without touching the file system, rejecting anything escaping `out`
+        for component in path.components() {
+            match component {
+                Component::Normal(component) => output.push(component),
+                Component::CurDir => {}
+                Component::ParentDir if output.pop() => {}
+                Component::ParentDir | Component::RootDir | Component::Prefix(_) => {
+                    return Err(PigError::OutputOutside(
+                        template.into(),
+                        config.output.join(path),
+                    ))
+                }
+            }
+        }
+
+        if output.as_os_str().is_empty() {
+            return Err(PigError::OutputOutside(
+                template.into(),
+                config.output.join(path),
+            ));
+        }
+
+        Ok(config.output.join(output))
+    }
+
+    /// The files to generate, failing on conflicts.
+    fn outputs<'a>(files: impl IntoIterator<Item = &'a PathBuf>) -> PigResult<HashSet<PathBuf>> {
+        let mut outputs = HashSet::new();
+
+        for file in files {
+            if !outputs.insert(file.clone()) {
+                return Err(PigError::ConflictingOutput(file.clone()));
             }
+        }
+
+        Ok(outputs)
+    }
+
+    /// Trashes the files of the outputs of `entries` that are not in `outputs`, sparing the
+    /// manifested files of the other entries of `config` (e.g. of previous stages).
+    fn clean(
+        config: &Config,
+        entries: &[ConfigEntry],
+        outputs: &HashSet<PathBuf>,
+    ) -> PigResult<()> {
+        let root = config
+            .file
+            .parent()
+            .ok_or_else(|| PigError::InvalidPath(config.file.clone()))?;
+        Journal::check(root)?;
+
+        let snapshot = root.join(Self::TRASH).join(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis()
+                .to_string(),
+        );
+        let target = |output: &Path, path: &Path| {
+            path.strip_prefix(output)
+                .map(|path| snapshot.join(path))
+                .map_err(|_| PigError::InvalidPath(path.into()))
         };
+        let manifested = |entry: &ConfigEntry| {
+            Manifest::read(entry)
+                .unwrap_or_default()
+                .files
+                .into_iter()
+                .map(|file| entry.output.join(file))
+                .collect::<Vec<_>>()
+        };
+
+        // Entries sharing an output are walked once, together
+        let mut dirs = BTreeMap::<&Path, bool>::new();
+        for entry in entries {
+            *dirs.entry(&entry.output).or_default() |= entry.shared_out;
+        }
+        let generated_files = entries.iter().flat_map(manifested).collect::<HashSet<_>>();
+        let kept = config
+            .entries
+            .iter()
+            .filter(|entry| !entries.iter().any(|other| other.name == entry.name))
+            .flat_map(manifested)
+            .collect::<HashSet<_>>();
+        let internal = config
+            .entries
+            .iter()
+            .map(|entry| entry.output.join(Self::DIR))
+            .collect::<Vec<_>>();
 
-        for config in &config.entries {
-            let json_context = config.output.join(Self::JSON_CONTEXT);
-            let yaml_context = config.output.join(Self::YAML_CONTEXT);
+        let extensions = outputs
+            .iter()
+            .filter_map(|output| output.extension())
+            .collect::<HashSet<_>>();
+        let mut orphans = Vec::new();
+        let mut seen = HashSet::new();
+        let (mut generated, mut unknown, mut foreign) = (0, 0, Vec::new());
 
-            for result in WalkDir::new(&config.output).follow_links(true) {
+        for (&output, &shared_out) in &dirs {
+            for result in WalkDir::new(output).follow_links(true) {
                 let entry = result?;
+                let path = entry.path();
 
                 if !entry.file_type().is_file()
-                    || entry.path().starts_with(&json_context)
-                    || entry.path().starts_with(&yaml_context)
+                    || internal.iter().any(|dir| path.starts_with(dir))
+                    || outputs.contains(path)
+                    || kept.contains(path)
+                    // Nested outputs
+                    || !seen.insert(path.to_path_buf())
                 {
                     continue;
                 }
 
-                if !outputs.contains(entry.path()) {
-                    trash(config, entry.path())?;
+                // Previously generated, maybe generated, or clearly not generated by `pig`
+                if generated_files.contains(path) {
+                    generated += 1;
+                } else if shared_out {
+                    // Files of other tools and humans
+                    continue;
+                } else if path
+                    .extension()
+                    .is_some_and(|extension| extensions.contains(extension))
+                {
+                    unknown += 1;
+                } else {
+                    foreign.push(path.to_path_buf());
                 }
+
+                orphans.push((output, path.to_path_buf()));
             }
         }
 
-        Ok(())
+        if !foreign.is_empty() && !config.force {
+            return Err(PigError::ForeignFiles(foreign));
+        }
+
+        if !orphans.is_empty() {
+            Output::info(format!(
+                "Trashing {generated} generated, {unknown} unknown and {} foreign files to {}",
+                foreign.len(),
+                snapshot.display(),
+            ));
+        }
+
+        if orphans.is_empty() {
+            return Ok(());
+        }
+
+        // Journaled first, and finished even on Ctrl-C
+        let journal = Journal::write(
+            root,
+            &snapshot,
+            orphans
+                .iter()
+                .map(|(output, path)| Ok((path.clone(), target(output, path)?)))
+                .collect::<PigResult<_>>()?,
+        )?;
+        let _deferred = Deferred::interrupts();
+
+        let mut parents = BTreeSet::new();
+        // Trashed files beneath each directory, and the names of those directly in it
+        let mut tree = BTreeMap::<PathBuf, (usize, usize, Vec<String>)>::new();
+
+        for (output, path) in &orphans {
+            let target = target(output, path)?;
+            let parent = path
+                .parent()
+                .ok_or_else(|| PigError::InvalidPath(path.clone()))?;
+            if let Some(dir) = target.parent() {
+                create_dir_all(dir)?;
+            }
+            std::fs::rename(path, target)?;
+            Progress::Cleaned { path: path.clone() }.emit();
+            parents.insert((output.to_path_buf(), parent.to_path_buf()));
+
+            for dir in path.ancestors().skip(1) {
+                let depth = dir
+                    .strip_prefix(output)
+                    .map_or(0, |dir| dir.components().count());
+                let (count, _, names) =
+                    tree.entry(dir.to_path_buf())
+                        .or_insert((0, depth, Vec::new()));
+                *count += 1;
+
+                if dir == parent {
+                    let name = path.file_name().unwrap_or(path.as_os_str());
+                    names.push(name.to_string_lossy().into());
+                }
+
+                if dir == *output {
+                    break;
+                }
+            }
+        }
+
+        for (dir, (count, depth, mut names)) in tree {
+            let indent = "  ".repeat(depth + 1);
+            let name = match depth {
+                0 => dir.strip_prefix(root).unwrap_or(&dir).display().to_string(),
+                _ => dir
+                    .file_name()
+                    .unwrap_or(dir.as_os_str())
+                    .to_string_lossy()
+                    .into(),
+            };
+
+            Output::line(format!("{indent}{name}/ ({count})"));
+
+            // Every file at `-vv`
+            if config.verbose >= 2 {
+                names.sort();
+
+                for name in names {
+                    Output::line(format!("{indent}  {name}"));
+                }
+            }
+        }
+
+        // Directories emptied by trashing, deepest first, but not output directories
+        let mut removed = 0;
+
+        for (output, parent) in parents.into_iter().rev() {
+            for dir in parent.ancestors() {
+                if dir == output || !dir.starts_with(&output) || std::fs::remove_dir(dir).is_err() {
+                    break;
+                }
+
+                removed += 1;
+            }
+        }
+
+        if removed > 0 {
+            Output::info(format!("Removed {removed} empty directories"));
+        }
+
+        journal.remove()
     }
 
-    fn render(config: &ConfigEntry, tera: &Tera, context: &Context) -> PigResult<()> {
-        for template in tera.get_template_names() {
-            let output = Self::output(config, template);
+    /// Renders templates accepted by `filter`, then `late` templates with the registered facts in
+    /// `pig.registry`, calling `ident`.
+    fn render(
+        config: &ConfigEntry,
+        tera: &Tera,
+        context: &Context,
+        ident: &Ident,
+        filter: impl Fn(&str) -> bool,
+    ) -> PigResult<Vec<(String, PathBuf, String)>> {
+        Self::banner(config, tera, context);
 
-            create_dir_all(output.parent().unwrap())?;
-            tera.render_to(template, context, File::create(output)?)?;
+        let mut files = Self::contents(config, tera, context, ident, filter)?;
+
+        if config.checksums {
+            for (_, file, content) in &mut files {
+                *content = Checksum::sign(file, std::mem::take(content));
+            }
         }
 
-        Ok(())
+        Self::warn(config, Self::budget(config, &files))?;
+
+        Ok(files)
+    }
+
+    /// The context subtrees `template` depends on, as JSON pointers, if declared in its front
+    /// matter: a leading comment with a `deps = ["components.schemas", "info"]` line.
+    fn deps(config: &ConfigEntry, template: &str) -> PigResult<Option<Vec<String>>> {
+        let content = std::fs::read_to_string(config.input.join(template))?;
+        let Some(front) = content
+            .trim_start()
+            .strip_prefix("{#")
+            .and_then(|content| content.split_once("#}"))
+            .map(|(front, _)| front.trim_start_matches('-').trim_end_matches('-'))
+        else {
+            return Ok(None);
+        };
+
+        for line in front.lines() {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+
+            if key.trim() == "deps" {
+                let deps = serde_json::from_str::<Vec<String>>(value.trim())?;
+
+                return Ok(Some(
+                    deps.iter()
+                        .map(|dep| {
+                            dep.split('.')
+                                .map(|key| {
+                                    format!("/{}", key.replace('~', "~0").replace('/', "~1"))
+                                })
+                                .collect()
+                        })
+                        .collect(),
+                ));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Renders templates accepted by `filter`, late templates last.
+    fn contents(
+        config: &ConfigEntry,
+        tera: &Tera,
+        context: &Context,
+        ident: &Ident,
+        filter: impl Fn(&str) -> bool,
+    ) -> PigResult<Vec<(String, PathBuf, String)>> {
+        let registry = Registry::default();
+        let emitter = Emitter::default();
+        let mut tera = tera.clone();
+        tera.register_function(Registry::NAME, registry.clone());
+        tera.register_function(Emitter::NAME, emitter.clone());
+        tera.register_function(Ident::NAME, ident.clone());
+        let dispatch = Dispatch::new(tera.clone(), context);
+        tera.register_function(Dispatch::NAME, dispatch.clone());
+
+        let variants = context
+            .get("pig")
+            .and_then(|pig| pig.get("variants"))
+            .and_then(Json::as_array)
+            .cloned()
+            .unwrap_or_default();
+        let mut partials = GlobSetBuilder::new();
+        for glob in &config.partials {
+            partials.add(Glob::new(glob)?);
+        }
+        let partials = partials.build()?;
+        // Partials and variants only render through others
+        let is_partial = |template: &str| {
+            partials.is_match(template) || variants.iter().any(|variant| variant == template)
+        };
+
+        let (mut late, mut early) = tera
+            .get_template_names()
+            .filter(|template| filter(template) && !is_partial(template))
+            .partition::<Vec<_>, _>(|template| config.late.iter().any(|late| late == template));
+        // In a stable order, for `ident` registers
+        late.sort_unstable();
+        early.sort_unstable();
+        let render = |templates: Vec<&str>, context: &Context| {
+            templates
+                .into_iter()
+                .map(|template| {
+                    let start = Instant::now();
+                    let output = Self::output(config, template)?;
+                    registry.template(template)?;
+                    let content = tera.render(template, context)?;
+
+                    Progress::TemplateRendered {
+                        entry: config.name.clone(),
+                        template: template.into(),
+                        path: output.clone(),
+                        duration: start.elapsed(),
+                    }
+                    .emit();
+
+                    let mut files = vec![(template.to_string(), output, content)];
+
+                    for (path, content) in emitter.files()? {
+                        let path = Self::path(config, template, Path::new(&path))?;
+                        files.push((template.to_string(), path, content));
+                    }
+
+                    Ok(files)
+                })
+                .collect::<PigResult<Vec<_>>>()
+                .map(|files| files.concat())
+        };
+
+        let mut files = render(early, context)?;
+
+        if !late.is_empty() {
+            let mut context = context.clone();
+            let mut pig = context.get("pig").cloned().unwrap_or_else(|| json!({}));
+            pig["registry"] = registry.facts()?;
+            context.insert("pig", &pig);
+            dispatch.context(&context)?;
+
+            files.extend(render(late, &context)?);
+        }
+
+        Ok(files)
+    }
+
+    /// Checks generated files against the entry's size budgets.
+    fn budget(config: &ConfigEntry, files: &[(String, PathBuf, String)]) -> Vec<PigWarning> {
+        let mut warnings = Vec::new();
+        let mut total = 0;
+
+        for (template, file, content) in files {
+            let size = content.len() as u64;
+            total += size;
+
+            if let Some(max) = config.max_file_size.filter(|max| size > *max) {
+                warnings.push(PigWarning::FileTooLarge(
+                    file.clone(),
+                    template.clone(),
+                    size,
+                    max,
+                ));
+            }
+        }
+
+        if let Some(max) = config.max_total_size.filter(|max| total > *max) {
+            warnings.push(PigWarning::TotalTooLarge(total, max));
+        }
+
+        warnings
+    }
+
+    /// Prints what is about to render.
+    fn banner(config: &ConfigEntry, tera: &Tera, context: &Context) {
+        let openapi = match &config.openapi {
+            Api::File(file) => file.display().to_string(),
+            Api::Inline(_) => "inline".into(),
+        };
+        let version = context
+            .get("info")
+            .and_then(|info| info.get("version"))
+            .and_then(Json::as_str)
+            .unwrap_or_default()
+            .to_string();
+        let templates = tera.get_template_names().count();
+
+        Output::info(format!(
+            "{} ({}): {openapi} v{version}, {templates} templates -> {}",
+            config.name,
+            config.file.display(),
+            config.output.display(),
+        ));
+        Progress::Banner {
+            entry: config.name.clone(),
+            config: config.file.clone(),
+            openapi,
+            version,
+            templates,
+            output: config.output.clone(),
+        }
+        .emit();
+    }
+
+    /// Writes files on `jobs` threads, reporting every failure at once.
+    fn write(files: &[(PathBuf, String)], jobs: usize) -> PigResult<()> {
+        let chunk = files.len().div_ceil(jobs).max(1);
+
+        let errors = std::thread::scope(|scope| {
+            files
+                .chunks(chunk)
+                .map(|files| {
+                    scope.spawn(move || {
+                        files
+                            .iter()
+                            .filter_map(|(path, content)| {
+                                path.parent()
+                                    .map_or(Ok(()), create_dir_all)
+                                    .and_then(|()| write(path, content))
+                                    .err()
+                                    .map(|err| (path.clone(), err))
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect::<Vec<_>>()
+                .into_iter()
+                .map(|handle| {
+                    handle
+                        .join()
+                        .map_err(|_| PigError::Panic("a writing thread panicked".into()))
+                })
+                .collect::<PigResult<Vec<_>>>()
+        })?
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(PigError::Write(errors))
+        }
     }
 }
 
 #[derive(Debug)]
 enum Event {
-    Config(DataChange),
-    Openapi(usize, DataChange),
-    Input(usize, DataChange),
+    Config,
+    Openapi(usize),
+    Input(usize),
+    Error(notify::Error),
 }
 
 pub struct Watcher {
@@ -186,10 +1255,13 @@ pub struct Watcher {
 }
 
 impl Watcher {
+    /// How often a shutdown request is checked for, while waiting for changes.
+    const SHUTDOWN_POLL: Duration = Duration::from_millis(200);
+
     pub fn new(config: Config) -> PigResult<Self> {
         let (sender, receiver) = std::sync::mpsc::channel();
         let config_watcher = RecommendedWatcher::new(
-            Watcher::handler(sender.clone(), Event::Config),
+            Watcher::handler(sender.clone(), || Event::Config),
             Watcher::config(),
         )?;
         let entries = config
@@ -209,7 +1281,7 @@ impl Watcher {
 
     fn handler(
         sender: Sender<Event>,
-        f: impl Fn(DataChange) -> Event,
+        f: impl Fn() -> Event,
     ) -> impl Fn(Result<notify::Event, notify::Error>) {
         move |event: Result<notify::Event, notify::Error>| match event {
             Ok(event) => match event.kind {
@@ -218,7 +1290,9 @@ impl Watcher {
                 notify::EventKind::Create(_) => {}
                 notify::EventKind::Modify(modify) => match modify {
                     notify::event::ModifyKind::Any => {}
-                    notify::event::ModifyKind::Data(data) => sender.send(f(data)).unwrap(),
+                    notify::event::ModifyKind::Data(_) => {
+                        let _ = sender.send(f());
+                    }
                     notify::event::ModifyKind::Metadata(_) => {}
                     notify::event::ModifyKind::Name(_) => {}
                     notify::event::ModifyKind::Other => {}
@@ -226,22 +1300,142 @@ impl Watcher {
                 notify::EventKind::Remove(_) => {}
                 notify::EventKind::Other => {}
             },
-            Err(error) => panic!("Error: {error:?}"),
+            Err(error) => {
+                let _ = sender.send(Event::Error(error));
+            }
         }
     }
 
+    /// Stops watching, after a shutdown request.
+    fn stop() -> PigResult<()> {
+        Output::info("Stopped watching");
+        Output::flush();
+
+        Ok(())
+    }
+
     fn config() -> notify::Config {
         notify::Config::default().with_poll_interval(Duration::from_millis(200))
     }
 
-    fn clean(&self) -> PigResult<()> {
+    /// Renders the templates of some entries affected by their changes, then cleans and writes.
+    fn render(&mut self, entries: impl IntoIterator<Item = usize>) -> PigResult<()> {
+        let mut non_empty = Vec::new();
+        for i in entries {
+            if !Pig::is_empty(&self.config, &self.entries[i].config)? {
+                non_empty.push(i);
+            }
+        }
+
+        let rendered = non_empty
+            .into_iter()
+            .map(|i| {
+                let entry = &self.entries[i];
+                let ident = Ident::default();
+                let files = Pig::render(
+                    &entry.config,
+                    &entry.tera,
+                    &entry.context,
+                    &ident,
+                    |template| entry.affected(template),
+                )?;
+                // Templates not rendered again keep the registers they may have
+                let registers =
+                    ident.has_registers() || (entry.changes.is_some() && entry.registers);
+
+                // Files of templates not rendered again are kept
+                let mut files = files
+                    .into_iter()
+                    .map(|(template, file, content)| (template, file, Some(content)))
+                    .collect::<Vec<_>>();
+                files.extend(
+                    entry
+                        .files
+                        .iter()
+                        .filter(|(template, _)| !entry.affected(template))
+                        .map(|(template, file)| (template.clone(), file.clone(), None)),
+                );
+
+                // Copied files are copied again (when changed)
+                let copies = Pig::copies(&entry.config)?;
+                files.extend(
+                    copies
+                        .iter()
+                        .map(|(source, file)| (source.display().to_string(), file.clone(), None)),
+                );
+
+                Ok((i, files, copies, registers))
+            })
+            .collect::<PigResult<Vec<_>>>()?;
+        let outputs = Pig::outputs(
+            self.entries
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| !rendered.iter().any(|(j, _, _, _)| i == j))
+                .flat_map(|(_, entry)| &entry.files)
+                .map(|(_, file)| file)
+                .chain(
+                    rendered
+                        .iter()
+                        .flat_map(|(_, files, _, _)| files)
+                        .map(|(_, file, _)| file),
+                ),
+        )?;
+
+        // Other entries may not have generated yet
         Pig::clean(
             &self.config,
-            self.entries
+            &rendered
                 .iter()
-                .map(|entry| (&entry.config, &entry.tera)),
+                .map(|(i, _, _, _)| self.entries[*i].config.clone())
+                .collect::<Vec<_>>(),
+            &outputs,
         )?;
 
+        for (i, files, copies, registers) in rendered {
+            let entry = &mut self.entries[i];
+
+            Pig::write(
+                &files
+                    .iter()
+                    .filter_map(|(_, file, content)| Some((file.clone(), content.clone()?)))
+                    .collect::<Vec<_>>(),
+                self.config.jobs,
+            )?;
+            Pig::copy(&copies)?;
+            entry.files = files
+                .into_iter()
+                .map(|(template, file, _)| (template, file))
+                .collect();
+            entry.changes = None;
+            entry.registers = registers;
+            Manifest::new(
+                &self.config.file,
+                &entry.config,
+                &entry.dependencies,
+                &entry
+                    .files
+                    .iter()
+                    .map(|(_, file)| file.clone())
+                    .collect::<Vec<_>>(),
+            )?
+            .write(&entry.config)?;
+        }
+
+        Ok(())
+    }
+
+    /// Generates the entries after entry `i` again, in order.
+    fn downstream(&mut self, i: usize) -> PigResult<()> {
+        for j in self.config.downstream(i) {
+            Progress::EntryStarted {
+                entry: self.entries[j].config.name.clone(),
+            }
+            .emit();
+            self.entries[j].on_upstream()?;
+            self.render([j])?;
+        }
+
         Ok(())
     }
 
@@ -249,30 +1443,72 @@ impl Watcher {
         self.config_watcher
             .watch(self.config.file.as_path(), RecursiveMode::Recursive)?;
 
-        for entry in &mut self.entries {
-            entry.watch()?;
-        }
+        for stage in self.config.stages() {
+            if Shutdown::is_requested() {
+                return Self::stop();
+            }
 
-        self.clean()?;
+            for &i in &stage {
+                Progress::EntryStarted {
+                    entry: self.entries[i].config.name.clone(),
+                }
+                .emit();
+                self.entries[i].watch()?;
+            }
 
-        for entry in &mut self.entries {
-            entry.render()?;
+            self.render(stage)?;
         }
 
-        for event in &self.receiver {
+        Progress::Finished.emit();
+        Pig::ready(&self.config)?;
+
+        loop {
+            // Between generations only
+            if Shutdown::is_requested() {
+                return Self::stop();
+            }
+
+            let event = match self.receiver.recv_timeout(Self::SHUTDOWN_POLL) {
+                Ok(event) => event,
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
+
+            if let Event::Openapi(i) | Event::Input(i) = event {
+                Progress::EntryStarted {
+                    entry: self.entries[i].config.name.clone(),
+                }
+                .emit();
+            }
+
             match event {
-                Event::Config(_) => return Self::new(Config::new(Args::parse())?)?.watch(),
-                Event::Openapi(i, _) => {
+                Event::Config => {
+                    // Readiness was already signaled
+                    let mut config = Config::new(Args::parse())?;
+                    config.ready_message = None;
+                    config.ready_fd = None;
+
+                    return Self::new(config)?.watch();
+                }
+                Event::Openapi(i) => {
                     self.entries[i].on_openapi()?;
-                    self.clean()?;
-                    self.entries[i].render()?;
+                    self.render([i])?;
+
+                    if let Some(archive) = &self.config.archive {
+                        Pig::archive(archive, [&self.entries[i].config])?;
+                    }
+
+                    self.downstream(i)?;
                 }
-                Event::Input(i, _) => {
+                Event::Input(i) => {
                     self.entries[i].on_input()?;
-                    self.clean()?;
-                    self.entries[i].render()?;
+                    self.render([i])?;
+                    self.downstream(i)?;
                 }
+                Event::Error(error) => return Err(error.into()),
             }
+
+            Progress::Finished.emit();
         }
 
         Ok(())
@@ -286,6 +1522,12 @@ pub struct WatcherEntry {
     dependencies: HashSet<PathBuf>,
     context: Context,
     tera: Tera,
+    /// The last generated files, with their template.
+    files: Vec<(String, PathBuf)>,
+    /// The context changes since the last render (JSON pointers), if only the context changed.
+    changes: Option<Vec<String>>,
+    /// Whether the last render called `ident` with a `used` register (unknown before).
+    registers: bool,
 }
 
 impl WatcherEntry {
@@ -293,22 +1535,51 @@ impl WatcherEntry {
         Ok(Self {
             config,
             openapi_watcher: RecommendedWatcher::new(
-                Watcher::handler(sender.clone(), move |event| Event::Openapi(index, event)),
+                Watcher::handler(sender.clone(), move || Event::Openapi(index)),
                 Watcher::config(),
             )?,
             input_watcher: RecommendedWatcher::new(
-                Watcher::handler(sender.clone(), move |event| Event::Input(index, event)),
+                Watcher::handler(sender.clone(), move || Event::Input(index)),
                 Watcher::config(),
             )?,
             dependencies: Default::default(),
             context: Default::default(),
             tera: Default::default(),
+            files: Default::default(),
+            changes: None,
+            registers: true,
+        })
+    }
+
+    /// Whether `template` has to render again: when it does not declare its dependencies, or
+    /// when one of them changed.
+    fn affected(&self, template: &str) -> bool {
+        let Some(changes) = &self.changes else {
+            return true;
+        };
+        let Ok(Some(deps)) = Pig::deps(&self.config, template) else {
+            return true;
+        };
+
+        changes.iter().any(|change| {
+            deps.iter().any(|dep| {
+                change == dep
+                    || change.starts_with(&format!("{dep}/"))
+                    || dep.starts_with(&format!("{change}/"))
+            })
         })
     }
 
     fn watch(&mut self) -> PigResult<()> {
-        (self.dependencies, self.context) = Pig::context(&self.config)?;
         self.tera = Pig::tera(&self.config)?;
+        (self.dependencies, self.context) = Pig::context(&self.config, &mut self.tera)?;
+
+        // New partials change the document too
+        if let Api::File(dir) = &self.config.openapi {
+            if dir.is_dir() {
+                self.openapi_watcher.watch(dir, RecursiveMode::Recursive)?;
+            }
+        }
 
         for dependency in &self.dependencies {
             self.openapi_watcher
@@ -326,7 +1597,23 @@ impl WatcherEntry {
             self.openapi_watcher.unwatch(dependency)?;
         }
 
-        (self.dependencies, self.context) = Pig::context(&self.config)?;
+        let old = self.context.clone().into_json();
+        (self.dependencies, self.context) = Pig::context(&self.config, &mut self.tera)?;
+
+        // Late templates need every template's facts, and `ident` registers every call before,
+        // so they render everything again
+        if self.config.late.is_empty() && !self.registers {
+            let changes = diff(&old, &self.context.clone().into_json());
+            self.changes = Some(
+                changes
+                    .iter()
+                    .map(Change::pointer)
+                    .map(String::from)
+                    .collect(),
+            );
+        } else {
+            self.changes = None;
+        }
 
         for dependency in &self.dependencies {
             self.openapi_watcher
@@ -342,9 +1629,175 @@ impl WatcherEntry {
         Ok(())
     }
 
-    fn render(&self) -> PigResult<()> {
-        Pig::render(&self.config, &self.tera, &self.context)?;
+    /// What changed in the entries of `after` is unknown: everything renders again.
+    fn on_upstream(&mut self) -> PigResult<()> {
+        self.on_input()?;
+        self.on_openapi()?;
+        self.changes = None;
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::GlobalConfig;
+    use std::fs::{read_dir, remove_dir_all, remove_file};
+
+    /// A fresh project of `config` with templates for `a.txt` (in `a`) and `b.txt` (in `b`).
+    fn project(name: &str, config: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("pig-clean-{name}-{}", std::process::id()));
+        let _ = remove_dir_all(&root);
+
+        for (file, content) in [
+            (
+                "api.yaml",
+                "openapi: 3.0.3\ninfo: { title: T, version: '1' }\npaths: {}\n",
+            ),
+            ("a/a.txt.jinja", "a"),
+            ("b/b.txt.jinja", "b"),
+            ("pig.yaml", config),
+        ] {
+            create_dir_all(root.join(file).parent().unwrap()).unwrap();
+            write(root.join(file), content).unwrap();
+        }
+
+        root
+    }
+
+    /// The config of `root`, without the user's global config.
+    fn config(root: &Path) -> Config {
+        let file = root.join("pig.yaml");
+        let args = Args::parse_from(["pig".as_ref(), file.as_os_str()]);
+
+        Config::with_global(args, GlobalConfig::default()).unwrap()
+    }
+
+    fn run(root: &Path) {
+        Pig::run(config(root)).unwrap();
+    }
+
+    fn files(dir: &Path) -> Vec<String> {
+        let mut files = read_dir(dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter(|entry| entry.path().is_file())
+            .map(|entry| entry.file_name().to_string_lossy().into())
+            .collect::<Vec<_>>();
+        files.sort();
+        files
+    }
+
+    /// Trashed files, of every snapshot.
+    fn trashed(root: &Path) -> Vec<String> {
+        read_dir(root.join(Pig::TRASH))
+            .into_iter()
+            .flatten()
+            .flatten()
+            .flat_map(|snapshot| files(&snapshot.path()))
+            .collect()
+    }
+
+    #[test]
+    fn shared_outputs() {
+        let root = project(
+            "shared",
+            "- { name: a, api: api.yaml, in: a, out: out }\n\
+             - { name: b, api: api.yaml, in: b, out: out }\n",
+        );
+        write(root.join("a/old.txt.jinja"), "old").unwrap();
+        run(&root);
+        assert_eq!(files(&root.join("out")), ["a.txt", "b.txt", "old.txt"]);
+
+        // Walked once, though in both entries' output
+        remove_file(root.join("a/old.txt.jinja")).unwrap();
+        run(&root);
+        assert_eq!(files(&root.join("out")), ["a.txt", "b.txt"]);
+        assert_eq!(trashed(&root), ["old.txt"]);
+        assert!(Journal::check(&root).is_ok());
+
+        remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn stages() {
+        let root = project(
+            "stages",
+            "- { name: a, api: api.yaml, in: a, out: out }\n\
+             - { name: b, api: api.yaml, in: b, out: out, after: [a] }\n",
+        );
+        run(&root);
+        run(&root);
+
+        // `b` spares the files `a` generated just before
+        assert_eq!(files(&root.join("out")), ["a.txt", "b.txt"]);
+        assert!(trashed(&root).is_empty());
+
+        remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn foreign_files() {
+        let root = project("foreign", "- { name: a, api: api.yaml, in: a, out: out }\n");
+        run(&root);
+        write(root.join("out/notes.md"), "mine").unwrap();
+
+        assert!(matches!(
+            Pig::run(config(&root)),
+            Err(PigError::ForeignFiles(_))
+        ));
+        assert_eq!(files(&root.join("out")), ["a.txt", "notes.md"]);
+
+        remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn vars() {
+        let root = project(
+            "vars",
+            "- name: a\n  api: api.yaml\n  in: a\n  out: out\n  \
+             vars: { package: '{{ info.title | lower }}_sdk', versions: ['{{ info.version }}', 2] }\n",
+        );
+        write(
+            root.join("a/a.txt.jinja"),
+            "{{ pig.vars.package }} {{ pig.vars.versions | json_encode() }}",
+        )
+        .unwrap();
+        run(&root);
+
+        assert_eq!(
+            std::fs::read_to_string(root.join("out/a.txt")).unwrap(),
+            r#"t_sdk ["1",2]"#
+        );
+
+        remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn registry() {
+        let root = project(
+            "registry",
+            "- { name: a, api: api.yaml, in: a, out: out, late: [index.txt.jinja] }\n",
+        );
+        write(
+            root.join("a/a.txt.jinja"),
+            "{{ register(kind='route', name='a') }}{{ register(kind='route', name='b', template='b') }}",
+        )
+        .unwrap();
+        write(
+            root.join("a/index.txt.jinja"),
+            "{% for route in pig.registry.route %}{{ route.name }}@{{ route.template }} {% endfor %}",
+        )
+        .unwrap();
+        run(&root);
+
+        assert_eq!(
+            std::fs::read_to_string(root.join("out/index.txt")).unwrap(),
+            "a@a.txt.jinja b@b "
+        );
+
+        remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,59 @@
+use crate::output::Output;
+use std::{
+    path::PathBuf,
+    sync::{mpsc::Sender, Mutex, PoisonError},
+    time::Duration,
+};
+
+static SUBSCRIBERS: Mutex<Vec<Sender<Progress>>> = Mutex::new(Vec::new());
+
+/// Progress of a generation, for embedders (IDE extensions, GUIs, ...).
+#[derive(Clone, Debug)]
+pub enum Progress {
+    EntryStarted {
+        entry: String,
+    },
+    /// An entry is about to render.
+    Banner {
+        entry: String,
+        config: PathBuf,
+        /// The `OpenAPI` file, or `inline`.
+        openapi: String,
+        /// The `info.version` of the `OpenAPI` document.
+        version: String,
+        templates: usize,
+        output: PathBuf,
+    },
+    TemplateRendered {
+        entry: String,
+        template: String,
+        path: PathBuf,
+        duration: Duration,
+    },
+    Cleaned {
+        path: PathBuf,
+    },
+    Warning {
+        entry: String,
+        message: String,
+    },
+    Finished,
+}
+
+impl Progress {
+    /// Sends all future progress events to `sender`.
+    pub fn subscribe(sender: Sender<Progress>) {
+        SUBSCRIBERS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(sender);
+    }
+
+    pub(crate) fn emit(self) {
+        Output::debug(format!("{self:?}"));
+        SUBSCRIBERS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .retain(|sender| sender.send(self.clone()).is_ok());
+    }
+}
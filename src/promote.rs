@@ -0,0 +1,172 @@
+use crate::{config::Case, derive::Derive};
+use serde_json::{Map, Value as Json};
+use std::collections::BTreeMap;
+
+/// Names anonymous inline object schemas (of request bodies, responses and nested in other
+/// schemas) and lifts them into `pig.components.schemas`, so that templates have a name for
+/// every type.
+#[derive(Debug)]
+pub struct Promote {
+    /// The prefix of metadata keys.
+    prefix: String,
+    /// Promoted schemas, by name.
+    schemas: BTreeMap<String, Json>,
+}
+
+impl Promote {
+    pub fn new(prefix: Option<&str>) -> Self {
+        Self {
+            prefix: prefix.unwrap_or("$").into(),
+            schemas: BTreeMap::new(),
+        }
+    }
+
+    fn key(&self, name: &str) -> String {
+        format!("{}{name}", self.prefix)
+    }
+
+    /// Names inline schemas in place (with `$name`, `$ref` to their promoted copy and
+    /// `$synthetic: true`), then adds the copies to `pig.components.schemas`.
+    pub fn apply(mut self, openapi: &mut Json) {
+        let mut operations = Vec::new();
+        Derive::operations(openapi, |path, method, _, operation| {
+            let name = match operation.get("operationId").and_then(Json::as_str) {
+                Some(id) => Case::Pascal.join(&Derive::words(id)),
+                None => Case::Pascal.join(&Derive::operation_words(path, method)),
+            };
+            operations.push((path.to_string(), method.to_string(), name));
+        });
+
+        for (path, method, name) in operations {
+            let operation = &mut openapi["paths"][&path][&method];
+            let success = operation["responses"]
+                .as_object()
+                .and_then(Derive::success_response)
+                .map(String::from);
+
+            for (_, media) in Self::contents(&mut operation["requestBody"]) {
+                self.promote(&mut media["schema"], format!("{name}RequestBody"));
+            }
+
+            for (status, response) in operation["responses"].as_object_mut().into_iter().flatten() {
+                let status = match status.as_str() {
+                    _ if Some(status) == success.as_ref() => String::new(),
+                    "default" => "Default".into(),
+                    status => status.into(),
+                };
+
+                for (_, media) in Self::contents(response) {
+                    self.promote(&mut media["schema"], format!("{name}{status}ResponseBody"));
+                }
+            }
+        }
+
+        for (name, schema) in openapi["components"]["schemas"]
+            .as_object_mut()
+            .into_iter()
+            .flatten()
+        {
+            self.children(schema, name);
+        }
+
+        for (name, schema) in std::mem::take(&mut self.schemas) {
+            openapi["pig"]["components"]["schemas"][name] = schema;
+        }
+    }
+
+    /// The media types of a request body or response.
+    fn contents(value: &mut Json) -> impl Iterator<Item = (&String, &mut Json)> {
+        value
+            .get_mut("content")
+            .and_then(Json::as_object_mut)
+            .into_iter()
+            .flatten()
+    }
+
+    /// Names `schema` (if an anonymous object) after `name`, then its children.
+    fn promote(&mut self, schema: &mut Json, name: String) {
+        let Some(object) = schema.as_object() else {
+            return;
+        };
+
+        // Referenced schemas have names already
+        if let Some(name) = object.get(&self.key("name")).and_then(Json::as_str) {
+            let name = name.to_string();
+            return self.children(schema, &name);
+        }
+
+        if object.contains_key(&self.key("ref")) || !Self::is_object(object) {
+            return self.children(schema, &name);
+        }
+
+        self.children(schema, &name);
+
+        // Identical schemas share their name
+        let name = (1..)
+            .map(|i| match i {
+                1 => name.clone(),
+                i => format!("{name}{i}"),
+            })
+            .find(|name| self.schemas.get(name).is_none_or(|other| other == schema))
+            .unwrap();
+
+        self.schemas.insert(name.clone(), schema.clone());
+
+        let Some(object) = schema.as_object_mut() else {
+            return;
+        };
+        object.insert(self.key("name"), name.clone().into());
+        object.insert(
+            self.key("ref"),
+            format!("#/pig/components/schemas/{name}").into(),
+        );
+        object.insert(self.key("synthetic"), true.into());
+    }
+
+    /// Promotes the properties, items, additional properties and alternatives of `schema`.
+    fn children(&mut self, schema: &mut Json, name: &str) {
+        let Some(object) = schema.as_object_mut() else {
+            return;
+        };
+
+        // Circular references point to schemas named elsewhere
+        if object.contains_key(&self.key("circular")) {
+            return;
+        }
+
+        for (property, schema) in object
+            .get_mut("properties")
+            .and_then(Json::as_object_mut)
+            .into_iter()
+            .flatten()
+        {
+            let property = Case::Pascal.join(&Derive::words(property));
+            self.promote(schema, format!("{name}{property}"));
+        }
+
+        if let Some(items) = object.get_mut("items") {
+            self.promote(items, format!("{name}Item"));
+        }
+
+        if let Some(additional) = object.get_mut("additionalProperties") {
+            self.promote(additional, format!("{name}Value"));
+        }
+
+        for key in ["oneOf", "anyOf"] {
+            for (i, schema) in object
+                .get_mut(key)
+                .and_then(Json::as_array_mut)
+                .into_iter()
+                .flatten()
+                .enumerate()
+            {
+                self.promote(schema, format!("{name}{}", i + 1));
+            }
+        }
+    }
+
+    fn is_object(schema: &Map<String, Json>) -> bool {
+        schema.get("type").and_then(Json::as_str) == Some("object")
+            || schema.contains_key("properties")
+    }
+}
@@ -0,0 +1,314 @@
+use crate::{config::Tls, manifest::Hasher, PigError, PigResult};
+use rustls::{
+    client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    crypto::{verify_tls12_signature, verify_tls13_signature, CryptoProvider},
+    pki_types::{pem::PemObject, CertificateDer, ServerName, UnixTime},
+    ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fs::{create_dir_all, read, write, File},
+    io::Read,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use ureq::{Agent, AgentBuilder, Proxy};
+
+/// Downloads remote `OpenAPI` files, for `$ref`s to URLs.
+#[derive(Clone, Debug)]
+pub struct Remote {
+    dir: PathBuf,
+    /// Headers, by host.
+    headers: BTreeMap<String, BTreeMap<String, String>>,
+    /// How long downloaded files are reused, across runs.
+    ttl: Option<Duration>,
+    /// Forbids downloads, only using previously downloaded files.
+    offline: bool,
+    /// Proxy URL, else from the environment.
+    proxy: Option<String>,
+    /// TLS settings, by host.
+    tls: BTreeMap<String, Tls>,
+    /// Agents, by host.
+    agents: HashMap<String, Agent>,
+    /// URLs missing from previous downloads, when offline.
+    missing: BTreeSet<String>,
+    /// Downloaded files and their URLs.
+    urls: HashMap<PathBuf, String>,
+}
+
+impl Default for Remote {
+    fn default() -> Self {
+        Self::new(std::env::temp_dir().join("pig"), BTreeMap::new())
+    }
+}
+
+impl Remote {
+    pub fn new<T: AsRef<Path>>(
+        dir: T,
+        headers: BTreeMap<String, BTreeMap<String, String>>,
+    ) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+            headers,
+            ttl: None,
+            offline: false,
+            proxy: None,
+            tls: BTreeMap::new(),
+            agents: HashMap::new(),
+            missing: BTreeSet::new(),
+            urls: HashMap::new(),
+        }
+    }
+
+    /// Reuses files downloaded less than `ttl` ago.
+    pub fn ttl(mut self, ttl: Option<Duration>) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Only uses previously downloaded files.
+    pub fn offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    /// Downloads through `proxy` (if any) instead of the environment's proxy.
+    pub fn proxy(mut self, proxy: Option<&str>) -> Self {
+        self.proxy = proxy.map(Into::into);
+        self
+    }
+
+    /// Uses `tls` settings for their host.
+    pub fn tls(mut self, tls: &BTreeMap<String, Tls>) -> Self {
+        self.tls = tls.clone();
+        self
+    }
+
+    /// URLs missing from previous downloads, when offline.
+    pub fn missing(&self) -> Vec<String> {
+        self.missing.iter().cloned().collect()
+    }
+
+    pub fn is_url(str: &str) -> bool {
+        str.starts_with("http://") || str.starts_with("https://")
+    }
+
+    /// The URL `file` was downloaded from, if any.
+    pub fn url(&self, file: &Path) -> Option<&str> {
+        self.urls.get(file).map(String::as_str)
+    }
+
+    /// Downloads `url` (once), returning the downloaded file.
+    pub fn fetch(&mut self, url: &str) -> PigResult<PathBuf> {
+        if let Some((file, _)) = self.urls.iter().find(|(_, known)| *known == url) {
+            return Ok(file.clone());
+        }
+
+        let mut hasher = Hasher::new();
+        hasher.write(url.as_bytes());
+        let file = self.dir.join(format!("{}.yaml", hasher.finish()));
+        let fresh = self.ttl.is_some_and(|ttl| {
+            file.metadata()
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .and_then(|modified| modified.elapsed().ok())
+                .is_some_and(|age| age < ttl)
+        });
+
+        if self.offline && !file.is_file() {
+            self.missing.insert(url.into());
+            return Err(PigError::Offline(vec![url.into()]));
+        } else if !fresh && !self.offline {
+            self.download(url, &file)?;
+        }
+
+        let file = file.canonicalize()?;
+        self.urls.insert(file.clone(), url.into());
+
+        Ok(file)
+    }
+
+    fn download(&mut self, url: &str, file: &Path) -> PigResult<()> {
+        let mut content = Vec::new();
+        let mut request = self.agent(url)?.get(url);
+
+        // Only to their host, and never in clear
+        if let Some(headers) = Self::by_host(&self.headers, url) {
+            if !url.starts_with("https://") {
+                return Err(PigError::InsecureHeaders(url.into()));
+            }
+
+            for (name, value) in headers {
+                request = request.set(name, value);
+            }
+        }
+
+        request
+            .call()
+            .map_err(|err| PigError::Http(err.to_string()))?
+            .into_reader()
+            .read_to_end(&mut content)?;
+
+        // Only write the file when the content changed, not to trigger the watcher
+        if read(file).ok().as_ref() != Some(&content) {
+            create_dir_all(&self.dir)?;
+            write(file, content)?;
+        } else {
+            File::options()
+                .write(true)
+                .open(file)?
+                .set_modified(SystemTime::now())?;
+        }
+
+        Ok(())
+    }
+
+    /// The host of `url`, with its port (if any).
+    fn host(url: &str) -> &str {
+        let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+
+        rest.split('/').next().unwrap_or_default()
+    }
+
+    /// The setting of the host of `url` in `settings`, by host with or without the port.
+    fn by_host<'a, T>(settings: &'a BTreeMap<String, T>, url: &str) -> Option<&'a T> {
+        let host = Self::host(url);
+        let name = host.rsplit_once(':').map_or(host, |(name, _)| name);
+
+        settings.get(host).or_else(|| settings.get(name))
+    }
+
+    /// The agent for the host of `url`, with its TLS settings (if any).
+    fn agent(&mut self, url: &str) -> PigResult<Agent> {
+        let host = Self::host(url).to_string();
+
+        if let Some(agent) = self.agents.get(&host) {
+            return Ok(agent.clone());
+        }
+
+        let http = |err: &dyn std::fmt::Display| PigError::Http(format!("{host}: {err}"));
+        let mut builder = AgentBuilder::new().try_proxy_from_env(true);
+
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(Proxy::new(proxy).map_err(|err| http(&err))?);
+        }
+
+        if let Some(tls) = Self::by_host(&self.tls, url) {
+            let provider = Arc::new(rustls::crypto::ring::default_provider());
+            let versions = ClientConfig::builder_with_provider(provider.clone())
+                .with_safe_default_protocol_versions()
+                .map_err(|err| http(&err))?;
+            let config = if tls.insecure {
+                versions
+                    .dangerous()
+                    .with_custom_certificate_verifier(Arc::new(Insecure(provider)))
+            } else {
+                let mut roots =
+                    RootCertStore::from_iter(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+
+                if let Some(ca) = &tls.ca {
+                    for cert in CertificateDer::pem_file_iter(ca).map_err(|err| http(&err))? {
+                        roots
+                            .add(cert.map_err(|err| http(&err))?)
+                            .map_err(|err| http(&err))?;
+                    }
+                }
+
+                versions.with_root_certificates(roots)
+            };
+
+            builder = builder.tls_config(Arc::new(config.with_no_client_auth()));
+        }
+
+        let agent = builder.build();
+        self.agents.insert(host, agent.clone());
+
+        Ok(agent)
+    }
+
+    /// `reference` relative to `url`.
+    pub fn join(url: &str, reference: &str) -> String {
+        if Self::is_url(reference) {
+            return reference.into();
+        }
+
+        let (scheme, rest) = url.split_once("://").unwrap_or(("https", url));
+        let (host, path) = rest.split_once('/').unwrap_or((rest, ""));
+        let mut segments = if reference.starts_with('/') {
+            Vec::new()
+        } else {
+            let mut segments = path.split('/').collect::<Vec<_>>();
+            segments.pop();
+            segments
+        };
+
+        for segment in reference.split('/') {
+            match segment {
+                "" | "." => {}
+                ".." => {
+                    segments.pop();
+                }
+                segment => segments.push(segment),
+            }
+        }
+
+        format!(
+            "{scheme}://{host}/{}",
+            segments
+                .into_iter()
+                .filter(|segment| !segment.is_empty())
+                .collect::<Vec<_>>()
+                .join("/")
+        )
+    }
+}
+
+/// Accepts any certificate, still checking handshake signatures.
+#[derive(Debug)]
+struct Insecure(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for Insecure {
+    fn verify_server_cert(
+        &self,
+        _: &CertificateDer,
+        _: &[CertificateDer],
+        _: &ServerName,
+        _: &[u8],
+        _: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
@@ -0,0 +1,206 @@
+use crate::{config::Renames, resolver::Resolved, PigError, PigResult, PigWarning};
+use serde_json::Value as Json;
+use std::collections::BTreeSet;
+
+#[derive(Debug)]
+pub enum Rename {}
+
+impl Rename {
+    /// Applies `renames` to the resolved document, updating references (with metadata keys
+    /// prefixed by `prefix`), shared targets and warning about missing names.
+    pub fn apply(resolved: &mut Resolved, renames: &Renames, prefix: &str) -> PigResult<()> {
+        let Resolved {
+            openapi,
+            warnings,
+            shared,
+            targets,
+            ..
+        } = resolved;
+
+        for (old, new) in &renames.schemas {
+            let schemas = openapi["components"]["schemas"].as_object_mut();
+            let Some(schemas) = schemas.filter(|schemas| schemas.contains_key(old)) else {
+                warnings.push(PigWarning::RenameNoMatch("schema".into(), old.clone()));
+                continue;
+            };
+
+            if schemas.contains_key(new) {
+                return Err(PigError::RenameConflict("schema".into(), new.clone()));
+            }
+
+            if let Some(schema) = schemas.remove(old) {
+                schemas.insert(new.clone(), schema);
+            }
+
+            let old = format!("/components/schemas/{}", Self::escape(old));
+            let new = format!("/components/schemas/{}", Self::escape(new));
+            let rename = |path: String| Self::path(&path, &old, &new).unwrap_or(path);
+            Self::schema(openapi, &old, &new, prefix);
+
+            *shared = std::mem::take(shared)
+                .into_iter()
+                .map(|(path, mut value)| {
+                    Self::schema(&mut value, &old, &new, prefix);
+                    (rename(path), value)
+                })
+                .collect();
+            *targets = std::mem::take(targets).into_iter().map(rename).collect();
+        }
+
+        let mut operations = BTreeSet::new();
+        Self::operations(openapi, &mut |operation| {
+            if let Some(id) = operation["operationId"].as_str() {
+                operations.insert(id.to_string());
+            }
+        });
+
+        for (old, new) in &renames.operations {
+            if !operations.remove(old) {
+                warnings.push(PigWarning::RenameNoMatch("operation".into(), old.clone()));
+                continue;
+            }
+
+            if !operations.insert(new.clone()) {
+                return Err(PigError::RenameConflict("operation".into(), new.clone()));
+            }
+
+            Self::operation(openapi, old, new);
+        }
+
+        for (old, new) in &renames.tags {
+            let tags = openapi["tags"].as_array_mut().into_iter().flatten();
+            let mut found = false;
+
+            for tag in tags {
+                if tag["name"] == *new {
+                    return Err(PigError::RenameConflict("tag".into(), new.clone()));
+                }
+
+                if tag["name"] == *old {
+                    tag["name"] = new.clone().into();
+                    found = true;
+                }
+            }
+
+            Self::operations(openapi, &mut |operation| {
+                for tag in operation["tags"].as_array_mut().into_iter().flatten() {
+                    if *tag == *old {
+                        *tag = new.clone().into();
+                        found = true;
+                    }
+                }
+            });
+
+            if !found {
+                warnings.push(PigWarning::RenameNoMatch("tag".into(), old.clone()));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Calls `f` with every operation of `paths` and `webhooks`.
+    fn operations(openapi: &mut Json, f: &mut impl FnMut(&mut Json)) {
+        for key in ["paths", "webhooks"] {
+            let items = openapi[key].as_object_mut().into_iter().flatten();
+
+            for (_, item) in items {
+                let operations = item.as_object_mut().into_iter().flatten();
+
+                for (method, operation) in operations {
+                    if Self::METHODS.contains(&method.as_str()) {
+                        f(operation);
+                    }
+                }
+            }
+        }
+    }
+
+    const METHODS: [&'static str; 8] = [
+        "get", "put", "post", "delete", "options", "head", "patch", "trace",
+    ];
+
+    /// Updates references to the schema at `old` (e.g. `/components/schemas/Pet`) and their
+    /// metadata, as well as discriminator mappings.
+    fn schema(value: &mut Json, old: &str, new: &str, prefix: &str) {
+        match value {
+            Json::Array(values) => {
+                for value in values {
+                    Self::schema(value, old, new, prefix);
+                }
+            }
+            Json::Object(object) => {
+                let key = format!("{prefix}ref");
+                let path = object.get(&key).and_then(Json::as_str);
+
+                if let Some(path) = path.and_then(|path| Self::path(path, old, new)) {
+                    let name = new.rsplit('/').next().unwrap_or_default();
+                    let name = name.replace("~1", "/").replace("~0", "~");
+
+                    object.insert(key, path.into());
+
+                    if let Some(Json::Array(keys)) = object.get_mut(&format!("{prefix}keys")) {
+                        if let Some(last) = keys.last_mut() {
+                            *last = name.clone().into();
+                        }
+                    }
+
+                    if object.contains_key(&format!("{prefix}name")) {
+                        object.insert(format!("{prefix}name"), name.into());
+                    }
+                }
+
+                if let Some(Json::Object(mapping)) = object
+                    .get_mut("discriminator")
+                    .and_then(|discriminator| discriminator.get_mut("mapping"))
+                {
+                    for value in mapping.values_mut() {
+                        if let Some(path) =
+                            value.as_str().and_then(|path| Self::path(path, old, new))
+                        {
+                            *value = path.into();
+                        }
+                    }
+                }
+
+                for value in object.values_mut() {
+                    Self::schema(value, old, new, prefix);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Updates `operationId`s, in operations and links.
+    fn operation(value: &mut Json, old: &str, new: &str) {
+        match value {
+            Json::Array(values) => {
+                for value in values {
+                    Self::operation(value, old, new);
+                }
+            }
+            Json::Object(object) => {
+                for (key, value) in object.iter_mut() {
+                    match value {
+                        Json::String(id) if key == "operationId" && id == old => {
+                            *id = new.into();
+                        }
+                        value => Self::operation(value, old, new),
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// `path` (a `$ref`) pointing to `new` instead of `old`, if it pointed to `old`.
+    fn path(path: &str, old: &str, new: &str) -> Option<String> {
+        path.strip_suffix(old)
+            .filter(|file| file.ends_with('#'))
+            .map(|file| format!("{file}{new}"))
+    }
+
+    fn escape(key: &str) -> String {
+        key.replace('~', "~0").replace('/', "~1")
+    }
+}
@@ -1,69 +1,111 @@
-use crate::PigResult;
-use openapiv3::OpenAPI;
-use serde_json::{json, Value as Json};
+use crate::{
+    config::{RefMetadata, Validate},
+    loader::{self, SpecLoader},
+    manifest::Hasher,
+    remote::Remote,
+    PigError, PigResult, PigWarning,
+};
+use serde_json::{Map, Value as Json};
 use std::{
-    collections::{HashMap, HashSet},
-    fs::File,
-    path::{Path, PathBuf},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fs::{create_dir_all, write},
+    path::{Component, Path, PathBuf},
+    sync::Arc,
 };
+use walkdir::WalkDir;
 
 #[derive(Clone, Eq, PartialEq, Debug)]
-struct Reference {
+pub(crate) struct Reference {
     file: PathBuf,
     keys: Vec<String>,
 }
 
 impl Reference {
-    fn new<T: AsRef<Path>>(current: T, str: &str) -> PigResult<Self> {
+    fn new<T: AsRef<Path>>(current: T, str: &str, remote: &mut Remote) -> PigResult<Self> {
         let current = current.as_ref();
         debug_assert!(current == current.canonicalize()?);
-        debug_assert!(current.is_file());
-
-        let (file, keys) = {
-            let mut split = str.split('#');
 
-            (
-                split.next().expect("Empty reference"),
-                split.next().expect("Empty reference"),
-            )
-        };
+        // Without a fragment, the reference is to the whole file
+        let (file, keys) = str.split_once('#').unwrap_or((str, ""));
 
         let file = file.trim();
         let file = if file.is_empty() {
             current.to_path_buf()
+        } else if Remote::is_url(file) {
+            remote.fetch(file)?
+        } else if let Some(url) = remote.url(current) {
+            remote.fetch(&Remote::join(url, file))?
         } else {
-            let base = current.parent().unwrap();
+            // Directories of partials are their own base
+            let base = match current.is_dir() {
+                true => current,
+                false => current
+                    .parent()
+                    .ok_or_else(|| PigError::InvalidPath(current.into()))?,
+            };
             let file: &Path = file.as_ref();
 
-            file.is_relative()
-                .then(|| base.join(file))
-                .unwrap_or_else(|| file.to_path_buf())
+            if file.is_relative() {
+                base.join(file)
+            } else {
+                file.to_path_buf()
+            }
         }
         .canonicalize()?;
 
-        let keys = keys
+        // A JSON Pointer (RFC 6901) in a URI fragment (RFC 3986)
+        let keys = Self::percent_decode(keys)
             .split('/')
             .map(str::trim)
             .filter(|key| !key.is_empty())
-            .map(Into::into)
+            .map(|key| key.replace("~1", "/").replace("~0", "~"))
             .collect();
 
         Ok(Self { file, keys })
     }
 
     fn display(&self, end: usize) -> String {
-        format!("{}#/{}", self.file.display(), self.keys[..end].join("/"))
+        format!(
+            "{}#{}",
+            self.file.display(),
+            Self::pointer(&self.keys[..end])
+        )
+    }
+
+    /// Escapes `keys` into a JSON Pointer.
+    fn pointer(keys: &[String]) -> String {
+        keys.iter()
+            .map(|key| format!("/{}", key.replace('~', "~0").replace('/', "~1")))
+            .collect()
+    }
+
+    pub(crate) fn percent_decode(str: &str) -> String {
+        let mut bytes = Vec::with_capacity(str.len());
+        let mut iter = str.bytes();
+
+        while let Some(byte) = iter.next() {
+            let hex = iter.clone().take(2).collect::<Vec<_>>();
+
+            match std::str::from_utf8(&hex)
+                .ok()
+                .filter(|_| byte == b'%' && hex.len() == 2)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                Some(decoded) => {
+                    bytes.push(decoded);
+                    iter.nth(1);
+                }
+                None => bytes.push(byte),
+            }
+        }
+
+        String::from_utf8_lossy(&bytes).into()
     }
 }
 
 impl std::fmt::Display for Reference {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}#/{}",
-            self.file.display(),
-            self.keys.join("/").as_str(),
-        )
+        write!(f, "{}", self.display(self.keys.len()))
     }
 }
 
@@ -71,20 +113,276 @@ impl std::fmt::Display for Reference {
 pub struct Resolver {
     file: PathBuf,
     files: HashMap<PathBuf, Json>,
+    roots: Vec<PathBuf>,
+    metadata: RefMetadata,
+    /// The prefix of metadata keys, defaulting to `$`.
+    prefix: Option<String>,
+    base: Option<PathBuf>,
+    cache: Option<PathBuf>,
+    validate: Validate,
+    /// The configured `api_kind` (if any), else auto-detected.
+    kind: Option<String>,
+    loaders: Vec<Arc<dyn SpecLoader>>,
+    /// The loader of the document, once the main file is loaded.
+    loader: Option<Arc<dyn SpecLoader>>,
+    remote: Remote,
+    warnings: Vec<PigWarning>,
+    refs: BTreeMap<String, BTreeSet<String>>,
+    depth: Option<usize>,
+    shared: BTreeMap<String, Json>,
+    /// References to add to `shared`, with their metadata.
+    queue: Vec<(String, Reference, Map<String, Json>)>,
+    /// The files merged into the document, when resolving a directory.
+    partials: Vec<PathBuf>,
+    targets: BTreeSet<String>,
+    /// Whether to keep the parsed files.
+    raw: bool,
+}
+
+/// The result of [`Resolver::resolve`].
+#[derive(Debug)]
+pub struct Resolved {
+    /// The files of the document.
+    pub dependencies: HashSet<PathBuf>,
+    pub openapi: Json,
+    pub warnings: Vec<PigWarning>,
+    /// Files referenced from each file (as in `$file`).
+    pub refs: BTreeMap<String, BTreeSet<String>>,
+    /// Targets of references deeper than the depth limit (if any), by `$ref`.
+    pub shared: BTreeMap<String, Json>,
+    /// The main file, as in `$file`.
+    pub root: String,
+    /// Every resolved reference, as in `$ref`.
+    pub targets: BTreeSet<String>,
+    /// The files of the document, by `$file`.
+    pub files: BTreeMap<String, PathBuf>,
+    /// The parsed files of the document, by `$file`, if kept (see [`Resolver::raw`]).
+    pub raw: BTreeMap<String, Json>,
+}
+
+impl Resolved {
+    /// Components of the main file that are referenced, e.g. `/components/schemas/Pet`.
+    pub fn components(&self) -> BTreeSet<String> {
+        let prefix = format!("{}#", self.root);
+
+        self.targets
+            .iter()
+            .filter_map(|target| target.strip_prefix(&prefix))
+            .filter(|pointer| {
+                pointer.starts_with("/components/") && pointer.matches('/').count() == 3
+            })
+            .map(String::from)
+            .collect()
+    }
 }
 
 impl Resolver {
     pub fn new<T: AsRef<Path>>(file: T) -> PigResult<Self> {
-        let mut resolver = Self {
+        // The main file is loaded when resolving, once the resolver is configured
+        Ok(Self {
             file: file.as_ref().canonicalize()?,
             files: HashMap::new(),
-        };
-        resolver.load(resolver.file.clone())?;
+            loaders: loader::loaders()?,
+            ..Default::default()
+        })
+    }
 
-        Ok(resolver)
+    /// Resolves an inline document, as if it were the content of `file`.
+    pub fn inline<T: AsRef<Path>>(file: T, openapi: Json) -> PigResult<Self> {
+        let file = file.as_ref().canonicalize()?;
+
+        // The document is validated when resolving, once the resolver is configured
+        Ok(Self {
+            files: HashMap::from([(file.clone(), openapi)]),
+            file,
+            loaders: loader::loaders()?,
+            ..Default::default()
+        })
     }
 
-    pub fn resolve(mut self) -> PigResult<(HashSet<PathBuf>, Json)> {
+    /// Resolves the partial documents (e.g. `paths` or `components` fragments) found in `dir`,
+    /// merged into one document, as if it were the content of `file`.
+    pub fn directory<T: AsRef<Path>, U: AsRef<Path>>(file: T, dir: U) -> PigResult<Self> {
+        let mut openapi = Json::Object(Map::new());
+        let mut partials = Vec::new();
+
+        for entry in WalkDir::new(dir)
+            .follow_links(true)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|entry| {
+                entry.depth() == 0 || !entry.file_name().to_string_lossy().starts_with('.')
+            })
+        {
+            let entry = entry?;
+            let path = entry.path().canonicalize()?;
+
+            if !entry.file_type().is_file()
+                || !path.extension().is_some_and(|extension| {
+                    ["yaml", "yml", "json"].iter().any(|e| extension == *e)
+                })
+            {
+                continue;
+            }
+
+            let mut partial = serde_yaml::from_slice::<Json>(&std::fs::read(&path)?)?;
+            Self::rebase(
+                &mut partial,
+                path.parent()
+                    .ok_or_else(|| PigError::InvalidPath(path.clone()))?,
+            );
+            Self::merge(&mut openapi, partial, &path, &mut Vec::new())?;
+            partials.push(path);
+        }
+
+        Ok(Self {
+            partials,
+            ..Self::inline(file, openapi)?
+        })
+    }
+
+    /// Makes relative file references of a partial absolute, as partials are merged elsewhere.
+    fn rebase(value: &mut Json, dir: &Path) {
+        match value {
+            Json::Array(values) => {
+                for value in values {
+                    Self::rebase(value, dir);
+                }
+            }
+            Json::Object(object) => {
+                for (key, value) in object.iter_mut() {
+                    match value {
+                        Json::String(reference) if key == "$ref" => {
+                            let (file, keys) = reference.split_once('#').unwrap_or((reference, ""));
+
+                            if !file.trim().is_empty()
+                                && !Remote::is_url(file.trim())
+                                && Path::new(file.trim()).is_relative()
+                            {
+                                let file = dir.join(file.trim()).display().to_string();
+                                *reference = if reference.contains('#') {
+                                    format!("{file}#{keys}")
+                                } else {
+                                    file
+                                };
+                            }
+                        }
+                        value => Self::rebase(value, dir),
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Merges objects recursively and arrays without duplicates, failing on different values.
+    fn merge(
+        target: &mut Json,
+        partial: Json,
+        file: &Path,
+        keys: &mut Vec<String>,
+    ) -> PigResult<()> {
+        match (target, partial) {
+            (Json::Object(target), Json::Object(partial)) => {
+                for (key, partial) in partial {
+                    match target.get_mut(&key) {
+                        Some(target) => {
+                            keys.push(key);
+                            Self::merge(target, partial, file, keys)?;
+                            keys.pop();
+                        }
+                        None => {
+                            target.insert(key, partial);
+                        }
+                    }
+                }
+            }
+            (Json::Array(target), Json::Array(partial)) => {
+                for partial in partial {
+                    if !target.contains(&partial) {
+                        target.push(partial);
+                    }
+                }
+            }
+            (target, partial) if *target == partial => {}
+            _ => {
+                return Err(PigError::PartialConflict(
+                    file.to_path_buf(),
+                    Reference::pointer(keys),
+                ))
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Only allows references to files inside `roots` (if any).
+    pub fn roots(mut self, roots: &[PathBuf]) -> Self {
+        self.roots = roots.to_vec();
+        self
+    }
+
+    /// Sets the metadata added to resolved references, with paths relative to `base` (if any).
+    pub fn metadata(mut self, metadata: RefMetadata, base: Option<&Path>) -> Self {
+        self.metadata = metadata;
+        self.base = base.map(Path::to_path_buf);
+        self
+    }
+
+    /// Prefixes metadata keys with `prefix` (if any) instead of `$`, e.g. `x-ref` for `x-`.
+    pub fn prefix(mut self, prefix: Option<&str>) -> Self {
+        self.prefix = prefix.map(Into::into);
+        self
+    }
+
+    /// The metadata key for `name`.
+    fn key(&self, name: &str) -> String {
+        format!("{}{name}", self.prefix.as_deref().unwrap_or("$"))
+    }
+
+    /// Keeps the parsed files, unresolved, in [`Resolved::raw`].
+    pub fn raw(mut self, raw: bool) -> Self {
+        self.raw = raw;
+        self
+    }
+
+    /// Caches parsed files in `cache`.
+    pub fn cache(mut self, cache: &Path) -> Self {
+        self.cache = Some(cache.to_path_buf());
+        self
+    }
+
+    /// Sets how files are validated against the `OpenAPI` specification.
+    pub fn validate(mut self, validate: Validate) -> Self {
+        self.validate = validate;
+        self
+    }
+
+    /// Only expands references nested in at most `depth` other references (if any), deeper ones
+    /// pointing to their target in a shared registry.
+    pub fn depth(mut self, depth: Option<usize>) -> Self {
+        self.depth = depth;
+        self
+    }
+
+    /// Loads the document with the loader of `kind` (if any) instead of detecting it.
+    pub fn kind(mut self, kind: Option<&str>) -> Self {
+        self.kind = kind.map(Into::into);
+        self
+    }
+
+    /// Downloads `$ref`s to URLs with `remote`.
+    pub fn remote(mut self, remote: Remote) -> Self {
+        self.remote = remote;
+        self
+    }
+
+    /// The main file, as in `$file`.
+    pub fn root(&self) -> String {
+        self.relative(&self.file).display().to_string()
+    }
+
+    pub fn resolve(mut self) -> PigResult<Resolved> {
         fn resolve(
             resolver: &mut Resolver,
             value: &mut Json,
@@ -99,81 +397,90 @@ impl Resolver {
                 }
                 Json::Object(object) => {
                     if let Some(reference) = object.get("$ref") {
-                        assert!(
-                            object.len() == 1,
-                            "Invalid $ref object: contains more keys ({})",
-                            object
-                                .keys()
-                                .map(String::as_str)
-                                .filter(|key| *key != "$ref")
-                                .collect::<Vec<_>>()
-                                .join(", "),
-                        );
-
-                        let reference = Reference::new(
-                            references
-                                .last()
-                                .map(|reference| &reference.file)
-                                .unwrap_or(&resolver.file),
-                            reference.as_str().expect("$ref is not a string"),
-                        )?;
+                        let reference = reference.as_str().ok_or_else(|| {
+                            PigError::InvalidRef(format!("{reference} (not a string)"))
+                        })?;
+
+                        // Sibling keys (allowed since OpenAPI 3.1) override the target's
+                        let mut siblings = object.clone();
+                        siblings.remove("$ref");
+
+                        let current = references
+                            .last()
+                            .map(|reference| reference.file.clone())
+                            .unwrap_or_else(|| resolver.file.clone());
+                        let reference =
+                            match Reference::new(&current, reference, &mut resolver.remote) {
+                                // Keep going, to report every missing URL at once
+                                Err(PigError::Offline(_)) => return Ok(()),
+                                reference => reference?,
+                            };
+
+                        if reference.file != current {
+                            let target = resolver.relative(&reference.file).display().to_string();
+                            resolver
+                                .refs
+                                .entry(resolver.relative(&current).display().to_string())
+                                .or_default()
+                                .insert(target);
+                        }
 
-                        if references.contains(&reference) {
-                            references.push(reference);
-                            panic!(
-                                "Circular reference detected: {}",
-                                references
-                                    .iter()
-                                    .map(ToString::to_string)
-                                    .collect::<Vec<_>>()
-                                    .join(" -> ")
-                            );
+                        let file = resolver.relative(&reference.file).display().to_string();
+                        let name = match reference.keys.last() {
+                            Some(key) => key.clone(),
+                            None => Path::new(&file)
+                                .file_stem()
+                                .unwrap_or_default()
+                                .to_string_lossy()
+                                .into(),
+                        };
+                        let path = if reference.keys.is_empty() {
+                            file.clone()
+                        } else {
+                            format!("{file}#{}", Reference::pointer(&reference.keys))
+                        };
+                        let mut extension = Map::new();
+                        resolver.targets.insert(path.clone());
+
+                        if resolver.metadata == RefMetadata::Full {
+                            extension.insert(resolver.key("ref"), path.clone().into());
+                            extension.insert(resolver.key("file"), file.into());
+                            extension.insert(resolver.key("keys"), reference.keys.clone().into());
+                        }
+                        if resolver.metadata != RefMetadata::None {
+                            extension.insert(resolver.key("name"), name.into());
                         }
 
-                        let extension = json!({
-                            "$ref": reference.to_string(),
-                            "$file": reference.file.display().to_string(),
-                            "$keys": reference
-                                .keys
-                                .iter()
-                                .map(|key| key.as_str().into())
-                                .collect::<Vec<Json>>(),
-                            "$name": reference
-                                .keys
-                                .last()
-                                .expect("Empty reference keys")
-                                .to_string(),
-                        })
-                        .as_object()
-                        .unwrap()
-                        .clone();
-
-                        *value = {
-                            let mut value = resolver.load(&reference.file)?;
-
-                            for (i, key) in reference.keys.iter().enumerate() {
-                                value = value.get(key).unwrap_or_else(|| {
-                                    panic!("$ref not found: {}", reference.display(i + 1))
-                                });
-                            }
+                        // Recursive types are not expanded any further
+                        if references.contains(&reference) {
+                            extension.insert(resolver.key("ref"), path.into());
+                            extension.insert(resolver.key("circular"), true.into());
+                            *value = extension.into();
 
-                            let mut value = value.clone();
+                            return Ok(());
+                        }
 
-                            references.push(reference);
-                            resolve(resolver, &mut value, references)?;
-                            references.pop();
+                        // Deep references point to their target in the shared registry
+                        if resolver.depth.is_some_and(|depth| references.len() > depth) {
+                            let mut pointer = extension.clone();
+                            pointer.insert(resolver.key("ref"), path.clone().into());
+                            pointer.insert(resolver.key("shared"), true.into());
 
-                            let object = value.as_object_mut().expect("$ref is not a YAML object");
+                            for (key, mut sibling) in siblings {
+                                resolve(resolver, &mut sibling, references)?;
+                                pointer.insert(key, sibling);
+                            }
 
-                            for key in object.keys() {
-                                if extension.contains_key(key) {
-                                    panic!("Reference contains {key}");
-                                }
+                            if !resolver.shared.contains_key(&path) {
+                                resolver.queue.push((path, reference, extension));
                             }
 
-                            object.extend(extension);
-                            value
-                        };
+                            *value = pointer.into();
+
+                            return Ok(());
+                        }
+
+                        *value = target(resolver, reference, extension, siblings, references)?;
                     } else {
                         for value in object.values_mut() {
                             resolve(resolver, value, references)?;
@@ -185,69 +492,452 @@ impl Resolver {
             Ok(())
         }
 
-        let mut output = self.files.get(&self.file).unwrap().clone();
+        /// The resolved target of `reference`.
+        fn target(
+            resolver: &mut Resolver,
+            reference: Reference,
+            extension: Map<String, Json>,
+            siblings: Map<String, Json>,
+            references: &mut Vec<Reference>,
+        ) -> PigResult<Json> {
+            let mut value = resolver.load(&reference.file)?;
+
+            for (i, key) in reference.keys.iter().enumerate() {
+                value = match value {
+                    Json::Array(values) => key.parse::<usize>().ok().and_then(|i| values.get(i)),
+                    value => value.get(key),
+                }
+                .ok_or_else(|| PigError::RefNotFound(reference.display(i + 1)))?;
+            }
+
+            // Metadata keys of the spec itself, not those of a chained reference (`$ref` being
+            // resolved, then overridden)
+            let conflict = value.as_object().and_then(|object| {
+                object
+                    .keys()
+                    .find(|key| *key != "$ref" && extension.contains_key(*key))
+                    .cloned()
+            });
+            let mut value = value.clone();
+            let display = reference.to_string();
+
+            if let Some(key) = conflict {
+                return Err(PigError::InvalidRef(format!(
+                    "{display} (target contains {key}, see `ref_prefix`)"
+                )));
+            }
+
+            references.push(reference);
+            resolve(resolver, &mut value, references)?;
+            references.pop();
+
+            // Only objects get metadata, e.g. not `#/servers/0/url`
+            if let Some(object) = value.as_object_mut() {
+                object.extend(extension);
+
+                for (key, mut sibling) in siblings {
+                    resolve(resolver, &mut sibling, references)?;
+                    object.insert(key, sibling);
+                }
+            } else if !siblings.is_empty() {
+                return Err(PigError::InvalidRef(format!(
+                    "{display} (siblings on a target that is not an object)"
+                )));
+            }
+
+            Ok(value)
+        }
+
+        if let Some(openapi) = self.files.remove(&self.file) {
+            let openapi = self.check(&self.file.clone(), openapi)?;
+            self.files.insert(self.file.clone(), openapi);
+        }
+
+        let mut output = self.load(self.file.clone())?.clone();
         resolve(&mut self, &mut output, &mut Vec::new())?;
 
-        let len = self.files.len();
-        let dependencies = self.files.into_keys().collect::<HashSet<_>>();
-        assert!(dependencies.len() == len);
+        // Even unvalidated, documents are objects
+        if !output.is_object() {
+            let kind = self
+                .loader
+                .as_ref()
+                .map_or("document", |loader| loader.kind());
+            return Err(PigError::InvalidSpec(kind.into(), "not an object".into()));
+        }
+
+        while let Some((path, reference, extension)) = self.queue.pop() {
+            if !self.shared.contains_key(&path) {
+                let value = target(&mut self, reference, extension, Map::new(), &mut Vec::new())?;
+                self.shared.insert(path, value);
+            }
+        }
 
-        Ok((dependencies, output))
+        let missing = self.remote.missing();
+        if !missing.is_empty() {
+            return Err(PigError::Offline(missing));
+        }
+
+        let root = self.root();
+        let files = self
+            .files
+            .keys()
+            .chain(&self.partials)
+            .map(|file| (self.relative(file).display().to_string(), file.clone()))
+            .collect();
+        let raw = self
+            .files
+            .iter()
+            .filter(|_| self.raw)
+            .map(|(file, value)| (self.relative(file).display().to_string(), value.clone()))
+            .collect();
+        let mut dependencies = self.files.into_keys().collect::<HashSet<_>>();
+        dependencies.extend(self.partials);
+
+        Ok(Resolved {
+            dependencies,
+            openapi: output,
+            warnings: self.warnings,
+            refs: self.refs,
+            shared: self.shared,
+            root,
+            targets: self.targets,
+            files,
+            raw,
+        })
     }
 }
 
 impl Resolver {
+    /// `file` relative to `base` (if any), or its URL for downloaded files.
+    fn relative(&self, file: &Path) -> PathBuf {
+        if let Some(url) = self.remote.url(file) {
+            return url.into();
+        }
+
+        let Some(base) = &self.base else {
+            return file.to_path_buf();
+        };
+        let common = base
+            .components()
+            .zip(file.components())
+            .take_while(|(base, file)| base == file)
+            .count();
+
+        base.components()
+            .skip(common)
+            .map(|_| Component::ParentDir)
+            .chain(file.components().skip(common))
+            .collect()
+    }
+
     fn load<T: AsRef<Path>>(&mut self, file: T) -> PigResult<&Json> {
         let file = file.as_ref();
         let file = if file.is_relative() {
-            self.file.parent().unwrap().join(file)
+            self.file
+                .parent()
+                .ok_or_else(|| PigError::InvalidPath(self.file.clone()))?
+                .join(file)
         } else {
             file.to_path_buf()
         }
         .canonicalize()?;
 
-        if !self.files.contains_key(&file) {
-            // After the main file is loaded, we will get the OpenAPI version
-            let value = if let Some(openapi) = {
-                self.files
-                    .get(&self.file)
-                    .and_then(|value| value.get("openapi"))
-                    .and_then(|version| version.as_str())
-            } {
-                let value = serde_yaml::from_reader::<_, Json>(File::open(&file)?)?;
-
-                // We allow omitting the mandatory fields in other files
-                {
-                    let mut value = value.clone();
-
-                    if let Some(object) = value.as_object_mut() {
-                        object.extend(
-                            json!({
-                                "openapi": openapi,
-                                "info": { "title": "", "version": "" },
-                                "paths": {},
-                            })
-                            .as_object()
-                            .unwrap()
-                            .clone(),
-                        );
-                    }
+        if !self.roots.is_empty()
+            && file != self.file
+            && self.remote.url(&file).is_none()
+            && !self.roots.iter().any(|root| file.starts_with(root))
+        {
+            return Err(PigError::RefOutsideRoots(file));
+        }
 
-                    // Make sure the file deserializes correctly into OpenAPI
-                    serde_json::from_value::<OpenAPI>(value)?;
+        if !self.files.contains_key(&file) {
+            // After the main file is loaded, we will get its loader
+            let kind = self
+                .loader
+                .as_ref()
+                .map(|loader| loader.kind())
+                .or(self.kind.as_deref());
+            let content = std::fs::read(&file)?;
+            let cache = self.cache.as_ref().map(|cache| {
+                let mut hasher = Hasher::new();
+                hasher.write(env!("CARGO_PKG_VERSION").as_bytes());
+                hasher.write(kind.unwrap_or_default().as_bytes());
+                hasher.write(format!("{:?}", self.validate).as_bytes());
+                hasher.write(&content);
+
+                cache.join(format!("{}.json", hasher.finish()))
+            });
+            // Cached with the kind of the loader, as loaders may convert documents
+            let cached = cache
+                .as_ref()
+                .and_then(|cache| std::fs::read(cache).ok())
+                .and_then(|value| serde_json::from_slice::<(String, Json)>(&value).ok());
+
+            let value = if let Some((kind, value)) = cached {
+                if self.loader.is_none() {
+                    self.kind.get_or_insert(kind);
+                    self.select(&value)?;
                 }
 
                 value
             } else {
-                // Make sure the file deserializes correctly into OpenAPI
-                let value = serde_yaml::from_reader::<_, OpenAPI>(File::open(&file)?)?;
+                let warnings = self.warnings.len();
+                let value = serde_yaml::from_slice::<Json>(&content)?;
+                let value = self.check(&file, value)?;
+
+                // Invalid files are not cached, to warn again next time
+                if let Some(cache) = cache.as_ref().filter(|_| warnings == self.warnings.len()) {
+                    if let Some(dir) = cache.parent() {
+                        create_dir_all(dir)?;
+                    }
+                    let kind = self.loader.as_ref().map(|loader| loader.kind());
+                    write(cache, serde_json::to_vec(&(kind, &value))?)?;
+                }
 
-                serde_json::to_value(value)?
+                value
             };
 
             self.files.insert(file.clone(), value);
         }
 
-        Ok(self.files.get(&file).unwrap())
+        self.files
+            .get(&file)
+            .ok_or_else(|| PigError::InvalidPath(file.clone()))
+    }
+
+    /// Selects the loader of the document from the main file.
+    fn select(&mut self, main: &Json) -> PigResult<()> {
+        let loader = match &self.kind {
+            Some(kind) => self.loaders.iter().find(|loader| loader.kind() == kind),
+            None => self
+                .loaders
+                .iter()
+                .find(|loader| loader.detect(main))
+                .or_else(|| {
+                    self.loaders
+                        .iter()
+                        .find(|loader| loader.kind() == "openapi30")
+                }),
+        };
+
+        self.loader = Some(
+            loader
+                .ok_or_else(|| PigError::UnknownApiKind(self.kind.clone().unwrap_or_default()))?
+                .clone(),
+        );
+
+        Ok(())
+    }
+
+    /// Normalizes a file with the document's loader, then validates it according to the
+    /// validation mode. Lenient validation records problems as warnings.
+    fn check(&mut self, file: &Path, value: Json) -> PigResult<Json> {
+        if self.loader.is_none() {
+            self.select(&value)?;
+        }
+
+        let loader = self
+            .loader
+            .clone()
+            .ok_or_else(|| PigError::UnknownApiKind(self.kind.clone().unwrap_or_default()))?;
+        let main = Some(file)
+            .filter(|file| *file != self.file)
+            .and_then(|_| self.files.get(&self.file));
+        let value = loader.normalize(value, main);
+
+        if self.validate == Validate::Off {
+            return Ok(value);
+        }
+
+        match loader.validate(&value, main) {
+            Ok(validated) => Ok(validated.unwrap_or(value)),
+            Err(err) if self.validate == Validate::Lenient => {
+                self.warnings.push(PigWarning::InvalidOpenapi(
+                    file.to_path_buf(),
+                    err.to_string(),
+                ));
+                Ok(value)
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::fs::{create_dir_all, remove_dir_all, write};
+
+    /// A fresh directory with `files`.
+    fn dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("pig-resolver-{name}-{}", std::process::id()));
+        let _ = remove_dir_all(&dir);
+        create_dir_all(&dir).unwrap();
+
+        for (file, content) in files {
+            write(dir.join(file), content).unwrap();
+        }
+
+        dir.canonicalize().unwrap()
+    }
+
+    /// Resolves `main.yaml` in `dir`, unvalidated.
+    fn resolve(dir: &Path, metadata: RefMetadata) -> PigResult<Resolved> {
+        Resolver::new(dir.join("main.yaml"))?
+            .metadata(metadata, Some(dir))
+            .validate(Validate::Off)
+            .resolve()
+    }
+
+    #[test]
+    fn percent_decode() {
+        assert_eq!(Reference::percent_decode("%7Bid%7D"), "{id}");
+        assert_eq!(Reference::percent_decode("a%20b%C3%A9"), "a bé");
+        assert_eq!(Reference::percent_decode("%zz%4"), "%zz%4");
+        assert_eq!(Reference::percent_decode("100%"), "100%");
+    }
+
+    #[test]
+    fn pointers() {
+        let dir = dir("pointers", &[("main.yaml", "{}"), ("other.yaml", "{}")]);
+        let main = dir.join("main.yaml");
+        let keys = |reference: &str| {
+            Reference::new(&main, reference, &mut Remote::default())
+                .unwrap()
+                .keys
+        };
+
+        assert_eq!(keys("#/paths/~1pets~1%7Bid%7D"), ["paths", "/pets/{id}"]);
+        // `~01` is `~1` escaped, not `/`
+        assert_eq!(keys("#/a~01/b~0"), ["a~1", "b~"]);
+        // Decoded before being split (RFC 6901)
+        assert_eq!(keys("#/a%20b%2Fc"), ["a b", "c"]);
+        assert!(keys("#").is_empty());
+        assert!(keys("").is_empty());
+
+        let reference = Reference::new(&main, "other.yaml#/x", &mut Remote::default()).unwrap();
+        assert_eq!(reference.file, dir.join("other.yaml"));
+        assert_eq!(
+            Reference::pointer(&["a/b".into(), "c~d".into()]),
+            "/a~1b/c~0d"
+        );
+        assert!(Reference::new(&main, "missing.yaml", &mut Remote::default()).is_err());
+
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn decoded_references() {
+        let dir = dir(
+            "resolve",
+            &[
+                (
+                    "main.yaml",
+                    r##"openapi: 3.0.3
+info: { title: T, version: "1" }
+paths: {}
+components:
+  schemas:
+    Pet:
+      type: object
+      properties:
+        parent: { $ref: "#/components/schemas/Pet" }
+        tag: { $ref: "common.yaml#/Tag~1Name" }
+"##,
+                ),
+                ("common.yaml", "Tag/Name: { type: string }"),
+            ],
+        );
+        let resolved = resolve(&dir, RefMetadata::Full).unwrap();
+        let pet = &resolved.openapi["components"]["schemas"]["Pet"]["properties"];
+
+        assert_eq!(
+            pet["tag"],
+            json!({
+                "type": "string",
+                "$ref": "common.yaml#/Tag~1Name",
+                "$file": "common.yaml",
+                "$keys": ["Tag/Name"],
+                "$name": "Tag/Name",
+            })
+        );
+        // Expanded once, from the component
+        assert_eq!(pet["parent"]["$name"], "Pet");
+        assert_eq!(pet["parent"]["properties"]["parent"]["$circular"], true);
+        assert_eq!(
+            resolved.refs["main.yaml"],
+            BTreeSet::from(["common.yaml".into()])
+        );
+
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn chained_references() {
+        let dir = dir(
+            "chained",
+            &[(
+                "main.yaml",
+                r##"components:
+  schemas:
+    A: { $ref: "#/components/schemas/B" }
+    B: { $ref: "#/components/schemas/C" }
+    C: { type: string }
+x-a: { $ref: "#/components/schemas/A" }
+"##,
+            )],
+        );
+        let resolved = resolve(&dir, RefMetadata::Full).unwrap();
+
+        // The outermost reference wins
+        assert_eq!(
+            resolved.openapi["x-a"],
+            json!({
+                "type": "string",
+                "$ref": "main.yaml#/components/schemas/A",
+                "$file": "main.yaml",
+                "$keys": ["components", "schemas", "A"],
+                "$name": "A",
+            })
+        );
+        assert_eq!(resolved.openapi["components"]["schemas"]["A"]["$name"], "B");
+
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn metadata_conflicts() {
+        let dir = dir(
+            "conflicts",
+            &[(
+                "main.yaml",
+                r##"components:
+  schemas:
+    A: { type: object, $name: mine }
+x-a: { $ref: "#/components/schemas/A" }
+"##,
+            )],
+        );
+
+        assert!(matches!(
+            resolve(&dir, RefMetadata::Full),
+            Err(PigError::InvalidRef(_))
+        ));
+        assert!(resolve(&dir, RefMetadata::None).is_ok());
+
+        remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn non_object_documents() {
+        let dir = dir("non-object", &[("main.yaml", "[]")]);
+
+        assert!(matches!(
+            resolve(&dir, RefMetadata::None),
+            Err(PigError::InvalidSpec(_, _))
+        ));
+
+        remove_dir_all(dir).unwrap();
     }
 }
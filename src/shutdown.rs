@@ -0,0 +1,38 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Graceful shutdown on SIGINT and SIGTERM when watching: the generation in flight finishes, so
+/// that no file is left half written, then `pig` stops. A second signal exits right away.
+#[derive(Debug)]
+pub enum Shutdown {}
+
+impl Shutdown {
+    /// Handles SIGINT and SIGTERM from now on.
+    pub fn handle() {
+        #[cfg(unix)]
+        {
+            extern "C" fn request(_: libc::c_int) {
+                if REQUESTED.swap(true, Ordering::Relaxed) {
+                    // SAFETY: `_exit` is async-signal-safe
+                    unsafe { libc::_exit(130) };
+                }
+            }
+
+            for signal in [libc::SIGINT, libc::SIGTERM] {
+                // SAFETY: the handler only stores an atomic, or exits
+                unsafe {
+                    libc::signal(
+                        signal,
+                        request as extern "C" fn(libc::c_int) as libc::sighandler_t,
+                    );
+                }
+            }
+        }
+    }
+
+    /// Whether a shutdown was requested.
+    pub fn is_requested() -> bool {
+        REQUESTED.load(Ordering::Relaxed)
+    }
+}
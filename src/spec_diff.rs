@@ -0,0 +1,433 @@
+use crate::derive::Derive;
+use serde_json::Value as Json;
+use std::collections::BTreeSet;
+
+/// A difference between two resolved `OpenAPI` documents, breaking clients or not.
+#[derive(Clone, Debug)]
+pub struct SpecChange {
+    pub breaking: bool,
+    /// Where, e.g. `GET /pets`, `GET /pets 200 application/json [].name`.
+    pub at: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for SpecChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = if self.breaking { "breaking" } else { "ok" };
+
+        write!(f, "{kind:>8} {}: {}", self.at, self.message)
+    }
+}
+
+/// Whether a schema is sent by clients or received by them.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum Direction {
+    Request,
+    Response,
+}
+
+/// Lists the changes of operations, parameters, bodies and schema fields from `old` to `new`.
+pub fn spec_diff(old: &Json, new: &Json) -> Vec<SpecChange> {
+    let mut changes = Vec::new();
+    let mut push = |breaking: bool, at: String, message: String| {
+        changes.push(SpecChange {
+            breaking,
+            at,
+            message,
+        })
+    };
+
+    let operations = |openapi: &Json| {
+        let mut operations = Vec::new();
+
+        for (path, item) in openapi["paths"].as_object().into_iter().flatten() {
+            for method in Derive::METHODS {
+                if let Some(operation) = item.get(method) {
+                    // Path level parameters apply to every operation
+                    let mut operation = operation.clone();
+                    for parameter in item["parameters"].as_array().into_iter().flatten() {
+                        match &mut operation["parameters"] {
+                            Json::Array(parameters) => parameters.push(parameter.clone()),
+                            parameters => *parameters = Json::Array(vec![parameter.clone()]),
+                        }
+                    }
+
+                    operations.push((format!("{} {path}", method.to_uppercase()), operation));
+                }
+            }
+        }
+
+        operations
+    };
+    let (olds, news) = (operations(old), operations(new));
+
+    for (at, old) in &olds {
+        match news.iter().find(|(other, _)| other == at) {
+            Some((_, new)) => operation(old, new, at, &mut push),
+            None => push(true, at.clone(), "removed operation".into()),
+        }
+    }
+
+    for (at, _) in &news {
+        if !olds.iter().any(|(other, _)| other == at) {
+            push(false, at.clone(), "added operation".into());
+        }
+    }
+
+    changes
+}
+
+fn operation(old: &Json, new: &Json, at: &str, push: &mut impl FnMut(bool, String, String)) {
+    let parameters = |operation: &Json| {
+        operation["parameters"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|parameter| {
+                let name = format!(
+                    "{} parameter {}",
+                    parameter["in"].as_str().unwrap_or_default(),
+                    parameter["name"].as_str().unwrap_or_default(),
+                );
+
+                (name, parameter.clone())
+            })
+            .collect::<Vec<_>>()
+    };
+    let (olds, news) = (parameters(old), parameters(new));
+
+    for (name, old) in &olds {
+        let at = format!("{at} {name}");
+
+        match news.iter().find(|(other, _)| other == name) {
+            Some((_, new)) => {
+                if !is_required(old) && is_required(new) {
+                    push(true, at.clone(), "became required".into());
+                }
+
+                schema(
+                    &old["schema"],
+                    &new["schema"],
+                    &at,
+                    "",
+                    Direction::Request,
+                    push,
+                );
+            }
+            None => push(true, at, "removed".into()),
+        }
+    }
+
+    for (name, new) in &news {
+        if !olds.iter().any(|(other, _)| other == name) {
+            let required = is_required(new);
+            let message = if required { "added, required" } else { "added" };
+
+            push(required, format!("{at} {name}"), message.into());
+        }
+    }
+
+    match (old.get("requestBody"), new.get("requestBody")) {
+        (Some(old), Some(new)) => {
+            if !is_required(old) && is_required(new) {
+                push(true, format!("{at} body"), "became required".into());
+            }
+
+            contents(old, new, &format!("{at} body"), Direction::Request, push);
+        }
+        (Some(_), None) => push(true, format!("{at} body"), "removed".into()),
+        (None, Some(new)) => push(is_required(new), format!("{at} body"), "added".into()),
+        (None, None) => {}
+    }
+
+    let responses = |operation: &Json| {
+        operation["responses"]
+            .as_object()
+            .into_iter()
+            .flatten()
+            .map(|(status, response)| (status.clone(), response.clone()))
+            .collect::<Vec<_>>()
+    };
+    let (olds, news) = (responses(old), responses(new));
+
+    for (status, old) in &olds {
+        let at = format!("{at} {status}");
+
+        match news.iter().find(|(other, _)| other == status) {
+            Some((_, new)) => contents(old, new, &at, Direction::Response, push),
+            None => push(true, at, "removed response".into()),
+        }
+    }
+
+    for (status, _) in &news {
+        if !olds.iter().any(|(other, _)| other == status) {
+            push(false, format!("{at} {status}"), "added response".into());
+        }
+    }
+}
+
+/// Compares the schemas of bodies (or responses), by media type.
+fn contents(
+    old: &Json,
+    new: &Json,
+    at: &str,
+    direction: Direction,
+    push: &mut impl FnMut(bool, String, String),
+) {
+    for (media, old) in old["content"].as_object().into_iter().flatten() {
+        let at = format!("{at} {media}");
+
+        match new["content"].get(media) {
+            Some(new) => schema(&old["schema"], &new["schema"], &at, "", direction, push),
+            None => push(true, at, "removed media type".into()),
+        }
+    }
+
+    for (media, _) in new["content"].as_object().into_iter().flatten() {
+        if old["content"].get(media).is_none() {
+            push(false, format!("{at} {media}"), "added media type".into());
+        }
+    }
+}
+
+/// Compares types, enums, properties and items of the schemas at `at`, for `field` (e.g.
+/// `[].owner.name`) within them.
+fn schema(
+    old: &Json,
+    new: &Json,
+    at: &str,
+    field: &str,
+    direction: Direction,
+    push: &mut impl FnMut(bool, String, String),
+) {
+    let here = match field {
+        "" => at.to_string(),
+        field => format!("{at} {field}"),
+    };
+
+    if old["type"] != new["type"] {
+        push(
+            true,
+            here,
+            format!("type changed from {} to {}", old["type"], new["type"]),
+        );
+        return;
+    }
+
+    let values = |schema: &Json| {
+        schema["enum"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(Json::to_string)
+            .collect::<BTreeSet<_>>()
+    };
+    let (olds, news) = (values(old), values(new));
+
+    // Clients may send removed values, or receive added ones
+    for value in olds.difference(&news) {
+        let breaking = direction == Direction::Request;
+        push(
+            breaking,
+            here.clone(),
+            format!("removed enum value {value}"),
+        );
+    }
+
+    for value in news.difference(&olds).filter(|_| !olds.is_empty()) {
+        let breaking = direction == Direction::Response;
+        push(breaking, here.clone(), format!("added enum value {value}"));
+    }
+
+    let required = |schema: &Json| {
+        schema["required"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(Json::as_str)
+            .map(String::from)
+            .collect::<BTreeSet<_>>()
+    };
+    let (olds, news) = (required(old), required(new));
+    let child = |name: &str| match field {
+        "" => name.to_string(),
+        field => format!("{field}.{name}"),
+    };
+
+    for (name, old) in old["properties"].as_object().into_iter().flatten() {
+        let field = child(name);
+        let here = format!("{at} {field}");
+
+        match new["properties"].get(name) {
+            Some(new) => {
+                if !olds.contains(name) && news.contains(name) {
+                    let breaking = direction == Direction::Request;
+                    push(breaking, here, "became required".into());
+                }
+
+                schema(old, new, at, &field, direction, push);
+            }
+            None => {
+                let breaking = direction == Direction::Response;
+                push(breaking, here, "removed field".into());
+            }
+        }
+    }
+
+    for (name, _) in new["properties"].as_object().into_iter().flatten() {
+        if old["properties"].get(name).is_none() {
+            let required = news.contains(name);
+            let breaking = direction == Direction::Request && required;
+            let message = if required {
+                "added field, required"
+            } else {
+                "added field"
+            };
+
+            push(breaking, format!("{at} {}", child(name)), message.into());
+        }
+    }
+
+    if old.get("items").is_some() || new.get("items").is_some() {
+        let field = format!("{field}[]");
+        schema(&old["items"], &new["items"], at, &field, direction, push);
+    }
+}
+
+fn is_required(value: &Json) -> bool {
+    value["required"].as_bool().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn pets(get: Json) -> Json {
+        json!({ "paths": { "/pets": { "get": get } } })
+    }
+
+    fn changes(old: &Json, new: &Json) -> Vec<(bool, String, String)> {
+        spec_diff(old, new)
+            .into_iter()
+            .map(|change| (change.breaking, change.at, change.message))
+            .collect()
+    }
+
+    #[test]
+    fn operations() {
+        let old = pets(json!({}));
+        let new = json!({ "paths": { "/cats": { "get": {} } } });
+
+        assert_eq!(
+            changes(&old, &new),
+            [
+                (true, "GET /pets".into(), "removed operation".into()),
+                (false, "GET /cats".into(), "added operation".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn path_parameters() {
+        let parameter = json!({ "name": "id", "in": "path", "required": true });
+        let old = json!({ "paths": { "/pets": { "parameters": [parameter], "get": {} } } });
+        let new = pets(json!({}));
+
+        assert_eq!(
+            changes(&old, &new),
+            [(true, "GET /pets path parameter id".into(), "removed".into())]
+        );
+        assert_eq!(
+            changes(&new, &old),
+            [(
+                true,
+                "GET /pets path parameter id".into(),
+                "added, required".into()
+            )]
+        );
+    }
+
+    #[test]
+    fn fields_by_direction() {
+        let body = |schema: Json| {
+            pets(json!({
+                "requestBody": { "content": { "application/json": { "schema": schema } } },
+                "responses": { "200": { "content": { "application/json": { "schema": schema } } } },
+            }))
+        };
+        let old = body(json!({ "type": "object", "properties": { "name": { "type": "string" } } }));
+        let new = body(json!({ "type": "object", "properties": {} }));
+
+        // Clients may not send it anymore, but expect to receive it
+        assert_eq!(
+            changes(&old, &new),
+            [
+                (
+                    false,
+                    "GET /pets body application/json name".into(),
+                    "removed field".into()
+                ),
+                (
+                    true,
+                    "GET /pets 200 application/json name".into(),
+                    "removed field".into()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn nested_types() {
+        let response = |kind: &str| {
+            pets(
+                json!({ "responses": { "200": { "content": { "application/json": { "schema": {
+                "type": "array",
+                "items": { "type": "object", "properties": { "age": { "type": kind } } },
+            } } } } } }),
+            )
+        };
+
+        assert_eq!(
+            changes(&response("integer"), &response("string")),
+            [(
+                true,
+                "GET /pets 200 application/json [].age".into(),
+                "type changed from \"integer\" to \"string\"".into()
+            )]
+        );
+        assert!(changes(&response("integer"), &response("integer")).is_empty());
+    }
+
+    #[test]
+    fn enums() {
+        let parameter = |values: Json| {
+            pets(
+                json!({ "parameters": [{ "name": "sort", "in": "query", "schema": { "enum": values } }] }),
+            )
+        };
+
+        assert_eq!(
+            changes(
+                &parameter(json!(["asc", "desc"])),
+                &parameter(json!(["asc"]))
+            ),
+            [(
+                true,
+                "GET /pets query parameter sort".into(),
+                "removed enum value \"desc\"".into()
+            )]
+        );
+        assert_eq!(
+            changes(
+                &parameter(json!(["asc"])),
+                &parameter(json!(["asc", "desc"]))
+            ),
+            [(
+                false,
+                "GET /pets query parameter sort".into(),
+                "added enum value \"desc\"".into()
+            )]
+        );
+    }
+}
@@ -0,0 +1,223 @@
+use crate::{remote::Remote, PigError, PigResult};
+use serde_json::{json, Value as Json};
+use std::{
+    collections::BTreeMap,
+    fs::{create_dir_all, read, read_dir, write},
+    path::{Component, Path, PathBuf},
+};
+
+/// Writes a document as a directory tree: the main file, plus one file per path item, webhook
+/// and component, with `$ref`s between them.
+#[derive(Debug)]
+pub struct Split {
+    /// The document's directory, against which its relative `$ref`s resolve.
+    input: PathBuf,
+    output: PathBuf,
+    openapi: Json,
+    /// Files by pointer into the document, e.g. `/components/schemas/Pet`.
+    locations: BTreeMap<String, PathBuf>,
+}
+
+impl Split {
+    const MAIN: &'static str = "openapi.yaml";
+
+    /// Splits `input` into `output`, which must be empty unless `force`.
+    pub fn new(input: &Path, output: &Path, force: bool) -> PigResult<Self> {
+        let input = input.canonicalize()?;
+        let openapi = serde_yaml::from_slice(&read(&input)?)?;
+
+        if !force && read_dir(output).is_ok_and(|mut files| files.next().is_some()) {
+            return Err(PigError::NotEmpty(output.to_path_buf()));
+        }
+
+        create_dir_all(output)?;
+
+        Ok(Self {
+            input: input
+                .parent()
+                .ok_or_else(|| PigError::InvalidPath(input.to_path_buf()))?
+                .to_path_buf(),
+            output: output.canonicalize()?,
+            openapi,
+            locations: BTreeMap::new(),
+        })
+    }
+
+    /// Writes the files, returning how many.
+    pub fn write(mut self) -> PigResult<usize> {
+        let mut files = Vec::new();
+
+        for (keys, dir) in [
+            (vec!["paths".to_string()], PathBuf::from("paths")),
+            (vec!["webhooks".to_string()], PathBuf::from("webhooks")),
+        ]
+        .into_iter()
+        .chain(
+            self.openapi["components"]
+                .as_object()
+                .into_iter()
+                .flatten()
+                .map(|(section, _)| {
+                    (
+                        vec!["components".to_string(), section.clone()],
+                        Path::new("components").join(section),
+                    )
+                }),
+        ) {
+            let parent = keys.iter().fold(&self.openapi, |value, key| &value[key]);
+
+            for (name, value) in parent.as_object().into_iter().flatten() {
+                let stem = Self::stem(name);
+                let file = (1..)
+                    .map(|i| match i {
+                        1 => dir.join(format!("{stem}.yaml")),
+                        i => dir.join(format!("{stem}_{i}.yaml")),
+                    })
+                    .find(|file| !files.iter().any(|(_, other, _)| other == file))
+                    .unwrap();
+                let mut keys = keys.clone();
+                keys.push(name.clone());
+
+                files.push((keys, file, value.clone()));
+            }
+        }
+
+        for (keys, file, _) in &files {
+            let location = keys
+                .iter()
+                .map(|key| format!("/{}", key.replace('~', "~0").replace('/', "~1")))
+                .collect::<String>();
+
+            self.locations.insert(location, file.clone());
+        }
+
+        let mut main = self.openapi.clone();
+        self.rebase(&mut main, Path::new(Self::MAIN));
+
+        for (keys, file, _) in &files {
+            let value = keys.iter().fold(&mut main, |value, key| &mut value[key]);
+            *value = json!({ "$ref": file.display().to_string() });
+        }
+
+        files.push((Vec::new(), PathBuf::from(Self::MAIN), main));
+
+        for (keys, file, mut value) in files.iter().cloned() {
+            if !keys.is_empty() {
+                self.rebase(&mut value, &file);
+            }
+
+            let file = self.output.join(file);
+            create_dir_all(
+                file.parent()
+                    .ok_or_else(|| PigError::InvalidPath(file.clone()))?,
+            )?;
+            write(&file, serde_yaml::to_string(&value)?)?;
+        }
+
+        Ok(files.len())
+    }
+
+    /// Rewrites `$ref`s in the value of `file` (relative to the output directory): local ones
+    /// to the files of their targets, relative ones to the same files as before.
+    fn rebase(&self, value: &mut Json, file: &Path) {
+        match value {
+            Json::Array(values) => {
+                for value in values {
+                    self.rebase(value, file);
+                }
+            }
+            Json::Object(object) => {
+                for (key, value) in object.iter_mut() {
+                    match value {
+                        Json::String(reference) if key == "$ref" => {
+                            if let Some(reference) = self.reference(reference, file) {
+                                *value = reference.into();
+                            }
+                        }
+                        value => self.rebase(value, file),
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// `reference` as seen from `file`, if it changes.
+    fn reference(&self, reference: &str, file: &Path) -> Option<String> {
+        let (target, pointer) = reference.split_once('#').unwrap_or((reference, ""));
+        let dir = self.output.join(file).parent()?.to_path_buf();
+
+        if target.trim().is_empty() {
+            // The file of the target or of one of its parents
+            let (target, rest) = self
+                .locations
+                .iter()
+                .filter_map(|(location, file)| {
+                    let rest = pointer.strip_prefix(location.as_str())?;
+                    (rest.is_empty() || rest.starts_with('/')).then_some((file.as_path(), rest))
+                })
+                .next()
+                .unwrap_or((Path::new(Self::MAIN), pointer));
+
+            // Local to the main file
+            if target == Path::new(Self::MAIN) && file == Path::new(Self::MAIN) {
+                return None;
+            }
+
+            let target = Self::relative(&dir, &self.output.join(target))
+                .display()
+                .to_string();
+
+            return Some(match rest {
+                "" => target,
+                rest => format!("{target}#{rest}"),
+            });
+        }
+
+        if Remote::is_url(target.trim()) || Path::new(target.trim()).is_absolute() {
+            return None;
+        }
+
+        let target = Self::relative(&dir, &self.input.join(target.trim()))
+            .display()
+            .to_string();
+
+        Some(match reference.split_once('#') {
+            Some(_) => format!("{target}#{pointer}"),
+            None => target,
+        })
+    }
+
+    /// `file` relative to `dir`.
+    fn relative(dir: &Path, file: &Path) -> PathBuf {
+        let common = dir
+            .components()
+            .zip(file.components())
+            .take_while(|(dir, file)| dir == file)
+            .count();
+
+        dir.components()
+            .skip(common)
+            .map(|_| Component::ParentDir)
+            .chain(file.components().skip(common))
+            .collect()
+    }
+
+    /// A file stem for `name`, e.g. `pets_{id}` for `/pets/{id}`.
+    fn stem(name: &str) -> String {
+        let stem = name
+            .trim_matches('/')
+            .chars()
+            .map(|char| match char {
+                '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+                char => char,
+            })
+            .collect::<String>();
+
+        if stem.is_empty() {
+            "root".into()
+        } else {
+            stem
+        }
+    }
+}
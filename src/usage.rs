@@ -0,0 +1,190 @@
+use crate::PigWarning;
+use serde_json::Value as Json;
+use std::{collections::HashSet, path::PathBuf};
+use tera::{
+    ast::{ExprVal, Node},
+    Context, Tera,
+};
+
+/// Cross-references the context with what templates did with it, to catch renamed tags or
+/// mistyped context paths silently producing incomplete output.
+#[derive(Debug)]
+pub enum Usage {}
+
+impl Usage {
+    /// Warns about operations and schemas of `components` whose name (`operationId`, or path)
+    /// appears in no generated file, and loops of templates over empty context values.
+    ///
+    /// This is a heuristic: names are used when they are a whole identifier of some file (ignoring
+    /// case and separators), paths when they appear verbatim.
+    pub fn check(
+        tera: &Tera,
+        context: &Context,
+        files: &[(String, PathBuf, String)],
+    ) -> Vec<PigWarning> {
+        let identifiers = files
+            .iter()
+            .flat_map(|(_, _, content)| Self::identifiers(content))
+            .collect::<HashSet<_>>();
+        let is_used = |name: &str| identifiers.contains(&Self::normalize(name));
+        let mut warnings = Vec::new();
+
+        for operation in context
+            .get("pig")
+            .and_then(|pig| pig.get("operations"))
+            .and_then(Json::as_array)
+            .into_iter()
+            .flatten()
+        {
+            let path = operation["path"].as_str().unwrap_or_default();
+            let used = match operation["operationId"].as_str() {
+                Some(name) => is_used(name),
+                None => files.iter().any(|(_, _, content)| content.contains(path)),
+            };
+
+            if !used {
+                let method = operation["method"].as_str().unwrap_or_default();
+                warnings.push(PigWarning::UnusedOperation(format!(
+                    "{} {path}",
+                    method.to_uppercase()
+                )));
+            }
+        }
+
+        for name in context
+            .get("components")
+            .and_then(|components| components.get("schemas"))
+            .and_then(Json::as_object)
+            .into_iter()
+            .flat_map(|schemas| schemas.keys())
+        {
+            if !is_used(name) {
+                warnings.push(PigWarning::UnusedSchema(name.clone()));
+            }
+        }
+
+        for template in tera.get_template_names() {
+            if let Ok(template) = tera.get_template(template) {
+                let mut loops = Vec::new();
+                Self::loops(&template.ast, &mut Vec::new(), &mut loops);
+
+                for container in loops {
+                    if Self::is_empty(context, &container) {
+                        warnings.push(PigWarning::EmptyLoop(template.name.clone(), container));
+                    }
+                }
+            }
+        }
+
+        warnings
+    }
+
+    /// The normalized identifiers of `content`, e.g. `listpets` and `pet` for `list_pets(&Pet)`.
+    fn identifiers(content: &str) -> impl Iterator<Item = String> + '_ {
+        content
+            .split(|char: char| !char.is_alphanumeric() && char != '_')
+            .filter(|identifier| !identifier.is_empty())
+            .map(Self::normalize)
+    }
+
+    /// Lowercase alphanumeric characters of `text`, e.g. `listpets` for `list_pets`.
+    fn normalize(text: &str) -> String {
+        text.chars()
+            .filter(|char| char.is_alphanumeric())
+            .flat_map(char::to_lowercase)
+            .collect()
+    }
+
+    /// The containers of unconditional loops over context values (not `locals`, without filters
+    /// nor `{% else %}`), e.g. `components.schemas`.
+    fn loops(nodes: &[Node], locals: &mut Vec<String>, found: &mut Vec<String>) {
+        for node in nodes {
+            match node {
+                Node::Set(_, set) => locals.push(set.key.clone()),
+                Node::Block(_, block, _) => Self::loops(&block.body, locals, found),
+                Node::FilterSection(_, section, _) => Self::loops(&section.body, locals, found),
+                Node::Forloop(_, forloop, _) => {
+                    if let ExprVal::Ident(ident) = &forloop.container.val {
+                        let root = ident.split(['.', '[']).next().unwrap_or_default();
+
+                        if forloop.container.filters.is_empty()
+                            && forloop.empty_body.is_none()
+                            && !locals.iter().any(|local| local == root)
+                        {
+                            found.push(ident.clone());
+                        }
+                    }
+
+                    let mut locals = locals.clone();
+                    locals.extend(forloop.key.clone());
+                    locals.push(forloop.value.clone());
+                    Self::loops(&forloop.body, &mut locals, found);
+                }
+                // Conditional loops may well iterate nothing
+                _ => {}
+            }
+        }
+    }
+
+    /// Whether `ident` is an empty array or object of `context`.
+    fn is_empty(context: &Context, ident: &str) -> bool {
+        let (root, pointer) = match ident.find(['.', '[']) {
+            Some(i) => (&ident[..i], ident[i..].trim_start_matches('.')),
+            None => (ident, ""),
+        };
+
+        match context
+            .get(root)
+            .and_then(|value| tera::dotted_pointer(value, pointer))
+        {
+            Some(Json::Array(values)) => values.is_empty(),
+            Some(Json::Object(values)) => values.is_empty(),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn check(files: &[&str]) -> Vec<PigWarning> {
+        let context = Context::from_value(json!({
+            "pig": { "operations": [
+                { "method": "get", "path": "/pets", "operationId": "list_pets" },
+                { "method": "get", "path": "/pets/{id}", "operationId": "Id" },
+                { "method": "delete", "path": "/pets/{id}" },
+            ] },
+            "components": { "schemas": { "Pet": {}, "Error": {} } },
+        }))
+        .unwrap();
+        let files = files
+            .iter()
+            .map(|content| (String::new(), PathBuf::new(), content.to_string()))
+            .collect::<Vec<_>>();
+
+        Usage::check(&Tera::default(), &context, &files)
+    }
+
+    #[test]
+    fn whole_identifiers() {
+        let warnings = check(&["fn listPets() -> PetStore", "struct Identity; // Error"]);
+        let warnings = warnings.iter().map(ToString::to_string).collect::<Vec<_>>();
+        let unused = |warning: PigWarning| warnings.contains(&warning.to_string());
+
+        assert_eq!(warnings.len(), 3);
+        assert!(unused(PigWarning::UnusedOperation("GET /pets/{id}".into())));
+        assert!(unused(PigWarning::UnusedOperation(
+            "DELETE /pets/{id}".into()
+        )));
+        assert!(unused(PigWarning::UnusedSchema("Pet".into())));
+    }
+
+    #[test]
+    fn paths() {
+        let warnings = check(&["list_pets Id Pet Error", "fetch('/pets/{id}')"]);
+
+        assert!(warnings.is_empty(), "{warnings:?}");
+    }
+}
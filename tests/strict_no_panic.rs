@@ -0,0 +1,225 @@
+//! Runs `pig --strict-no-panic` on fuzzed malformed specs: it must fail with errors, never panic
+//! (exit code 101) nor abort.
+
+use serde_json::{json, Value as Json};
+use std::{
+    fs::{create_dir_all, remove_dir_all, write},
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+const SEED: &str = r##"openapi: 3.0.3
+info:
+  title: Pets
+  version: 1.0.0
+paths:
+  /pets/{id}:
+    parameters:
+      - $ref: "#/components/parameters/Id"
+    get:
+      operationId: getPet
+      tags: [pets]
+      responses:
+        "200":
+          description: A pet
+          content:
+            application/json:
+              schema:
+                $ref: "common.yaml#/components/schemas/Pet"
+        default:
+          $ref: "#/components/responses/Error"
+components:
+  parameters:
+    Id:
+      name: id
+      in: path
+      required: true
+      schema: { type: string }
+  responses:
+    Error:
+      description: An error
+      content:
+        application/json:
+          schema: { $ref: "#/components/schemas/Error" }
+  schemas:
+    Error:
+      type: object
+      properties:
+        message: { type: string }
+"##;
+
+const COMMON: &str = r##"components:
+  schemas:
+    Pet:
+      type: object
+      required: [name]
+      properties:
+        name: { type: string }
+        parent: { $ref: "#/components/schemas/Pet" }
+"##;
+
+const TEMPLATE: &str = r#"{% for path, item in paths %}{{ path }}
+{% endfor %}{% for name, schema in components.schemas %}{{ name }}
+{% endfor %}"#;
+
+/// Tricky fixed cases, before random ones.
+const CASES: [&str; 12] = [
+    "",
+    "~",
+    "[]",
+    "openapi: 3.0.3",
+    "openapi: [3]\ninfo: 1\npaths: []",
+    "openapi: 3.0.3\ninfo: { title: T, version: '1' }\npaths: { $ref: '#' }",
+    "openapi: 3.0.3\ninfo: { title: T, version: '1' }\npaths: { $ref: '/' }",
+    "openapi: 3.0.3\ninfo: { title: T, version: '1' }\npaths: { $ref: '../../../../../../' }",
+    "openapi: 3.0.3\ninfo: { title: T, version: '1' }\npaths: { $ref: '#/a~2b/%zz' }",
+    "openapi: 3.0.3\ninfo: { title: T, version: '1' }\npaths: {}\nx: { $ref: '#/x' }",
+    "openapi: 3.1.0\ninfo: { title: T, version: '1' }\ncomponents: { schemas: { A: { type: [1, null] } } }",
+    "swagger: '2.0'\ninfo: { title: T, version: '1' }\npaths: { /a: { get: { responses: 1 } } }",
+];
+
+/// A xorshift generator, seeded for reproducible runs.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n.max(1) as u64) as usize
+    }
+
+    fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len())]
+    }
+}
+
+/// `spec` with some bytes replaced by YAML syntax, or truncated.
+fn garble(rng: &mut Rng, spec: &str) -> String {
+    let mut bytes = spec.as_bytes().to_vec();
+
+    if rng.below(3) == 0 {
+        bytes.truncate(rng.below(bytes.len()));
+    }
+
+    for _ in 0..=rng.below(8) {
+        if bytes.is_empty() {
+            break;
+        }
+
+        let i = rng.below(bytes.len());
+        bytes[i] = *rng.pick(b":{}[]#&*!|>'\"%@-\n\t ~/$");
+    }
+
+    String::from_utf8_lossy(&bytes).into()
+}
+
+/// `value` with some nodes replaced by values of the wrong type or bad references.
+fn mutate(rng: &mut Rng, value: &mut Json) {
+    let replacement = |rng: &mut Rng| match rng.below(8) {
+        0 => Json::Null,
+        1 => json!(-1),
+        2 => json!("#/components/schemas/Missing"),
+        3 => {
+            json!({ "$ref": *rng.pick(&["#", "#/", "#/paths", "common.yaml#/nope", "missing.yaml", "/", "#/components/schemas/Error/properties/message/~1"]) })
+        }
+        4 => json!([[[[[{}]]]]]),
+        5 => json!({ "type": ["string", 1, null], "items": true }),
+        6 => json!(true),
+        _ => json!({ "": { "$ref": "" } }),
+    };
+
+    match value {
+        Json::Object(object) if !object.is_empty() => {
+            let key = object.keys().nth(rng.below(object.len())).cloned();
+            let value = key.and_then(|key| object.get_mut(&key));
+
+            if let Some(value) = value {
+                if rng.below(3) == 0 {
+                    *value = replacement(rng);
+                } else {
+                    mutate(rng, value);
+                }
+            }
+        }
+        Json::Array(values) if !values.is_empty() => {
+            let i = rng.below(values.len());
+            mutate(rng, &mut values[i]);
+        }
+        value => *value = replacement(rng),
+    }
+}
+
+/// A project for `spec`, in a fresh directory.
+fn project(dir: &Path, spec: &str, validate: &str) -> PathBuf {
+    let _ = remove_dir_all(dir);
+    create_dir_all(dir.join("templates")).unwrap();
+    write(dir.join("openapi.yaml"), spec).unwrap();
+    write(dir.join("common.yaml"), COMMON).unwrap();
+    write(dir.join("templates/index.txt.jinja"), TEMPLATE).unwrap();
+    write(
+        dir.join("pig.yaml"),
+        format!("- api: openapi.yaml\n  in: templates\n  out: output\n  validate: {validate}\n"),
+    )
+    .unwrap();
+
+    dir.join("pig.yaml")
+}
+
+#[test]
+fn malformed_specs_never_panic() {
+    let root = std::env::temp_dir().join(format!("pig-strict-no-panic-{}", std::process::id()));
+    let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+    let seed = serde_yaml::from_str::<Json>(SEED).unwrap();
+    let specs = CASES
+        .iter()
+        .map(|case| case.to_string())
+        .chain((0..200).map(|i| {
+            if i % 2 == 0 {
+                garble(&mut rng, SEED)
+            } else {
+                let mut spec = seed.clone();
+                for _ in 0..=rng.below(3) {
+                    mutate(&mut rng, &mut spec);
+                }
+                serde_yaml::to_string(&spec).unwrap()
+            }
+        }))
+        .collect::<Vec<_>>();
+    let mut panics = Vec::new();
+
+    for (i, spec) in specs.iter().enumerate() {
+        let validate = ["strict", "lenient", "off"][i % 3];
+        let config = project(&root.join("project"), spec, validate);
+        let output = Command::new(env!("CARGO_BIN_EXE_pig"))
+            .args([
+                "--strict-no-panic",
+                "--offline",
+                "--plain",
+                "--color",
+                "never",
+            ])
+            .arg(&config)
+            .env("XDG_CACHE_HOME", root.join("cache"))
+            .env("XDG_CONFIG_HOME", root.join("config"))
+            .output()
+            .unwrap();
+
+        // Errors exit with 1, panics with 101, aborts without a code
+        if !matches!(output.status.code(), Some(0 | 1)) {
+            panics.push(format!(
+                "case {i} (validate: {validate}), {:?}:\n{spec}\n{}{}",
+                output.status,
+                String::from_utf8_lossy(&output.stdout),
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+    }
+
+    let _ = remove_dir_all(&root);
+    assert!(panics.is_empty(), "{}", panics.join("\n\n"));
+}